@@ -0,0 +1,142 @@
+use vte::{Params, Parser, Perform};
+
+/// Stateful decoder that turns raw PTY bytes into plain-text lines.
+///
+/// Escape sequences are dropped, `\r` rewinds to the start of the current line
+/// (so spinner frames and progress bars overwrite instead of piling up), and
+/// sequences split across reads are carried over to the next `feed` call.
+pub struct LineDecoder {
+    parser: Parser,
+    line: LineBuilder,
+}
+
+#[derive(Default)]
+struct LineBuilder {
+    chars: Vec<char>,
+    col: usize,
+    completed: Vec<String>,
+}
+
+impl LineDecoder {
+    pub fn new() -> Self {
+        Self {
+            parser: Parser::new(),
+            line: LineBuilder::default(),
+        }
+    }
+
+    /// Decoder continuing an unterminated line (e.g. restored from a snapshot).
+    pub fn with_partial(line: &str) -> Self {
+        let chars: Vec<char> = line.chars().collect();
+        Self {
+            parser: Parser::new(),
+            line: LineBuilder {
+                col: chars.len(),
+                chars,
+                completed: Vec::new(),
+            },
+        }
+    }
+
+    /// Feed raw bytes and return the lines completed by a `\n`.
+    pub fn feed(&mut self, data: &[u8]) -> Vec<String> {
+        self.parser.advance(&mut self.line, data);
+        std::mem::take(&mut self.line.completed)
+    }
+
+    /// The line currently being built (not yet terminated by `\n`).
+    pub fn partial(&self) -> String {
+        self.line.chars.iter().collect()
+    }
+}
+
+impl LineBuilder {
+    fn put(&mut self, c: char) {
+        if self.col < self.chars.len() {
+            self.chars[self.col] = c;
+        } else {
+            self.chars.push(c);
+        }
+        self.col += 1;
+    }
+}
+
+impl Perform for LineBuilder {
+    fn print(&mut self, c: char) {
+        self.put(c);
+    }
+
+    fn execute(&mut self, byte: u8) {
+        match byte {
+            b'\n' => {
+                self.completed.push(self.chars.iter().collect());
+                self.chars.clear();
+                self.col = 0;
+            }
+            b'\r' => self.col = 0,
+            b'\t' => {
+                let next = (self.col / 8 + 1) * 8;
+                while self.col < next {
+                    self.put(' ');
+                }
+            }
+            0x08 => self.col = self.col.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    fn csi_dispatch(&mut self, params: &Params, _intermediates: &[u8], _ignore: bool, c: char) {
+        // Erase in Line: keep the plain-text line in sync with redraws
+        if c == 'K' {
+            let mode = params.iter().next().and_then(|p| p.first().copied()).unwrap_or(0);
+            match mode {
+                0 => self.chars.truncate(self.col),
+                2 => {
+                    self.chars.clear();
+                    self.col = 0;
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_color_codes() {
+        let mut d = LineDecoder::new();
+        d.feed(b"\x1b[31mred\x1b[0m text");
+        assert_eq!(d.partial(), "red text");
+    }
+
+    #[test]
+    fn carriage_return_overwrites() {
+        let mut d = LineDecoder::new();
+        let lines = d.feed(b"50%\r75%\r100%\n");
+        assert_eq!(lines, vec!["100%"]);
+    }
+
+    #[test]
+    fn crlf_keeps_line() {
+        let mut d = LineDecoder::new();
+        assert_eq!(d.feed(b"hello\r\nworld\r\n"), vec!["hello", "world"]);
+    }
+
+    #[test]
+    fn escape_split_across_chunks() {
+        let mut d = LineDecoder::new();
+        assert!(d.feed(b"ok \x1b[3").is_empty());
+        assert_eq!(d.feed(b"2mgreen\n"), vec!["ok green"]);
+    }
+
+    #[test]
+    fn erase_line_clears_spinner() {
+        let mut d = LineDecoder::new();
+        d.feed(b"\xe2\xa0\x8b Working");
+        d.feed(b"\r\x1b[2KDone");
+        assert_eq!(d.partial(), "Done");
+    }
+}
@@ -1,13 +1,25 @@
+use crate::backend::Backend;
+use crate::bookmarks::{Bookmark, Bookmarks};
 use crate::br_poller::{self, BrTaskInfo};
 use crate::config::AppConfig;
 use crate::dir_tree::DirTree;
+use crate::export;
 use crate::file_preview::FilePreview;
 use crate::input_history::InputHistory;
+use crate::key_encoder;
+use crate::launch::{self, LaunchSpec};
+use crate::log_filter::LogFilter;
+use crate::log_pager::LogHistory;
+use crate::log_search::{Hit, LogSearch, SearchView};
 use crate::log_store::LogStore;
-use crate::needs_input::NeedsInputDetector;
-use crate::persistence;
-use crate::pty_manager::PtyHandle;
+use crate::needs_input::{self, Choice};
+use crate::protocol::{Event as EngineEvent, Request};
+use crate::recording;
+use crate::replay::{Cast, ReplayPlayer};
+use crate::scrollback::Scrollback;
+use crate::selection::{self, Selection, SelectionKind};
 use crate::session::{Session, SessionStatus};
+use crate::terminal::{TerminalStore, DEFAULT_COLS, DEFAULT_ROWS};
 use crate::ui;
 
 use ftui_core::event::{Event, KeyCode, KeyEvent, KeyEventKind, Modifiers, PasteEvent};
@@ -17,10 +29,14 @@ use ftui_render::frame::Frame;
 use ftui_runtime::subscription::Every;
 use ftui_runtime::{Cmd, Model};
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::time::{Duration, Instant};
 use uuid::Uuid;
 
+/// Shortest time between searches of the unified log while output arrives:
+/// unlike a session's log it is rebuilt and searched in full each time.
+const UNIFIED_SEARCH_INTERVAL: Duration = Duration::from_millis(500);
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Panel {
     SessionList,
@@ -56,13 +72,18 @@ impl Panel {
 pub enum LogMode {
     Individual,
     Unified,
+    /// Every session's terminal tiled side by side
+    Grid,
 }
 
 pub enum Msg {
     Key(KeyEvent),
     Paste(PasteEvent),
-    PtyPollTick,
+    Resize,
+    EngineTick,
     BrPollTick,
+    /// A recording read in the background for the replay viewer
+    ReplayLoaded(Uuid, std::io::Result<Cast>),
     Noop,
 }
 
@@ -71,6 +92,7 @@ impl From<Event> for Msg {
         match event {
             Event::Key(k) => Msg::Key(k),
             Event::Paste(p) => Msg::Paste(p),
+            Event::Resize { .. } => Msg::Resize,
             _ => Msg::Noop,
         }
     }
@@ -83,73 +105,301 @@ pub struct AppState {
     pub input_text: String,
     pub config: AppConfig,
     pub log_store: LogStore,
+    pub terminals: TerminalStore,
     pub log_mode: LogMode,
+    /// Scroll position of each session's individual log and of the unified log
+    pub log_scroll: HashMap<Uuid, Scrollback>,
+    pub unified_scroll: Scrollback,
+    /// Unified log shows when each line arrived
+    pub show_timestamps: bool,
+    /// Narrows the unified log by session, status and line content
+    pub log_filter: Option<LogFilter>,
+    /// Unified log lines, updated as output arrives rather than every frame
+    pub unified_log: std::cell::RefCell<ui::log_panel::UnifiedLog>,
+    /// Log panel search; kept after its prompt closes for n/N and highlighting
+    pub search: Option<LogSearch>,
+    /// Output arrived since the search last ran, and when that was
+    search_stale: bool,
+    searched_at: Instant,
+    /// Lines dropped from memory, paged in from each session's log file
+    pub log_history: HashMap<Uuid, LogHistory>,
+    /// Log panel takes the whole body area
+    pub log_zoomed: bool,
+    /// Center panel shows the active session's details instead of the file preview
+    pub show_details: bool,
     pub dir_tree: DirTree,
     pub file_preview: FilePreview,
     pub br_tasks: HashMap<Uuid, BrTaskInfo>,
     pub input_history: InputHistory,
-    // PTY handles (runtime-only, not serialized)
-    pty_handles: HashMap<Uuid, PtyHandle>,
-    // needs_input detection
-    needs_input_detector: NeedsInputDetector,
-    last_output_at: HashMap<Uuid, Instant>,
+    /// Raw key passthrough to the active session's PTY
+    pub passthrough: bool,
+    /// Lost the connection to `deck server`
+    pub disconnected: bool,
+    /// Recording being played back in the log panel
+    pub replay: Option<ReplayPlayer>,
+    /// One-off message in the status bar, cleared by the next key
+    pub notice: Option<String>,
+    /// Keyboard selection in the log panel or file preview, copied with OSC 52
+    pub visual: Option<(Panel, Selection)>,
+    /// Each session's bookmarks, loaded from the file next to its log
+    pub bookmarks: HashMap<Uuid, Bookmarks>,
+    /// Bookmark picker open, with the active session's bookmark selected in it
+    pub bookmark_pick: Option<usize>,
+    // Session engine (in-process or the server); sessions, logs and terminals mirror its events
+    backend: Backend,
+    // Created by this client; selected once the engine reports it
+    pending_select: Option<Uuid>,
+    // PTY sizing: last rendered frame size and the size last requested for each PTY
+    frame_size: std::cell::Cell<(u16, u16)>,
+    requested_sizes: HashMap<Uuid, (u16, u16)>,
+    // Process groups left running by a previous deck (session id, pgid) awaiting a decision
+    orphans: Vec<(Uuid, u32)>,
+    // Quit dialog: live sessions must be stopped or left running
+    confirming_quit: bool,
+    // Search prompt open in the input bar
+    search_prompt: bool,
+    // Filter bar open in the input bar, with the expression being edited,
+    // why it doesn't parse and the named filter picked with Up/Down
+    filter_prompt: bool,
+    filter_text: String,
+    filter_error: Option<String>,
+    filter_pick: Option<usize>,
     // Session creation dialog state
     creating_session: bool,
     create_step: CreateStep,
     create_name: String,
     create_path: String,
+    create_command: String,
+    create_env: String,
+    create_profile: String,
+    create_error: Option<&'static str>,
     // Rename dialog state
     renaming: bool,
     rename_text: String,
+    // Transcript export prompt: destination of the active session's transcript
+    exporting: bool,
+    export_path: String,
+    // The typed path names an existing file: y overwrites it
+    export_overwrite: bool,
+    // Note prompt for a bookmark being added to a session
+    new_bookmark: Option<(Uuid, Bookmark)>,
+    // Line of the bookmark last jumped to with [ and ]
+    bookmark_cursor: Option<usize>,
 }
 
 #[derive(PartialEq)]
 enum CreateStep {
     Name,
     Path,
+    Command,
+    Env,
+    Profile,
 }
 
 impl AppState {
-    pub fn new(config: AppConfig) -> Self {
-        let sessions = persistence::load_sessions(&config.sessions_file_path);
-        let dir_tree = if let Some(session) = sessions.first() {
-            DirTree::new(&session.root_path)
-        } else {
-            DirTree::empty()
-        };
-        Self {
-            sessions,
+    pub fn new(config: AppConfig, backend: Backend) -> Self {
+        let mut state = Self {
+            sessions: Vec::new(),
             active_session: 0,
             active_panel: Panel::SessionList,
             input_text: String::new(),
             config,
             log_store: LogStore::new(),
+            terminals: TerminalStore::new(),
             log_mode: LogMode::Individual,
-            dir_tree,
+            log_scroll: HashMap::new(),
+            unified_scroll: Scrollback::default(),
+            show_timestamps: false,
+            log_filter: None,
+            unified_log: Default::default(),
+            search: None,
+            search_stale: false,
+            searched_at: Instant::now(),
+            log_history: HashMap::new(),
+            log_zoomed: false,
+            show_details: false,
+            dir_tree: DirTree::empty(),
             file_preview: FilePreview::new(),
             br_tasks: HashMap::new(),
             input_history: InputHistory::new(),
-            pty_handles: HashMap::new(),
-            needs_input_detector: NeedsInputDetector::new(),
-            last_output_at: HashMap::new(),
+            passthrough: false,
+            disconnected: false,
+            replay: None,
+            notice: None,
+            visual: None,
+            bookmarks: HashMap::new(),
+            bookmark_pick: None,
+            backend,
+            pending_select: None,
+            frame_size: std::cell::Cell::new((0, 0)),
+            requested_sizes: HashMap::new(),
+            orphans: Vec::new(),
+            confirming_quit: false,
+            search_prompt: false,
+            filter_prompt: false,
+            filter_text: String::new(),
+            filter_error: None,
+            filter_pick: None,
             creating_session: false,
             create_step: CreateStep::Name,
             create_name: String::new(),
             create_path: String::new(),
+            create_command: String::new(),
+            create_env: String::new(),
+            create_profile: String::new(),
+            create_error: None,
             renaming: false,
             rename_text: String::new(),
+            exporting: false,
+            export_path: String::new(),
+            export_overwrite: false,
+            new_bookmark: None,
+            bookmark_cursor: None,
+        };
+        // The initial snapshot (immediate for the local engine)
+        state.pump_events();
+        state
+    }
+
+    /// Whether the session has a live PTY in the engine.
+    fn is_live(session: &Session) -> bool {
+        session.status == SessionStatus::Running || session.status == SessionStatus::NeedsInput
+    }
+
+    fn active_id(&self) -> Option<Uuid> {
+        self.sessions.get(self.active_session).map(|s| s.id)
+    }
+
+    /// Send a request and apply whatever the engine answered so far.
+    fn request(&mut self, request: Request) {
+        self.backend.send(request);
+        self.pump_events();
+    }
+
+    /// Apply pending engine events; returns whether any session output arrived.
+    fn pump_events(&mut self) -> bool {
+        let mut output = false;
+        for event in self.backend.poll() {
+            output |= matches!(event, EngineEvent::Output { .. });
+            self.apply_event(event);
+        }
+        if !self.backend.is_connected() {
+            self.disconnected = true;
+            self.passthrough = false;
+        }
+        output
+    }
+
+    fn apply_event(&mut self, event: EngineEvent) {
+        match event {
+            EngineEvent::Snapshot {
+                sessions,
+                orphans,
+                logs,
+                terminals,
+            } => {
+                self.log_store = LogStore::new();
+                self.log_history.clear();
+                self.terminals = TerminalStore::new();
+                for log in logs {
+                    self.log_store
+                        .restore(log.id, log.first_line, log.lines, log.arrived);
+                }
+                for term in terminals {
+                    self.terminals
+                        .restore(term.id, term.rows, term.cols, &term.state);
+                }
+                self.orphans = orphans;
+                self.set_sessions(sessions);
+                self.dir_tree = match self.sessions.get(self.active_session) {
+                    Some(session) => DirTree::new(&session.root_path),
+                    None => DirTree::empty(),
+                };
+            }
+            EngineEvent::Sessions { sessions } => self.set_sessions(sessions),
+            EngineEvent::Output { id, data } => {
+                self.log_store.append(id, &data);
+                self.terminals.process(id, &data);
+            }
+            EngineEvent::Resized { id, cols, rows } => {
+                self.terminals.resize(id, rows, cols);
+            }
+            EngineEvent::Orphans { orphans } => self.orphans = orphans,
         }
     }
 
-    fn save(&self) {
-        let _ = persistence::save_sessions(&self.config.sessions_file_path, &self.sessions);
+    /// Replace the mirrored session list, keeping the selection on the same session.
+    fn set_sessions(&mut self, sessions: Vec<Session>) {
+        let previous = self.active_id();
+        self.sessions = sessions;
+
+        // Drop local state of deleted sessions
+        let ids: HashSet<Uuid> = self.sessions.iter().map(|s| s.id).collect();
+        self.log_store.retain(|id| ids.contains(id));
+        self.terminals.retain(|id| ids.contains(id));
+        self.requested_sizes.retain(|id, _| ids.contains(id));
+        self.log_scroll.retain(|id, _| ids.contains(id));
+        self.log_history.retain(|id, _| ids.contains(id));
+        self.br_tasks.retain(|id, _| ids.contains(id));
+        self.bookmarks.retain(|id, _| ids.contains(id));
+        for session in &self.sessions {
+            self.bookmarks
+                .entry(session.id)
+                .or_insert_with(|| Bookmarks::load(&session.log_path));
+        }
+
+        let wanted = self
+            .pending_select
+            .filter(|id| self.sessions.iter().any(|s| s.id == *id))
+            .or(previous);
+        if wanted == self.pending_select {
+            self.pending_select = None;
+        }
+        self.active_session = wanted
+            .and_then(|id| self.sessions.iter().position(|s| s.id == id))
+            .unwrap_or(self.active_session)
+            .min(self.sessions.len().saturating_sub(1));
+        if self.active_id() != previous {
+            self.sync_dir_tree();
+        }
+
+        // Passthrough ends with the active session's PTY
+        if self.passthrough && !self.sessions.get(self.active_session).is_some_and(Self::is_live) {
+            self.passthrough = false;
+        }
     }
 
     fn handle_key(&mut self, key: KeyEvent) -> Cmd<Msg> {
+        // Passthrough: every key except the reserved exit key goes to the PTY
+        if self.passthrough {
+            return self.handle_passthrough_key(key);
+        }
+
         // Only process Press events
         if key.kind != KeyEventKind::Press {
             return Cmd::None;
         }
+        self.notice = None;
+
+        // Quit and orphan dialogs
+        if self.confirming_quit {
+            return self.handle_quit_dialog(key);
+        }
+        if !self.orphans.is_empty() {
+            return self.handle_orphan_dialog(key);
+        }
+
+        if self.search_prompt {
+            return self.handle_search_prompt_key(key);
+        }
+        if self.filter_prompt {
+            return self.handle_filter_prompt_key(key);
+        }
+
+        // Replay viewer takes the keys until it is closed
+        if self.replay.is_some() {
+            return self.handle_replay_key(key);
+        }
 
         // Session creation dialog
         if self.creating_session {
@@ -160,47 +410,59 @@ impl AppState {
         if self.renaming {
             return self.handle_rename_dialog(key);
         }
+        if self.exporting {
+            return self.handle_export_prompt(key);
+        }
+        if self.visual.is_some() {
+            return self.handle_visual_key(key);
+        }
+        if self.new_bookmark.is_some() {
+            return self.handle_bookmark_prompt(key);
+        }
+        if self.bookmark_pick.is_some() {
+            return self.handle_bookmark_picker(key);
+        }
 
         // Global keys
         match (key.code, key.modifiers) {
             (KeyCode::Char('c'), m) if m.contains(Modifiers::CTRL) => {
                 // Send SIGINT to active session's PTY if running, otherwise quit
-                if let Some(session) = self.sessions.get(self.active_session) {
-                    if session.status == SessionStatus::Running
-                        || session.status == SessionStatus::NeedsInput
-                    {
-                        if let Some(handle) = self.pty_handles.get_mut(&session.id) {
-                            let _ = handle.send_sigint();
-                            return Cmd::None;
-                        }
-                    }
+                if let Some(session) = self.sessions.get(self.active_session)
+                    && Self::is_live(session)
+                {
+                    let id = session.id;
+                    self.request(Request::Interrupt { id });
+                    return Cmd::None;
                 }
-                self.save();
-                return Cmd::Quit;
+                return self.request_quit();
             }
             (KeyCode::Char('q'), _) if self.active_panel != Panel::Input => {
-                self.save();
-                return Cmd::Quit;
+                return self.request_quit();
             }
             // Panel navigation: Ctrl+h/j/k/l
             (KeyCode::Char('h'), m) if m.contains(Modifiers::CTRL) => {
-                self.active_panel = self.active_panel.prev();
+                self.cycle_focus(false);
                 return Cmd::None;
             }
             (KeyCode::Char('l'), m) if m.contains(Modifiers::CTRL) => {
-                self.active_panel = self.active_panel.next();
+                self.cycle_focus(true);
                 return Cmd::None;
             }
             (KeyCode::Char('j'), m) if m.contains(Modifiers::CTRL) => {
-                self.active_panel = self.active_panel.next();
+                self.cycle_focus(true);
                 return Cmd::None;
             }
             (KeyCode::Char('k'), m) if m.contains(Modifiers::CTRL) => {
-                self.active_panel = self.active_panel.prev();
+                self.cycle_focus(false);
                 return Cmd::None;
             }
             (KeyCode::Tab, _) => {
-                self.active_panel = self.active_panel.next();
+                self.cycle_focus(true);
+                return Cmd::None;
+            }
+            // Quick reply to the session waiting for input: Alt+1..9
+            (KeyCode::Char(c @ '1'..='9'), m) if m.contains(Modifiers::ALT) => {
+                self.quick_reply(c as usize - '1' as usize);
                 return Cmd::None;
             }
             _ => {}
@@ -216,6 +478,17 @@ impl AppState {
         }
     }
 
+    /// Move focus to the next or previous panel. While the log is zoomed only
+    /// it and the input bar are on screen, so focus stays between those two.
+    fn cycle_focus(&mut self, forward: bool) {
+        self.active_panel = match (self.log_zoomed, self.active_panel) {
+            (true, Panel::Log) => Panel::Input,
+            (true, _) => Panel::Log,
+            (false, panel) if forward => panel.next(),
+            (false, panel) => panel.prev(),
+        };
+    }
+
     fn sync_dir_tree(&mut self) {
         if let Some(session) = self.sessions.get(self.active_session) {
             self.dir_tree.set_root(&session.root_path);
@@ -242,19 +515,23 @@ impl AppState {
                 self.create_step = CreateStep::Name;
                 self.create_name.clear();
                 self.create_path.clear();
+                self.create_command.clear();
+                self.create_env.clear();
+                self.create_profile.clear();
+                self.create_error = None;
             }
             KeyCode::Char('d') => {
-                if !self.sessions.is_empty() {
-                    let session_id = self.sessions[self.active_session].id;
-                    self.pty_handles.remove(&session_id);
-                    self.last_output_at.remove(&session_id);
-                    self.br_tasks.remove(&session_id);
-                    self.sessions.remove(self.active_session);
-                    if self.active_session >= self.sessions.len() && self.active_session > 0 {
-                        self.active_session -= 1;
-                    }
-                    self.sync_dir_tree();
-                    self.save();
+                // The engine also stops the agent of a deleted session
+                if let Some(id) = self.active_id() {
+                    self.request(Request::DeleteSession { id });
+                }
+            }
+            KeyCode::Char('i') => {
+                self.show_details = !self.show_details;
+            }
+            KeyCode::Char('s') => {
+                if let Some(id) = self.active_id() {
+                    self.request(Request::Stop { id });
                 }
             }
             KeyCode::Char('r') => {
@@ -265,20 +542,15 @@ impl AppState {
             }
             KeyCode::Char('m') => {
                 // Manual NeedsInput toggle
-                if let Some(session) = self.sessions.get_mut(self.active_session) {
-                    match session.status {
-                        SessionStatus::Running => {
-                            let _ = session.transition_to(SessionStatus::NeedsInput);
-                            self.save();
-                        }
-                        SessionStatus::NeedsInput => {
-                            let _ = session.transition_to(SessionStatus::Running);
-                            // Reset timeout timer on manual resume
-                            self.last_output_at.insert(session.id, Instant::now());
-                            self.save();
-                        }
-                        _ => {}
-                    }
+                if let Some(id) = self.active_id() {
+                    self.request(Request::ToggleNeedsInput { id });
+                }
+            }
+            KeyCode::Char('p') => return self.open_replay(),
+            KeyCode::Char('e') => {
+                if let Some(session) = self.sessions.get(self.active_session) {
+                    self.exporting = true;
+                    self.export_path = export::default_path(session).display().to_string();
                 }
             }
             _ => {}
@@ -286,6 +558,59 @@ impl AppState {
         Cmd::None
     }
 
+    /// Play back the active session's recording in the log panel. The file is
+    /// read off the UI thread; a long recording can be large.
+    fn open_replay(&mut self) -> Cmd<Msg> {
+        let Some(session) = self.sessions.get(self.active_session) else {
+            return Cmd::None;
+        };
+        let id = session.id;
+        let path = recording::cast_path(&session.log_path);
+        Cmd::task(move || Msg::ReplayLoaded(id, Cast::load(&path)))
+    }
+
+    fn handle_replay_loaded(&mut self, id: Uuid, cast: std::io::Result<Cast>) -> Cmd<Msg> {
+        // The user moved on to another session while it was loading
+        if self.active_id() != Some(id) {
+            return Cmd::None;
+        }
+        match cast {
+            Ok(cast) => self.replay = Some(ReplayPlayer::new(id, cast)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                self.notice = Some("録画がありません (設定の record_sessions を有効にしてください)".to_string());
+            }
+            Err(e) => self.notice = Some(format!("録画を開けません: {}", e)),
+        }
+        Cmd::None
+    }
+
+    fn handle_replay_key(&mut self, key: KeyEvent) -> Cmd<Msg> {
+        let Some(replay) = self.replay.as_mut() else {
+            return Cmd::None;
+        };
+        match key.code {
+            KeyCode::Escape | KeyCode::Char('q') => self.replay = None,
+            KeyCode::Char(' ') => replay.toggle_play(),
+            KeyCode::Left => replay.seek_by(-5.0),
+            KeyCode::Right => replay.seek_by(5.0),
+            KeyCode::Down => replay.seek_by(-60.0),
+            KeyCode::Up => replay.seek_by(60.0),
+            KeyCode::Home => replay.seek(0.0),
+            KeyCode::End => replay.seek(replay.duration()),
+            KeyCode::Char(c @ '0'..='9') => {
+                replay.seek_fraction(f64::from(c as u8 - b'0') / 10.0);
+            }
+            KeyCode::Char('[') => replay.prev_instruction(),
+            KeyCode::Char(']') => replay.next_instruction(),
+            KeyCode::Char('n') => replay.next_state_change(),
+            KeyCode::Char('N') => replay.prev_state_change(),
+            KeyCode::Char('+') | KeyCode::Char('=') => replay.faster(),
+            KeyCode::Char('-') => replay.slower(),
+            _ => {}
+        }
+        Cmd::None
+    }
+
     fn handle_dir_tree_key(&mut self, key: KeyEvent) -> Cmd<Msg> {
         match key.code {
             KeyCode::Up => {
@@ -300,6 +625,7 @@ impl AppState {
                 } else if let Some(path) = self.dir_tree.selected_path() {
                     let path = path.to_path_buf();
                     self.file_preview.load(&path);
+                    self.show_details = false;
                 }
             }
             KeyCode::Char('h') => {
@@ -318,6 +644,10 @@ impl AppState {
             KeyCode::Down => {
                 self.file_preview.scroll_down(20);
             }
+            KeyCode::Char('v') if key.modifiers.contains(Modifiers::CTRL) => {
+                self.start_visual(Panel::FilePreview, SelectionKind::Block);
+            }
+            KeyCode::Char('v') => self.start_visual(Panel::FilePreview, SelectionKind::Line),
             KeyCode::Char('e') => {
                 if let Some(path) = &self.file_preview.path {
                     let _ = std::process::Command::new("open")
@@ -332,350 +662,1187 @@ impl AppState {
 
     fn handle_log_key(&mut self, key: KeyEvent) -> Cmd<Msg> {
         match key.code {
+            KeyCode::Char('v') if key.modifiers.contains(Modifiers::CTRL) => {
+                self.start_visual(Panel::Log, SelectionKind::Block);
+            }
+            KeyCode::Char('v') => self.start_visual(Panel::Log, SelectionKind::Line),
             KeyCode::Char('t') => {
                 self.log_mode = match self.log_mode {
                     LogMode::Individual => LogMode::Unified,
-                    LogMode::Unified => LogMode::Individual,
+                    LogMode::Unified | LogMode::Grid => LogMode::Individual,
                 };
+                self.sync_pty_sizes();
             }
-            _ => {}
-        }
-        Cmd::None
-    }
-
-    fn handle_input_key(&mut self, key: KeyEvent) -> Cmd<Msg> {
-        match key.code {
-            KeyCode::Char(c) => {
-                self.input_text.push(c);
+            KeyCode::Char('g') => {
+                self.log_mode = match self.log_mode {
+                    LogMode::Grid => LogMode::Individual,
+                    _ => LogMode::Grid,
+                };
+                self.sync_pty_sizes();
             }
-            KeyCode::Backspace => {
-                self.input_text.pop();
+            KeyCode::Char('z') => {
+                self.log_zoomed = !self.log_zoomed;
+                self.sync_pty_sizes();
             }
-            KeyCode::Up => {
-                if let Some(text) = self.input_history.up() {
-                    self.input_text = text.to_string();
+            KeyCode::Char('F') => {
+                // Filters apply to the unified log
+                if self.log_mode != LogMode::Unified {
+                    self.log_mode = LogMode::Unified;
+                    self.sync_pty_sizes();
                 }
+                self.filter_prompt = true;
+                self.filter_pick = None;
             }
-            KeyCode::Down => {
-                match self.input_history.down() {
-                    Some(text) => self.input_text = text.to_string(),
-                    None => self.input_text.clear(),
-                }
+            KeyCode::Char('T') if self.log_mode == LogMode::Unified => {
+                self.show_timestamps = !self.show_timestamps;
+                self.refresh_search();
             }
-            KeyCode::Enter => {
-                if !self.input_text.is_empty() && !self.sessions.is_empty() {
-                    let session_idx = self.active_session;
-                    let session_id = self.sessions[session_idx].id;
-                    let input = self.input_text.clone();
-                    self.input_text.clear();
-                    self.input_history.push(input.clone());
-
-                    // Spawn PTY if not running
-                    if !self.pty_handles.contains_key(&session_id) {
-                        let root_path = self.sessions[session_idx].root_path.clone();
-
-                        // Validate directory still exists
-                        if !root_path.is_dir() {
-                            self.log_store.append(
-                                session_id,
-                                format!(
-                                    "エラー: ディレクトリが存在しません: {}\n",
-                                    root_path.display()
-                                )
-                                .as_bytes(),
-                            );
-                            let session = &mut self.sessions[session_idx];
-                            let _ = session.transition_to(SessionStatus::Running);
-                            let _ = session.transition_to(SessionStatus::Failed);
-                            self.save();
-                            return Cmd::None;
-                        }
-
-                        match PtyHandle::spawn(&root_path, 80, 24) {
-                            Ok(handle) => {
-                                let pid = handle.process_id();
-                                self.pty_handles.insert(session_id, handle);
-                                self.last_output_at.insert(session_id, Instant::now());
-                                let session = &mut self.sessions[session_idx];
-                                let _ = session.transition_to(SessionStatus::Running);
-                                session.pty_pid = pid;
-                                session.instruction = Some(input.clone());
-                                let _ = persistence::write_log_header(
-                                    &session.log_path,
-                                    session,
-                                );
-                                self.save();
-                            }
-                            Err(e) => {
-                                let err_msg = format!("{}", e);
-                                let msg = if err_msg.contains("No such file")
-                                    || err_msg.contains("not found")
-                                {
-                                    "エラー: 'claude' コマンドが見つかりません。Claude Code をインストールしてください。\n"
-                                        .to_string()
-                                } else {
-                                    format!("エラー: セッション開始に失敗: {}\n", e)
-                                };
-                                self.log_store.append(session_id, msg.as_bytes());
-                                let session = &mut self.sessions[session_idx];
-                                let _ = session.transition_to(SessionStatus::Running);
-                                let _ = session.transition_to(SessionStatus::Failed);
-                                self.save();
-                                return Cmd::None;
-                            }
-                        }
-                    }
-
-                    // If session was NeedsInput, transition back to Running
-                    if self.sessions[session_idx].status == SessionStatus::NeedsInput {
-                        let _ = self.sessions[session_idx].transition_to(SessionStatus::Running);
-                        self.last_output_at.insert(session_id, Instant::now());
-                        self.save();
-                    }
-
-                    // Send input to PTY
-                    if let Some(handle) = self.pty_handles.get_mut(&session_id) {
-                        let _ = handle.send_input(&format!("{}\n", input));
-                    }
+            KeyCode::PageUp => {
+                self.scroll_log(|s, first, end, height| {
+                    s.scroll_by(-(height.saturating_sub(1).max(1) as isize), first, end, height)
+                });
+            }
+            KeyCode::PageDown => {
+                self.scroll_log(|s, first, end, height| {
+                    s.scroll_by(height.saturating_sub(1).max(1) as isize, first, end, height)
+                });
+            }
+            KeyCode::Up => self.scroll_log(|s, first, end, height| s.scroll_by(-1, first, end, height)),
+            KeyCode::Down => self.scroll_log(|s, first, end, height| s.scroll_by(1, first, end, height)),
+            KeyCode::Home => self.scroll_log(|s, first, _, _| s.scroll_to_top(first)),
+            KeyCode::End => self.scroll_log(|s, _, _, _| s.follow()),
+            KeyCode::Char('f') => self.scroll_log(Scrollback::toggle_follow),
+            KeyCode::Char('/') if self.log_mode != LogMode::Grid => {
+                // A new query keeps the previous options
+                let search = self.search.get_or_insert_with(LogSearch::new);
+                search.query.clear();
+                search.compile();
+                self.search_prompt = true;
+                self.sync_log_history();
+                self.refresh_search();
+            }
+            KeyCode::Char('b') => self.start_bookmark(),
+            KeyCode::Char('B') => {
+                if self.active_id().and_then(|id| self.bookmarks.get(&id)).is_some_and(|b| !b.items.is_empty()) {
+                    self.bookmark_pick = Some(0);
+                } else {
+                    self.notice = Some("ブックマークはありません".to_string());
                 }
             }
-            KeyCode::Escape => {
-                self.active_panel = Panel::SessionList;
+            KeyCode::Char(']') => self.step_bookmark(true),
+            KeyCode::Char('[') => self.step_bookmark(false),
+            KeyCode::Char('n') if self.search.is_some() => self.step_search(LogSearch::next),
+            KeyCode::Char('N') if self.search.is_some() => self.step_search(LogSearch::prev),
+            KeyCode::Escape => self.search = None,
+            KeyCode::Char('i') => {
+                // Passthrough needs a live PTY; the terminal is shown in individual mode
+                if let Some(session) = self.sessions.get(self.active_session)
+                    && Self::is_live(session)
+                {
+                    self.log_scroll.remove(&session.id);
+                    self.passthrough = true;
+                    self.log_mode = LogMode::Individual;
+                    self.sync_pty_sizes();
+                }
             }
             _ => {}
         }
         Cmd::None
     }
 
-    fn handle_pty_poll(&mut self) -> Cmd<Msg> {
-        // Collect info about active sessions (Running + NeedsInput) to avoid borrow conflicts
-        let active: Vec<(Uuid, std::path::PathBuf, SessionStatus)> = self
-            .sessions
-            .iter()
-            .filter(|s| {
-                s.status == SessionStatus::Running || s.status == SessionStatus::NeedsInput
-            })
-            .map(|s| (s.id, s.log_path.clone(), s.status.clone()))
-            .collect();
-
-        let mut finished: Vec<(Uuid, bool, u32)> = Vec::new();
-        let mut needs_input_detected: Vec<Uuid> = Vec::new();
-
-        for (session_id, log_path, status) in &active {
-            if let Some(handle) = self.pty_handles.get_mut(session_id) {
-                // Drain output
-                let chunks = handle.try_read();
-                let has_output = !chunks.is_empty();
-
-                for chunk in &chunks {
-                    self.log_store.append(*session_id, chunk);
-                    let _ = persistence::append_log(log_path, chunk);
-                }
-
-                if has_output {
-                    self.last_output_at.insert(*session_id, Instant::now());
-
-                    // Check for needs_input patterns (only for Running sessions)
-                    if *status == SessionStatus::Running {
-                        if let Some(line) = self.log_store.last_non_empty_line(session_id) {
-                            if self.needs_input_detector.check(line) {
-                                needs_input_detected.push(*session_id);
-                            }
-                        }
-                    }
-                }
+    /// Apply a scroll action to the log view of the current mode
+    /// with its line range (first, end) and visible height.
+    fn scroll_log(&mut self, action: impl FnOnce(&mut Scrollback, usize, usize, usize)) {
+        self.sync_log_history();
+        let height = self.log_view_height();
+        let Some(range) = ui::log_panel::text_range(self) else {
+            return;
+        };
+        let scroll = match self.log_mode {
+            LogMode::Unified => &mut self.unified_scroll,
+            _ => match self.active_id() {
+                Some(id) => self.log_scroll.entry(id).or_default(),
+                None => return,
+            },
+        };
+        action(scroll, range.start, range.end, height);
+        self.page_in_visible_lines();
+    }
 
-                // Check exit
-                if let Some((success, code)) = handle.check_exit() {
-                    finished.push((*session_id, success, code));
+    /// Start selecting in the log panel (at its last visible line) or the
+    /// file preview (at its first).
+    fn start_visual(&mut self, panel: Panel, kind: SelectionKind) {
+        let line = match panel {
+            Panel::Log => {
+                if self.log_mode == LogMode::Grid {
+                    self.notice = Some("グリッド表示では選択できません".to_string());
+                    return;
                 }
+                self.sync_log_history();
+                let Some(visible) = self.visible_log_lines().filter(|v| !v.is_empty()) else {
+                    return;
+                };
+                visible.end - 1
             }
-        }
-
-        // Check timeout for Running sessions without recent output
-        let timeout = Duration::from_secs(self.config.needs_input_timeout_sec);
-        for (session_id, _, status) in &active {
-            if *status == SessionStatus::Running {
-                if let Some(last) = self.last_output_at.get(session_id) {
-                    if last.elapsed() > timeout {
-                        needs_input_detected.push(*session_id);
-                    }
+            _ => {
+                if self.file_preview.path.is_none() {
+                    return;
                 }
+                self.file_preview.scroll
             }
-        }
+        };
+        self.visual = Some((panel, Selection::new(kind, line, 0)));
+        self.reveal_selection();
+    }
 
-        // Transition to NeedsInput
-        let mut state_changed = false;
-        for id in &needs_input_detected {
-            if let Some(session) = self.sessions.iter_mut().find(|s| s.id == *id) {
-                if session.status == SessionStatus::Running {
-                    let _ = session.transition_to(SessionStatus::NeedsInput);
-                    state_changed = true;
-                }
+    fn handle_visual_key(&mut self, key: KeyEvent) -> Cmd<Msg> {
+        let Some((panel, mut selection)) = self.visual else {
+            return Cmd::None;
+        };
+        let within = self.selectable_lines(panel);
+        let (width, height) = self.view_size(panel);
+        let page = height.saturating_sub(1).max(1) as isize;
+        let all = within.len() as isize;
+        let (lines, cols) = match key.code {
+            KeyCode::Escape | KeyCode::Char('q') => {
+                self.visual = None;
+                return Cmd::None;
+            }
+            KeyCode::Enter | KeyCode::Char('y') => {
+                self.visual = None;
+                self.copy_selection(panel, &selection);
+                return Cmd::None;
+            }
+            KeyCode::Char('v') if key.modifiers.contains(Modifiers::CTRL) => {
+                selection.kind = SelectionKind::Block;
+                (0, 0)
             }
+            KeyCode::Char('v') => {
+                selection.kind = SelectionKind::Line;
+                (0, 0)
+            }
+            KeyCode::Up | KeyCode::Char('k') => (-1, 0),
+            KeyCode::Down | KeyCode::Char('j') => (1, 0),
+            KeyCode::Left | KeyCode::Char('h') => (0, -1),
+            KeyCode::Right | KeyCode::Char('l') => (0, 1),
+            KeyCode::PageUp => (-page, 0),
+            KeyCode::PageDown => (page, 0),
+            KeyCode::Home | KeyCode::Char('g') => (-all, 0),
+            KeyCode::End | KeyCode::Char('G') => (all, 0),
+            _ => return Cmd::None,
+        };
+        selection.move_by(lines, cols, &within, width);
+        self.visual = Some((panel, selection));
+        self.reveal_selection();
+        Cmd::None
+    }
+
+    /// Line numbers a selection in `panel` can cover.
+    fn selectable_lines(&self, panel: Panel) -> std::ops::Range<usize> {
+        match panel {
+            Panel::Log => ui::log_panel::text_range(self).unwrap_or(0..0),
+            _ => 0..self.file_preview.total_lines,
         }
+    }
 
-        // Handle finished sessions
-        if !finished.is_empty() {
-            for (id, success, code) in &finished {
-                self.pty_handles.remove(id);
-                self.last_output_at.remove(id);
-                if let Some(session) = self.sessions.iter_mut().find(|s| s.id == *id) {
-                    // NeedsInput → Running first if needed for valid transition
-                    if session.status == SessionStatus::NeedsInput {
-                        let _ = session.transition_to(SessionStatus::Running);
-                    }
-                    if *success {
-                        let _ = session.transition_to(SessionStatus::Done);
-                    } else {
-                        let _ = session.transition_to(SessionStatus::Failed);
-                    }
-                    session.exit_code = Some(*code as i32);
-                    session.pty_pid = None;
+    /// Scroll the selection's panel so its cursor is in view.
+    fn reveal_selection(&mut self) {
+        let Some((panel, selection)) = self.visual else {
+            return;
+        };
+        let line = selection.cursor_line();
+        match panel {
+            Panel::Log => self.scroll_log(|s, first, end, height| s.reveal(line, first, end, height)),
+            _ => {
+                let height = self.view_size(panel).1;
+                let preview = &mut self.file_preview;
+                if line < preview.scroll {
+                    preview.scroll = line;
+                } else if line >= preview.scroll + height {
+                    preview.scroll = line + 1 - height;
                 }
             }
-            state_changed = true;
-        }
-
-        if state_changed {
-            self.save();
         }
-
-        Cmd::None
     }
 
-    fn handle_br_poll(&mut self) -> Cmd<Msg> {
-        for session in &self.sessions {
-            let id = session.id;
-            match br_poller::poll(&session.root_path) {
-                Some(info) => {
-                    self.br_tasks.insert(id, info);
-                }
-                None => {
-                    self.br_tasks.remove(&id);
-                }
+    fn copy_selection(&mut self, panel: Panel, selection: &Selection) {
+        let text = match panel {
+            Panel::Log => self.log_selection_text(selection),
+            _ => {
+                let lines: Vec<&str> = self
+                    .file_preview
+                    .content
+                    .as_deref()
+                    .unwrap_or_default()
+                    .lines()
+                    .collect();
+                selection.text(|n| lines.get(n).copied().unwrap_or_default().to_string())
             }
+        };
+        let sequence = selection::osc52(&text, std::env::var_os("TMUX").is_some());
+        let written = {
+            use std::io::Write;
+            let mut stdout = std::io::stdout();
+            stdout
+                .write_all(sequence.as_bytes())
+                .and_then(|()| stdout.flush())
+        };
+        let count = selection.lines().count();
+        self.notice = Some(match written {
+            Ok(()) => format!("{} 行をクリップボードにコピーしました", count),
+            Err(e) => format!("コピーできません: {}", e),
+        });
+    }
+
+    /// Selected log lines, reading those no longer in memory from the log file.
+    fn log_selection_text(&self, selection: &Selection) -> String {
+        if self.log_mode == LogMode::Unified {
+            let lines = ui::log_panel::unified_lines(self);
+            return selection.text(|n| lines.get(n).map(|l| l.text.clone()).unwrap_or_default());
         }
-        Cmd::None
+        let Some(id) = self.active_id() else {
+            return String::new();
+        };
+        let first = self.log_store.first_line(&id);
+        let start = *selection.lines().start();
+        let mut older = Vec::new();
+        if start < first
+            && let Some(history) = self.log_history.get(&id)
+        {
+            let _ = history.read(start..first, |_, page| older.extend(page));
+        }
+        let memory = self.log_store.lines(&id);
+        selection.text(|n| {
+            let line = match n.checked_sub(first) {
+                Some(i) => memory.get(i),
+                None => older.get(n - start),
+            };
+            line.cloned().unwrap_or_default()
+        })
     }
 
-    fn handle_create_dialog(&mut self, key: KeyEvent) -> Cmd<Msg> {
+    fn handle_search_prompt_key(&mut self, key: KeyEvent) -> Cmd<Msg> {
+        let Some(search) = self.search.as_mut() else {
+            self.search_prompt = false;
+            return Cmd::None;
+        };
         match key.code {
             KeyCode::Escape => {
-                self.creating_session = false;
+                self.search = None;
+                self.search_prompt = false;
+                return Cmd::None;
             }
             KeyCode::Enter => {
-                if self.create_step == CreateStep::Name {
-                    self.create_step = CreateStep::Path;
-                } else {
-                    let name = if self.create_name.is_empty() {
-                        format!("session-{}", self.sessions.len() + 1)
-                    } else {
-                        self.create_name.clone()
-                    };
-                    let path = if self.create_path.is_empty() {
-                        std::env::current_dir().unwrap_or_default()
-                    } else {
-                        std::path::PathBuf::from(&self.create_path)
-                    };
-
-                    if path.is_dir() {
-                        let session = Session::new(name, path, &self.config.logs_root_path);
-                        self.sessions.push(session);
-                        self.active_session = self.sessions.len() - 1;
-                        self.sync_dir_tree();
-                        self.save();
-                        self.creating_session = false;
-                    } else {
-                        // Show error: stay in dialog, switch to path step
-                        self.create_step = CreateStep::Path;
-                        self.create_path = format!("(未検出) {}", path.display());
-                    }
+                if search.query.is_empty() {
+                    self.search = None;
                 }
+                self.search_prompt = false;
+                return Cmd::None;
             }
-            KeyCode::Char(c) => {
-                if self.create_step == CreateStep::Name {
-                    self.create_name.push(c);
-                } else {
-                    self.create_path.push(c);
-                }
+            KeyCode::Char('r') if key.modifiers.contains(Modifiers::ALT) => {
+                search.regex = !search.regex;
+            }
+            KeyCode::Char('c') if key.modifiers.contains(Modifiers::ALT) => {
+                search.case_sensitive = !search.case_sensitive;
             }
             KeyCode::Backspace => {
-                if self.create_step == CreateStep::Name {
-                    self.create_name.pop();
-                } else {
-                    self.create_path.pop();
-                }
+                search.query.pop();
             }
-            _ => {}
+            KeyCode::Char(c) if !key.modifiers.contains(Modifiers::CTRL) => {
+                search.query.push(c);
+            }
+            _ => return Cmd::None,
         }
-        Cmd::None
-    }
+        search.compile();
 
-    fn handle_paste(&mut self, text: String) -> Cmd<Msg> {
-        // Sanitize: remove newlines from pasted text for single-line inputs
-        let clean = text.replace('\n', "").replace('\r', "");
-
-        if self.creating_session {
-            if self.create_step == CreateStep::Name {
-                self.create_name.push_str(&clean);
-            } else {
-                self.create_path.push_str(&clean);
-            }
-        } else if self.renaming {
-            self.rename_text.push_str(&clean);
-        } else if self.active_panel == Panel::Input {
-            self.input_text.push_str(&clean);
+        // Incremental: jump to the nearest hit at or above the bottom of the view
+        self.refresh_search();
+        let bottom = self.visible_log_lines().map_or(0, |v| v.end.saturating_sub(1));
+        if let Some(hit) = self.search.as_mut().and_then(|s| s.select_at_or_before(bottom)) {
+            self.reveal_hit(hit);
         }
         Cmd::None
     }
 
-    fn handle_rename_dialog(&mut self, key: KeyEvent) -> Cmd<Msg> {
+    fn handle_filter_prompt_key(&mut self, key: KeyEvent) -> Cmd<Msg> {
         match key.code {
             KeyCode::Escape => {
-                self.renaming = false;
+                self.filter_text.clear();
+                self.filter_prompt = false;
             }
             KeyCode::Enter => {
-                if let Some(session) = self.sessions.get_mut(self.active_session) {
-                    if !self.rename_text.is_empty() {
-                        session.name = self.rename_text.clone();
-                        self.save();
-                    }
-                }
-                self.renaming = false;
+                self.filter_prompt = false;
+                return Cmd::None;
             }
-            KeyCode::Char(c) => {
-                self.rename_text.push(c);
+            KeyCode::Up | KeyCode::Down => {
+                // Cycle through the named filters from the config
+                let names: Vec<&String> = self.config.log_filters.keys().collect();
+                if names.is_empty() {
+                    return Cmd::None;
+                }
+                let last = names.len() - 1;
+                let pick = match (self.filter_pick, key.code) {
+                    (None, KeyCode::Up) => last,
+                    (None, _) => 0,
+                    (Some(i), KeyCode::Up) => i.checked_sub(1).unwrap_or(last),
+                    (Some(i), _) => (i + 1) % names.len(),
+                };
+                self.filter_text = format!("@{}", names[pick]);
+                self.filter_pick = Some(pick);
             }
             KeyCode::Backspace => {
-                self.rename_text.pop();
+                self.filter_text.pop();
             }
-            _ => {}
+            KeyCode::Char(c) if !key.modifiers.contains(Modifiers::CTRL) => {
+                self.filter_text.push(c);
+            }
+            _ => return Cmd::None,
         }
+        self.apply_filter();
         Cmd::None
     }
-}
 
-impl Model for AppState {
-    type Message = Msg;
+    /// Filter the unified log by the expression in the filter bar as it is
+    /// typed; one that doesn't parse leaves the last valid filter in place.
+    fn apply_filter(&mut self) {
+        match LogFilter::parse(&self.filter_text, &self.config.log_filters) {
+            Ok(filter) => {
+                self.filter_error = None;
+                self.log_filter = (!filter.expr.is_empty()).then_some(filter);
+                // Line numbers change with the filter
+                self.unified_scroll.follow();
+                self.refresh_search();
+            }
+            Err(e) => self.filter_error = Some(e),
+        }
+    }
 
-    fn init(&mut self) -> Cmd<Msg> {
-        Cmd::None
+    /// The log view searches run over: the active session or the unified log.
+    fn search_view(&self) -> Option<SearchView> {
+        match self.log_mode {
+            LogMode::Unified => Some(SearchView::Unified),
+            LogMode::Individual => self.active_id().map(SearchView::Session),
+            LogMode::Grid => None,
+        }
     }
 
-    fn update(&mut self, msg: Msg) -> Cmd<Msg> {
-        match msg {
-            Msg::Key(key) => self.handle_key(key),
-            Msg::Paste(paste) => self.handle_paste(paste.text),
-            Msg::PtyPollTick => self.handle_pty_poll(),
-            Msg::BrPollTick => self.handle_br_poll(),
-            Msg::Noop => Cmd::None,
+    /// Re-run the search over the lines of the current view.
+    fn refresh_search(&mut self) {
+        let Some(mut search) = self.search.take() else {
+            return;
+        };
+        self.search_stale = false;
+        self.searched_at = Instant::now();
+        match (self.search_view(), ui::log_panel::text_range(self)) {
+            (Some(SearchView::Unified), Some(_)) => {
+                search.update(SearchView::Unified, 0, &ui::log_panel::unified_lines(self));
+            }
+            (Some(SearchView::Session(id)), Some(range)) => {
+                // Older lines come from the log file, the rest from memory
+                let first = self.log_store.first_line(&id);
+                let memory = self.log_store.lines(&id);
+                let history = self.log_history.get(&id);
+                let _ = search.update_log(SearchView::Session(id), range, |lines, each| {
+                    if let Some(history) = history {
+                        history.read(lines.start..lines.end.min(first), &mut *each)?;
+                    }
+                    let from = lines.start.max(first);
+                    if from < lines.end {
+                        each(from, memory[from - first..lines.end - first].to_vec());
+                    }
+                    Ok::<(), std::io::Error>(())
+                });
+            }
+            _ => {}
         }
+        self.search = Some(search);
     }
 
-    fn view(&self, frame: &mut Frame) {
-        let area = Rect::from_size(frame.buffer.width(), frame.buffer.height());
+    /// Move to another hit (n/N) and scroll it into view.
+    fn step_search(&mut self, step: fn(&mut LogSearch) -> Option<Hit>) {
+        self.sync_log_history();
+        self.refresh_search();
+        if let Some(hit) = self.search.as_mut().and_then(step) {
+            self.reveal_hit(hit);
+        }
+    }
 
+    fn reveal_hit(&mut self, hit: Hit) {
+        self.scroll_log(|s, first, end, height| s.reveal(hit.line, first, end, height));
+    }
+
+    /// Line numbers currently shown by the text view.
+    fn visible_log_lines(&self) -> Option<std::ops::Range<usize>> {
+        let range = ui::log_panel::text_range(self)?;
+        let scroll = match self.log_mode {
+            LogMode::Unified => self.unified_scroll,
+            _ => self
+                .active_id()
+                .and_then(|id| self.log_scroll.get(&id).copied())
+                .unwrap_or_default(),
+        };
+        Some(scroll.visible(range.start, range.end, self.log_view_height()))
+    }
+
+    /// Index the active session's log file once lines have been dropped from
+    /// memory, so scrolling and search reach back to the start of the file.
+    /// Bookmarks are placed by file line, so sessions with any are indexed too.
+    fn sync_log_history(&mut self) {
+        if self.log_mode != LogMode::Individual {
+            return;
+        }
+        let Some(id) = self.active_id() else {
+            return;
+        };
+        let has_bookmarks = self.bookmarks.get(&id).is_some_and(|b| !b.items.is_empty());
+        if self.log_store.first_line(&id) == 0 && !has_bookmarks {
+            return;
+        }
+        self.synced_history(id);
+    }
+
+    /// The session's log history, indexed up to its latest output.
+    fn synced_history(&mut self, id: Uuid) -> Option<&LogHistory> {
+        let session = self.sessions.iter().find(|s| s.id == id)?;
+        let first = self.log_store.first_line(&id);
+        let memory_len = self.log_store.lines(&id).len();
+        let history = self
+            .log_history
+            .entry(id)
+            .or_insert_with(|| LogHistory::new(&session.log_path));
+        history.sync(first, memory_len).ok()?;
+        Some(history)
+    }
+
+    /// Bookmark the last line with text in the individual log view, then ask
+    /// for a note.
+    fn start_bookmark(&mut self) {
+        if self.log_mode != LogMode::Individual {
+            self.notice = Some("ブックマークは個別ログで使えます".to_string());
+            return;
+        }
+        let Some(id) = self.active_id() else {
+            return;
+        };
+        if self.synced_history(id).is_none() {
+            self.notice = Some("ログファイルを読めません".to_string());
+            return;
+        }
+        self.page_in_visible_lines();
+        let Some(visible) = self.visible_log_lines().filter(|v| !v.is_empty()) else {
+            return;
+        };
+        let line = visible
+            .clone()
+            .rev()
+            .find(|&n| !ui::log_panel::individual_line(self, n).trim().is_empty())
+            .unwrap_or(visible.end - 1);
+        let text = ui::log_panel::individual_line(self, line).to_string();
+        let Some(file_line) = self.log_history.get(&id).and_then(|h| h.file_line(line)) else {
+            return;
+        };
+        let Some(bookmarks) = self.bookmarks.get_mut(&id) else {
+            return;
+        };
+        bookmarks.refresh_segment();
+        let bookmark = Bookmark {
+            segment: bookmarks.segment(),
+            line: file_line,
+            text,
+            note: String::new(),
+            created_at: chrono::Utc::now(),
+        };
+        self.new_bookmark = Some((id, bookmark));
+    }
+
+    fn handle_bookmark_prompt(&mut self, key: KeyEvent) -> Cmd<Msg> {
+        let Some((id, bookmark)) = &mut self.new_bookmark else {
+            return Cmd::None;
+        };
+        match key.code {
+            KeyCode::Escape => self.new_bookmark = None,
+            KeyCode::Enter => {
+                let (id, bookmark) = (*id, bookmark.clone());
+                self.new_bookmark = None;
+                if let Some(bookmarks) = self.bookmarks.get_mut(&id) {
+                    self.notice = Some(match bookmarks.add(bookmark) {
+                        Ok(()) => "ブックマークしました".to_string(),
+                        Err(e) => format!("ブックマークを保存できません: {}", e),
+                    });
+                }
+            }
+            KeyCode::Char(c) if !key.modifiers.intersects(Modifiers::CTRL | Modifiers::ALT) => {
+                bookmark.note.push(c);
+            }
+            KeyCode::Backspace => {
+                bookmark.note.pop();
+            }
+            _ => {}
+        }
+        Cmd::None
+    }
+
+    fn handle_bookmark_picker(&mut self, key: KeyEvent) -> Cmd<Msg> {
+        let (Some(pick), Some(id)) = (self.bookmark_pick, self.active_id()) else {
+            self.bookmark_pick = None;
+            return Cmd::None;
+        };
+        let count = self.bookmarks.get(&id).map_or(0, |b| b.items.len());
+        match key.code {
+            KeyCode::Escape | KeyCode::Char('q') => self.bookmark_pick = None,
+            KeyCode::Up | KeyCode::Char('k') => self.bookmark_pick = Some(pick.saturating_sub(1)),
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.bookmark_pick = Some((pick + 1).min(count.saturating_sub(1)));
+            }
+            KeyCode::Enter => {
+                self.bookmark_pick = None;
+                self.jump_to_bookmark(id, pick);
+            }
+            KeyCode::Char('d') | KeyCode::Delete => {
+                if let Some(bookmarks) = self.bookmarks.get_mut(&id)
+                    && let Err(e) = bookmarks.remove(pick)
+                {
+                    self.notice = Some(format!("ブックマークを保存できません: {}", e));
+                }
+                let left = count.saturating_sub(1);
+                self.bookmark_pick = (left > 0).then(|| pick.min(left - 1));
+            }
+            _ => {}
+        }
+        Cmd::None
+    }
+
+    /// Absolute lines of the session's bookmarks in its current log file,
+    /// with their index in its bookmarks.
+    fn bookmark_lines(&mut self, id: Uuid) -> Vec<(usize, usize)> {
+        let Some(bookmarks) = self.bookmarks.get_mut(&id) else {
+            return Vec::new();
+        };
+        bookmarks.refresh_segment();
+        let files: Vec<(usize, usize)> = bookmarks.current().map(|(i, b)| (i, b.line)).collect();
+        let Some(history) = self.synced_history(id) else {
+            return Vec::new();
+        };
+        files
+            .into_iter()
+            .filter_map(|(i, line)| Some((history.absolute_line(line)?, i)))
+            .collect()
+    }
+
+    fn jump_to_bookmark(&mut self, id: Uuid, index: usize) {
+        if self.log_mode != LogMode::Individual {
+            self.log_mode = LogMode::Individual;
+            self.sync_pty_sizes();
+        }
+        let line = self
+            .bookmark_lines(id)
+            .into_iter()
+            .find(|(_, i)| *i == index)
+            .map(|(line, _)| line)
+            .filter(|line| ui::log_panel::text_range(self).is_some_and(|r| r.contains(line)));
+        let Some(line) = line else {
+            self.notice = Some("ブックマークの行はローテーション済みのログにあります（書き出しには含まれます）".to_string());
+            return;
+        };
+        // The log was written over since (a new run of the session)
+        let text = self.log_line_text(id, line);
+        let bookmark = self.bookmarks.get(&id).and_then(|b| b.items.get(index));
+        if bookmark.is_some_and(|b| !text.is_some_and(|text| b.matches(&text))) {
+            self.notice = Some("ブックマークの行が見つかりません（ログが書き換えられています）".to_string());
+            return;
+        }
+        self.active_panel = Panel::Log;
+        self.center_log_on(line);
+    }
+
+    /// Absolute log line `n` of a session, from memory or the log file.
+    fn log_line_text(&mut self, id: Uuid, n: usize) -> Option<String> {
+        let first = self.log_store.first_line(&id);
+        if let Some(i) = n.checked_sub(first) {
+            return self.log_store.lines(&id).get(i).cloned();
+        }
+        let mut text = None;
+        self.synced_history(id)?
+            .read(n..n + 1, |_, page| text = page.into_iter().next())
+            .ok()?;
+        text
+    }
+
+    /// Jump to the next or previous bookmark from the last one jumped to,
+    /// or from the middle of the view.
+    fn step_bookmark(&mut self, forward: bool) {
+        if self.log_mode != LogMode::Individual {
+            self.notice = Some("ブックマークは個別ログで使えます".to_string());
+            return;
+        }
+        let Some(id) = self.active_id() else {
+            return;
+        };
+        let mut lines: Vec<usize> = self.bookmark_lines(id).into_iter().map(|(line, _)| line).collect();
+        lines.sort_unstable();
+        let Some(visible) = self.visible_log_lines() else {
+            return;
+        };
+        let from = self
+            .bookmark_cursor
+            .filter(|line| visible.contains(line))
+            .unwrap_or((visible.start + visible.end) / 2);
+        let target = if forward {
+            lines.into_iter().find(|&line| line > from)
+        } else {
+            lines.into_iter().rev().find(|&line| line < from)
+        };
+        match target {
+            Some(line) => {
+                self.bookmark_cursor = Some(line);
+                self.center_log_on(line);
+            }
+            None => self.notice = Some("この先にブックマークはありません".to_string()),
+        }
+    }
+
+    /// Scroll the log so `line` is in the middle of the view.
+    fn center_log_on(&mut self, line: usize) {
+        self.scroll_log(|s, first, end, height| {
+            let top = s.visible(first, end, height).start;
+            s.scroll_by(line as isize - (height / 2) as isize - top as isize, first, end, height)
+        });
+    }
+
+    /// Absolute lines of the active session's bookmarks, for highlighting.
+    pub fn bookmarked_lines(&self) -> HashSet<usize> {
+        let Some(id) = self.active_id() else {
+            return HashSet::new();
+        };
+        let (Some(bookmarks), Some(history)) = (self.bookmarks.get(&id), self.log_history.get(&id)) else {
+            return HashSet::new();
+        };
+        bookmarks
+            .current()
+            .filter_map(|(_, b)| history.absolute_line(b.line))
+            .collect()
+    }
+
+    /// Page in the file lines the log view is about to show.
+    fn page_in_visible_lines(&mut self) {
+        if self.log_mode != LogMode::Individual {
+            return;
+        }
+        if let Some(visible) = self.visible_log_lines()
+            && let Some(id) = self.active_id()
+            && let Some(history) = self.log_history.get_mut(&id)
+        {
+            let _ = history.load(visible);
+        }
+    }
+
+    fn handle_passthrough_key(&mut self, key: KeyEvent) -> Cmd<Msg> {
+        if key.kind == KeyEventKind::Release {
+            return Cmd::None;
+        }
+        if key_encoder::is_passthrough_exit(&key) {
+            self.passthrough = false;
+            return Cmd::None;
+        }
+        let Some(session_id) = self.sessions.get(self.active_session).map(|s| s.id) else {
+            self.passthrough = false;
+            return Cmd::None;
+        };
+        let application_cursor = self
+            .terminals
+            .screen(&session_id)
+            .is_some_and(|screen| screen.application_cursor());
+        if let Some(bytes) = key_encoder::encode(&key, application_cursor) {
+            self.send_raw(session_id, &bytes);
+        }
+        Cmd::None
+    }
+
+    /// The replies offered by a session waiting for input: the active one if it
+    /// is waiting, else the first waiting session whose prompt offers any.
+    pub fn quick_replies(&self) -> Option<(&Session, Vec<Choice>)> {
+        let offered = |session: &Session| {
+            if session.status != SessionStatus::NeedsInput {
+                return None;
+            }
+            let lines = self
+                .terminals
+                .bottom_lines(&session.id, needs_input::SCREEN_LINES);
+            Some(needs_input::choices(&lines)).filter(|choices| !choices.is_empty())
+        };
+        self.sessions
+            .get(self.active_session)
+            .into_iter()
+            .chain(&self.sessions)
+            .find_map(|session| Some((session, offered(session)?)))
+    }
+
+    fn quick_reply(&mut self, index: usize) {
+        let Some((session, choices)) = self.quick_replies() else {
+            self.notice = Some("返信できる入力待ちのセッションはありません".to_string());
+            return;
+        };
+        let Some(choice) = choices.get(index) else {
+            return;
+        };
+        let (id, name) = (session.id, session.name.clone());
+        let notice = format!("{} に「{}」と返信しました", name, choice.label);
+        self.send_raw(id, choice.reply.as_bytes());
+        self.notice = Some(notice);
+    }
+
+    /// Write raw bytes to a session's PTY (the engine resumes it if it was waiting).
+    fn send_raw(&mut self, session_id: Uuid, data: &[u8]) {
+        self.request(Request::SendBytes {
+            id: session_id,
+            data: data.to_vec(),
+        });
+    }
+
+    fn handle_input_key(&mut self, key: KeyEvent) -> Cmd<Msg> {
+        match key.code {
+            KeyCode::Char(c) => {
+                self.input_text.push(c);
+            }
+            KeyCode::Backspace => {
+                self.input_text.pop();
+            }
+            KeyCode::Up => {
+                if let Some(text) = self.input_history.up() {
+                    self.input_text = text.to_string();
+                }
+            }
+            KeyCode::Down => {
+                match self.input_history.down() {
+                    Some(text) => self.input_text = text.to_string(),
+                    None => self.input_text.clear(),
+                }
+            }
+            KeyCode::Enter => {
+                if !self.input_text.is_empty()
+                    && let Some(id) = self.active_id()
+                {
+                    let text = std::mem::take(&mut self.input_text);
+                    self.input_history.push(text.clone());
+                    // Spawns the agent at the log panel's size if it isn't running
+                    let (cols, rows) = self.default_pty_size();
+                    self.requested_sizes.insert(id, (cols, rows));
+                    self.request(Request::Instruct {
+                        id,
+                        text,
+                        cols,
+                        rows,
+                    });
+                }
+            }
+            KeyCode::Escape => {
+                self.active_panel = if self.log_zoomed {
+                    Panel::Log
+                } else {
+                    Panel::SessionList
+                };
+            }
+            _ => {}
+        }
+        Cmd::None
+    }
+
+    fn handle_engine_tick(&mut self) -> Cmd<Msg> {
+        if let Some(replay) = &mut self.replay {
+            replay.tick();
+        }
+        // Layout changes (terminal resize, zoom, mode switch) are picked up here
+        self.sync_pty_sizes();
+        let output = self.pump_events();
+        self.sync_log_history();
+        self.page_in_visible_lines();
+
+        // Keep hits current as output arrives or the view switches
+        self.search_stale |= output;
+        if let Some(search) = &self.search {
+            let view = self.search_view();
+            let due = view != Some(SearchView::Unified)
+                || self.searched_at.elapsed() >= UNIFIED_SEARCH_INTERVAL;
+            if search.view != view || (self.search_stale && due) {
+                self.refresh_search();
+            }
+        }
+        Cmd::None
+    }
+
+    /// Quit, asking first what to do with sessions that still have a live PTY.
+    fn request_quit(&mut self) -> Cmd<Msg> {
+        if self.disconnected || !self.sessions.iter().any(Self::is_live) {
+            return Cmd::Quit;
+        }
+        self.confirming_quit = true;
+        Cmd::None
+    }
+
+    fn handle_quit_dialog(&mut self, key: KeyEvent) -> Cmd<Msg> {
+        match key.code {
+            KeyCode::Char('s') => {
+                self.request(Request::StopAll);
+                Cmd::Quit
+            }
+            // Detach and leave the sessions to the server. The local engine can't
+            // offer this: closing its PTY masters hangs up the sessions anyway
+            KeyCode::Char('l') if self.backend.is_remote() => Cmd::Quit,
+            KeyCode::Escape => {
+                self.confirming_quit = false;
+                Cmd::None
+            }
+            _ => Cmd::None,
+        }
+    }
+
+    fn handle_orphan_dialog(&mut self, key: KeyEvent) -> Cmd<Msg> {
+        let kill = match key.code {
+            KeyCode::Char('k') => true,
+            KeyCode::Char('l') | KeyCode::Escape => false,
+            _ => return Cmd::None,
+        };
+        self.orphans.clear();
+        self.request(Request::ResolveOrphans { kill });
+        Cmd::None
+    }
+
+    fn handle_br_poll(&mut self) -> Cmd<Msg> {
+        for session in &self.sessions {
+            let id = session.id;
+            match br_poller::poll(&session.root_path) {
+                Some(info) => {
+                    self.br_tasks.insert(id, info);
+                }
+                None => {
+                    self.br_tasks.remove(&id);
+                }
+            }
+        }
+        Cmd::None
+    }
+
+    fn handle_create_dialog(&mut self, key: KeyEvent) -> Cmd<Msg> {
+        match key.code {
+            KeyCode::Escape => {
+                self.creating_session = false;
+            }
+            KeyCode::Enter => match self.create_step {
+                CreateStep::Name => self.create_step = CreateStep::Path,
+                CreateStep::Path => {
+                    let path = self.create_dir();
+                    if path.is_dir() {
+                        self.create_step = CreateStep::Command;
+                    } else {
+                        // Show error: stay on the path step
+                        self.create_path = format!("(未検出) {}", path.display());
+                    }
+                }
+                CreateStep::Command => match launch::split_args(&self.create_command) {
+                    Ok(_) => {
+                        self.create_error = None;
+                        self.create_step = CreateStep::Env;
+                    }
+                    Err(e) => self.create_error = Some(e),
+                },
+                CreateStep::Env => match self.create_launch() {
+                    Ok(launch) => {
+                        self.create_error = None;
+                        self.create_profile = self.config.default_profile_for(&launch.program);
+                        self.create_step = CreateStep::Profile;
+                    }
+                    Err(e) => self.create_error = Some(e),
+                },
+                CreateStep::Profile => {
+                    let path = self.create_dir();
+                    let known = self.config.detection_profiles().contains_key(&self.create_profile);
+                    let launch = self
+                        .create_launch()
+                        .and_then(|launch| if known { Ok(launch) } else { Err("そのプロファイルはありません") });
+                    match launch {
+                        Ok(launch) => {
+                            let name = if self.create_name.is_empty() {
+                                format!("session-{}", self.sessions.len() + 1)
+                            } else {
+                                self.create_name.clone()
+                            };
+                            let mut session = Session::new(name, path, &self.config.logs_root_path);
+                            session.launch = launch;
+                            session.needs_input_profile = Some(self.create_profile.clone());
+                            self.pending_select = Some(session.id);
+                            self.creating_session = false;
+                            self.request(Request::CreateSession {
+                                session: Box::new(session),
+                            });
+                        }
+                        Err(e) => self.create_error = Some(e),
+                    }
+                }
+            },
+            KeyCode::Tab if self.create_step == CreateStep::Profile => {
+                // Cycle through the profile names
+                let names: Vec<String> = self.config.detection_profiles().into_keys().collect();
+                let next = names
+                    .iter()
+                    .position(|name| *name == self.create_profile)
+                    .map_or(0, |i| (i + 1) % names.len());
+                self.create_profile = names[next].clone();
+            }
+            KeyCode::Char(c) => {
+                self.create_field().push(c);
+            }
+            KeyCode::Backspace => {
+                self.create_field().pop();
+            }
+            _ => {}
+        }
+        Cmd::None
+    }
+
+    /// Text buffer edited by the current step of the creation dialog.
+    fn create_field(&mut self) -> &mut String {
+        match self.create_step {
+            CreateStep::Name => &mut self.create_name,
+            CreateStep::Path => &mut self.create_path,
+            CreateStep::Command => &mut self.create_command,
+            CreateStep::Env => &mut self.create_env,
+            CreateStep::Profile => &mut self.create_profile,
+        }
+    }
+
+    /// Launch spec from the command and environment steps.
+    fn create_launch(&self) -> Result<LaunchSpec, &'static str> {
+        let path = self.create_dir();
+        LaunchSpec::parse(&self.create_command, &self.create_env).and_then(|spec| {
+            match spec.resolved_env(&path) {
+                Ok(_) => Ok(spec),
+                Err(_) => Err("env ファイルを読み込めません"),
+            }
+        })
+    }
+
+    fn create_dir(&self) -> std::path::PathBuf {
+        if self.create_path.is_empty() {
+            std::env::current_dir().unwrap_or_default()
+        } else {
+            std::path::PathBuf::from(&self.create_path)
+        }
+    }
+
+    fn handle_paste(&mut self, text: String) -> Cmd<Msg> {
+        if self.passthrough {
+            if let Some(session_id) = self.sessions.get(self.active_session).map(|s| s.id) {
+                let bracketed = self
+                    .terminals
+                    .screen(&session_id)
+                    .is_some_and(|screen| screen.bracketed_paste());
+                let data = if bracketed {
+                    format!("\x1b[200~{}\x1b[201~", text)
+                } else {
+                    text
+                };
+                self.send_raw(session_id, data.as_bytes());
+            }
+            return Cmd::None;
+        }
+
+        // Sanitize: remove newlines from pasted text for single-line inputs
+        let clean = text.replace(['\n', '\r'], "");
+
+        if self.creating_session {
+            self.create_field().push_str(&clean);
+        } else if self.renaming {
+            self.rename_text.push_str(&clean);
+        } else if self.exporting {
+            self.export_overwrite = false;
+            self.export_path.push_str(&clean);
+        } else if let Some((_, bookmark)) = &mut self.new_bookmark {
+            bookmark.note.push_str(&clean);
+        } else if self.active_panel == Panel::Input {
+            self.input_text.push_str(&clean);
+        }
+        Cmd::None
+    }
+
+    fn handle_export_prompt(&mut self, key: KeyEvent) -> Cmd<Msg> {
+        if self.export_overwrite {
+            match key.code {
+                KeyCode::Char('y') => self.write_export(),
+                KeyCode::Char('n') | KeyCode::Escape => self.export_overwrite = false,
+                _ => {}
+            }
+            return Cmd::None;
+        }
+        match key.code {
+            KeyCode::Escape => self.exporting = false,
+            KeyCode::Enter => {
+                if export::expand_path(&self.export_path).is_file() {
+                    self.export_overwrite = true;
+                } else {
+                    self.write_export();
+                }
+            }
+            KeyCode::Tab => {
+                // Switch between Markdown and HTML by the extension
+                let path = std::path::Path::new(&self.export_path);
+                let ext = match export::Format::from_path(path) {
+                    export::Format::Markdown => "html",
+                    export::Format::Html => "md",
+                };
+                self.export_path = path.with_extension(ext).display().to_string();
+            }
+            KeyCode::Char(c) => self.export_path.push(c),
+            KeyCode::Backspace => {
+                self.export_path.pop();
+            }
+            _ => {}
+        }
+        Cmd::None
+    }
+
+    /// Write the active session's transcript to the typed path and close the prompt.
+    fn write_export(&mut self) {
+        let path = export::expand_path(&self.export_path);
+        if let Some(session) = self.sessions.get(self.active_session)
+            && !path.as_os_str().is_empty()
+        {
+            self.notice = Some(match export::export(session, &path) {
+                Ok(()) => format!("書き出しました: {}", path.display()),
+                Err(e) => format!("書き出せません: {}", e),
+            });
+        }
+        self.exporting = false;
+        self.export_overwrite = false;
+    }
+
+    fn handle_rename_dialog(&mut self, key: KeyEvent) -> Cmd<Msg> {
+        match key.code {
+            KeyCode::Escape => {
+                self.renaming = false;
+            }
+            KeyCode::Enter => {
+                if let Some(id) = self.active_id()
+                    && !self.rename_text.is_empty()
+                {
+                    let name = self.rename_text.clone();
+                    self.request(Request::RenameSession { id, name });
+                }
+                self.renaming = false;
+            }
+            KeyCode::Char(c) => {
+                self.rename_text.push(c);
+            }
+            KeyCode::Backspace => {
+                self.rename_text.pop();
+            }
+            _ => {}
+        }
+        Cmd::None
+    }
+}
+
+impl Model for AppState {
+    type Message = Msg;
+
+    fn init(&mut self) -> Cmd<Msg> {
+        Cmd::None
+    }
+
+    fn update(&mut self, msg: Msg) -> Cmd<Msg> {
+        match msg {
+            Msg::Key(key) => self.handle_key(key),
+            Msg::Paste(paste) => self.handle_paste(paste.text),
+            Msg::Resize => {
+                self.sync_pty_sizes();
+                Cmd::None
+            }
+            Msg::EngineTick => self.handle_engine_tick(),
+            Msg::BrPollTick => self.handle_br_poll(),
+            Msg::ReplayLoaded(id, cast) => self.handle_replay_loaded(id, cast),
+            Msg::Noop => Cmd::None,
+        }
+    }
+
+    fn view(&self, frame: &mut Frame) {
+        let area = Rect::from_size(frame.buffer.width(), frame.buffer.height());
+        self.frame_size.set((area.width, area.height));
+        let layout = self.layout(area);
+
+        // Render each panel (zoom leaves only the log panel in the body)
+        if !self.log_zoomed {
+            ui::session_list::render(self, frame, layout.session_list, self.active_panel == Panel::SessionList);
+            ui::dir_tree_panel::render(self, frame, layout.dir_tree, self.active_panel == Panel::DirTree);
+            if self.bookmark_pick.is_some() {
+                ui::bookmark_picker::render(self, frame, layout.center);
+            } else if self.show_details {
+                ui::session_details::render(self, frame, layout.center, self.active_panel == Panel::FilePreview);
+            } else {
+                ui::file_panel::render(self, frame, layout.center, self.active_panel == Panel::FilePreview);
+            }
+        }
+        // The bookmark picker takes the center panel, or the log when it is zoomed
+        if self.log_zoomed && self.bookmark_pick.is_some() {
+            ui::bookmark_picker::render(self, frame, layout.log);
+        } else {
+            ui::log_panel::render(self, frame, layout.log, self.active_panel == Panel::Log);
+        }
+
+        // Input bar - show dialog if active, otherwise normal input
+        if self.confirming_quit {
+            self.render_quit_dialog(frame, layout.input);
+        } else if !self.orphans.is_empty() {
+            self.render_orphan_dialog(frame, layout.input);
+        } else if self.creating_session {
+            self.render_create_dialog(frame, layout.input);
+        } else if self.renaming {
+            self.render_rename_dialog(frame, layout.input);
+        } else if self.exporting {
+            self.render_export_prompt(frame, layout.input);
+        } else if self.new_bookmark.is_some() {
+            self.render_bookmark_prompt(frame, layout.input);
+        } else if self.search_prompt {
+            self.render_search_prompt(frame, layout.input);
+        } else if self.filter_prompt {
+            self.render_filter_prompt(frame, layout.input);
+        } else {
+            ui::input_bar::render(self, frame, layout.input, self.active_panel == Panel::Input);
+        }
+
+        ui::status_bar::render(self, frame, layout.status);
+    }
+
+    fn subscriptions(&self) -> Vec<Box<dyn ftui_runtime::subscription::Subscription<Msg>>> {
+        let mut subs: Vec<Box<dyn ftui_runtime::subscription::Subscription<Msg>>> = Vec::new();
+
+        // Engine events arrive at any time (other clients, server-side detection)
+        subs.push(Box::new(Every::new(
+            Duration::from_millis(50),
+            || Msg::EngineTick,
+        )));
+
+        if !self.sessions.is_empty() {
+            subs.push(Box::new(Every::new(
+                Duration::from_secs(self.config.br_poll_interval_sec),
+                || Msg::BrPollTick,
+            )));
+        }
+
+        subs
+    }
+}
+
+/// Panel areas for one frame, shared by `view` and PTY sizing.
+struct Layout {
+    session_list: Rect,
+    dir_tree: Rect,
+    center: Rect,
+    log: Rect,
+    input: Rect,
+    status: Rect,
+}
+
+impl AppState {
+    fn layout(&self, area: Rect) -> Layout {
         // Top-level: body | input_area | status_bar
         let rows = Flex::vertical()
             .constraints([
@@ -685,8 +1852,20 @@ impl Model for AppState {
             ])
             .split(area);
         let body = rows[0];
-        let input_area = rows[1];
-        let status_area = rows[2];
+        let input = rows[1];
+        let status = rows[2];
+
+        if self.log_zoomed {
+            let empty = Rect::new(0, 0, 0, 0);
+            return Layout {
+                session_list: empty,
+                dir_tree: empty,
+                center: empty,
+                log: body,
+                input,
+                status,
+            };
+        }
 
         // Body: sidebar | center | log_panel
         let cols = Flex::horizontal()
@@ -697,60 +1876,81 @@ impl Model for AppState {
             ])
             .split(body);
         let sidebar = cols[0];
-        let center = cols[1];
-        let log_area = cols[2];
 
         // Sidebar: session_list | dir_tree
         let sidebar_rows = Flex::vertical()
             .constraints([Constraint::Percentage(40.0), Constraint::Percentage(60.0)])
             .split(sidebar);
-        let session_list_area = sidebar_rows[0];
-        let dir_tree_area = sidebar_rows[1];
-
-        // Render each panel
-        ui::session_list::render(self, frame, session_list_area, self.active_panel == Panel::SessionList);
-        ui::dir_tree_panel::render(self, frame, dir_tree_area, self.active_panel == Panel::DirTree);
-        ui::file_panel::render(self, frame, center, self.active_panel == Panel::FilePreview);
-        ui::log_panel::render(self, frame, log_area, self.active_panel == Panel::Log);
 
-        // Input bar - show dialog if active, otherwise normal input
-        if self.creating_session {
-            self.render_create_dialog(frame, input_area);
-        } else if self.renaming {
-            self.render_rename_dialog(frame, input_area);
-        } else {
-            ui::input_bar::render(self, frame, input_area, self.active_panel == Panel::Input);
+        Layout {
+            session_list: sidebar_rows[0],
+            dir_tree: sidebar_rows[1],
+            center: cols[1],
+            log: cols[2],
+            input,
+            status,
         }
-
-        ui::status_bar::render(self, frame, status_area);
     }
 
-    fn subscriptions(&self) -> Vec<Box<dyn ftui_runtime::subscription::Subscription<Msg>>> {
-        let mut subs: Vec<Box<dyn ftui_runtime::subscription::Subscription<Msg>>> = Vec::new();
-
-        let has_active = self.sessions.iter().any(|s| {
-            s.status == SessionStatus::Running || s.status == SessionStatus::NeedsInput
-        });
+    /// PTY size (cols, rows) for a session shown in the individual log view.
+    fn default_pty_size(&self) -> (u16, u16) {
+        let (width, height) = self.frame_size.get();
+        if width == 0 || height == 0 {
+            return (DEFAULT_COLS, DEFAULT_ROWS);
+        }
+        let log = self.layout(Rect::from_size(width, height)).log;
+        let inner = ui::log_panel::inner_area(log);
+        (inner.width.max(1), inner.height.max(1))
+    }
 
-        if has_active {
-            subs.push(Box::new(Every::new(
-                Duration::from_millis(50),
-                || Msg::PtyPollTick,
-            )));
+    /// Inner (width, height) of the log panel or the file preview.
+    fn view_size(&self, panel: Panel) -> (usize, usize) {
+        let (width, height) = self.frame_size.get();
+        if width == 0 || height == 0 {
+            return (DEFAULT_COLS as usize, DEFAULT_ROWS as usize);
         }
+        let layout = self.layout(Rect::from_size(width, height));
+        let area = match panel {
+            Panel::Log => layout.log,
+            _ => layout.center,
+        };
+        let inner = ui::log_panel::inner_area(area);
+        (inner.width.max(1) as usize, inner.height.max(1) as usize)
+    }
 
-        if !self.sessions.is_empty() {
-            subs.push(Box::new(Every::new(
-                Duration::from_secs(self.config.br_poll_interval_sec),
-                || Msg::BrPollTick,
-            )));
+    /// Rows of text the log panel shows.
+    fn log_view_height(&self) -> usize {
+        let (width, height) = self.frame_size.get();
+        if width == 0 || height == 0 {
+            return DEFAULT_ROWS as usize;
         }
+        let log = self.layout(Rect::from_size(width, height)).log;
+        ui::log_panel::inner_area(log).height.max(1) as usize
+    }
 
-        subs
+    /// Ask the engine to resize each live PTY to the area it is drawn into.
+    /// Only this client's own layout changes are sent, so attached clients
+    /// with different sizes don't keep overriding each other.
+    fn sync_pty_sizes(&mut self) {
+        let (width, height) = self.frame_size.get();
+        if width == 0 || height == 0 {
+            return;
+        }
+        let log = self.layout(Rect::from_size(width, height)).log;
+        for (id, area) in ui::log_panel::terminal_viewports(self, log) {
+            let size = (area.width.max(1), area.height.max(1));
+            if self.requested_sizes.get(&id) == Some(&size) {
+                continue;
+            }
+            self.requested_sizes.insert(id, size);
+            self.request(Request::Resize {
+                id,
+                cols: size.0,
+                rows: size.1,
+            });
+        }
     }
-}
 
-impl AppState {
     fn render_create_dialog(&self, frame: &mut Frame, area: Rect) {
         use ftui_style::Style;
         use ftui_widgets::block::Block;
@@ -758,16 +1958,184 @@ impl AppState {
         use ftui_widgets::paragraph::Paragraph;
         use ftui_widgets::Widget;
 
-        let text = if self.create_step == CreateStep::Name {
-            format!("セッション名 (Enterでスキップ): {}", self.create_name)
+        let text = match self.create_step {
+            CreateStep::Name => format!("セッション名 (Enterでスキップ): {}", self.create_name),
+            CreateStep::Path => format!("作業ディレクトリ (Enterで現在地): {}", self.create_path),
+            CreateStep::Command => format!("起動コマンド (Enterで claude): {}", self.create_command),
+            CreateStep::Env => format!(
+                "環境変数 (KEY=VALUE, @envファイル / Enterでなし): {}",
+                self.create_env
+            ),
+            CreateStep::Profile => format!(
+                "入力待ち検知プロファイル (Tabで切替 / Enterで作成): {}",
+                self.create_profile
+            ),
+        };
+        let title = match self.create_error {
+            Some(e) => format!("New Session - {}", e),
+            None => "New Session".to_string(),
+        };
+
+        let paragraph = Paragraph::new(text).block(
+            Block::bordered()
+                .border_type(BorderType::Rounded)
+                .title(&title)
+                .border_style(Style::new().fg(ui::theme::DIALOG_BORDER)),
+        );
+        paragraph.render(area, frame);
+    }
+
+    fn render_quit_dialog(&self, frame: &mut Frame, area: Rect) {
+        use ftui_style::Style;
+        use ftui_widgets::block::Block;
+        use ftui_widgets::borders::BorderType;
+        use ftui_widgets::paragraph::Paragraph;
+        use ftui_widgets::Widget;
+
+        let leave = if self.backend.is_remote() {
+            "l:デタッチ (サーバーで実行継続)  "
+        } else {
+            ""
+        };
+        let text = format!(
+            "実行中のセッションが {} 件あります  s:停止して終了  {}Esc:キャンセル",
+            self.sessions.iter().filter(|s| Self::is_live(s)).count(),
+            leave
+        );
+        let paragraph = Paragraph::new(text).block(
+            Block::bordered()
+                .border_type(BorderType::Rounded)
+                .title("Quit")
+                .border_style(Style::new().fg(ui::theme::DIALOG_BORDER)),
+        );
+        paragraph.render(area, frame);
+    }
+
+    fn render_orphan_dialog(&self, frame: &mut Frame, area: Rect) {
+        use ftui_style::Style;
+        use ftui_widgets::block::Block;
+        use ftui_widgets::borders::BorderType;
+        use ftui_widgets::paragraph::Paragraph;
+        use ftui_widgets::Widget;
+
+        let names: Vec<String> = self
+            .orphans
+            .iter()
+            .filter_map(|(id, pgid)| {
+                let session = self.sessions.iter().find(|s| s.id == *id)?;
+                Some(format!("{} (pid {})", session.name, pgid))
+            })
+            .collect();
+        let text = format!(
+            "前回のプロセスが実行中です: {}  k:終了させる  l:そのまま",
+            names.join(", ")
+        );
+        let paragraph = Paragraph::new(text).block(
+            Block::bordered()
+                .border_type(BorderType::Rounded)
+                .title("Orphaned Processes")
+                .border_style(Style::new().fg(ui::theme::DIALOG_BORDER)),
+        );
+        paragraph.render(area, frame);
+    }
+
+    fn render_search_prompt(&self, frame: &mut Frame, area: Rect) {
+        use ftui_style::Style;
+        use ftui_widgets::block::Block;
+        use ftui_widgets::borders::BorderType;
+        use ftui_widgets::paragraph::Paragraph;
+        use ftui_widgets::Widget;
+
+        let Some(search) = &self.search else {
+            return;
+        };
+        let (current, total) = search.counter();
+        let status = match &search.error {
+            Some(e) => format!("正規表現エラー: {}", e.lines().last().unwrap_or_default()),
+            None => format!("{}/{} 件", current, total),
+        };
+        let text = format!("/{}  {}", search.query, status);
+        let title = format!(
+            "Search [{}] [{}] (Alt+r:正規表現 Alt+c:大文字小文字 Enter:確定 Esc:解除)",
+            if search.regex { "regex" } else { "literal" },
+            if search.case_sensitive { "Aa" } else { "aa" },
+        );
+        let paragraph = Paragraph::new(text).block(
+            Block::bordered()
+                .border_type(BorderType::Rounded)
+                .title(&title)
+                .border_style(Style::new().fg(ui::theme::DIALOG_BORDER)),
+        );
+        paragraph.render(area, frame);
+    }
+
+    fn render_filter_prompt(&self, frame: &mut Frame, area: Rect) {
+        use ftui_style::Style;
+        use ftui_widgets::block::Block;
+        use ftui_widgets::borders::BorderType;
+        use ftui_widgets::paragraph::Paragraph;
+        use ftui_widgets::Widget;
+
+        let mut text = format!("フィルタ: {}", self.filter_text);
+        if let Some(error) = &self.filter_error {
+            text.push_str(&format!("  ({})", error));
+        }
+        let paragraph = Paragraph::new(text).block(
+            Block::bordered()
+                .border_type(BorderType::Rounded)
+                .title("Filter (s:名前 #タグ status:状態 -除外 @保存済み  Up/Down:保存済み Enter:確定 Esc:解除)")
+                .border_style(Style::new().fg(ui::theme::DIALOG_BORDER)),
+        );
+        paragraph.render(area, frame);
+    }
+
+    fn render_export_prompt(&self, frame: &mut Frame, area: Rect) {
+        use ftui_style::Style;
+        use ftui_widgets::block::Block;
+        use ftui_widgets::borders::BorderType;
+        use ftui_widgets::paragraph::Paragraph;
+        use ftui_widgets::Widget;
+
+        let format = match export::Format::from_path(std::path::Path::new(&self.export_path)) {
+            export::Format::Markdown => "Markdown",
+            export::Format::Html => "HTML",
+        };
+        let (text, title) = if self.export_overwrite {
+            (
+                format!("{} は既にあります。上書きしますか？", self.export_path),
+                "Export (y:上書き n:パスを編集)",
+            )
         } else {
-            format!("作業ディレクトリ (Enterで現在地): {}", self.create_path)
+            (
+                format!("書き出し先 ({}): {}", format, self.export_path),
+                "Export (.md:Markdown .html:HTML  Tab:形式切替 Enter:書き出し Esc:キャンセル)",
+            )
         };
+        let paragraph = Paragraph::new(text).block(
+            Block::bordered()
+                .border_type(BorderType::Rounded)
+                .title(title)
+                .border_style(Style::new().fg(ui::theme::DIALOG_BORDER)),
+        );
+        paragraph.render(area, frame);
+    }
+
+    fn render_bookmark_prompt(&self, frame: &mut Frame, area: Rect) {
+        use ftui_style::Style;
+        use ftui_widgets::block::Block;
+        use ftui_widgets::borders::BorderType;
+        use ftui_widgets::paragraph::Paragraph;
+        use ftui_widgets::Widget;
 
+        let Some((_, bookmark)) = &self.new_bookmark else {
+            return;
+        };
+        let text = format!("メモ: {}", bookmark.note);
+        let title = format!("Bookmark: {} (Enter:保存 Esc:キャンセル)", bookmark.text.trim());
         let paragraph = Paragraph::new(text).block(
             Block::bordered()
                 .border_type(BorderType::Rounded)
-                .title("New Session")
+                .title(&title)
                 .border_style(Style::new().fg(ui::theme::DIALOG_BORDER)),
         );
         paragraph.render(area, frame);
@@ -790,3 +2158,142 @@ impl AppState {
         paragraph.render(area, frame);
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A client with a local engine keeping its files in `dir`.
+    fn state(dir: &std::path::Path) -> AppState {
+        let config = AppConfig::in_dir(dir);
+        AppState::new(config.clone(), Backend::local(config))
+    }
+
+    #[test]
+    fn pty_sizes_follow_zoom_and_frame_size() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dir = tmp.path();
+        let mut state = state(dir);
+        let session = Session::new("s".to_string(), dir.to_path_buf(), &dir.join("logs"));
+        let id = session.id;
+        state.sessions.push(session);
+
+        // 160x45: the log panel is the right part of the body, above the input and status bars
+        state.frame_size.set((160, 45));
+        state.sync_pty_sizes();
+        let (cols, rows) = state.requested_sizes[&id];
+        assert_eq!(rows, 45 - 3 - 1 - 2);
+        assert!(cols < 160 - 30);
+
+        // Zoomed, the log takes the whole width
+        state.log_zoomed = true;
+        state.sync_pty_sizes();
+        assert_eq!(state.requested_sizes[&id], (160 - 2, rows));
+
+        // A tiny frame still gives the PTY at least one cell
+        state.frame_size.set((4, 3));
+        state.sync_pty_sizes();
+        let (cols, rows) = state.requested_sizes[&id];
+        assert!(cols >= 1 && rows >= 1);
+    }
+
+    #[test]
+    fn local_quit_cannot_leave_sessions_running() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dir = tmp.path();
+        let mut state = state(dir);
+        state.confirming_quit = true;
+        state.handle_quit_dialog(KeyEvent::new(KeyCode::Char('l')));
+        assert!(state.confirming_quit);
+        state.handle_quit_dialog(KeyEvent::new(KeyCode::Escape));
+        assert!(!state.confirming_quit);
+    }
+
+    #[test]
+    fn bookmarks_take_plain_notes_and_notice_rewritten_logs() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dir = tmp.path();
+        let mut state = state(dir);
+        let session = Session::new("s".to_string(), dir.to_path_buf(), &dir.join("logs"));
+        let (id, log_path) = (session.id, session.log_path.clone());
+        crate::persistence::write_log_header(&log_path, &session).unwrap();
+        crate::persistence::append_log(&log_path, b"one\r\ntwo\r\n").unwrap();
+        state.log_store.append(id, b"one\r\ntwo\r\n");
+        state.set_sessions(vec![session]);
+        state.frame_size.set((160, 45));
+
+        state.start_bookmark();
+        state.handle_bookmark_prompt(KeyEvent::new(KeyCode::Char('w')).with_modifiers(Modifiers::CTRL));
+        state.handle_bookmark_prompt(KeyEvent::new(KeyCode::Char('x')).with_modifiers(Modifiers::ALT));
+        state.handle_bookmark_prompt(KeyEvent::new(KeyCode::Char('n')));
+        state.handle_bookmark_prompt(KeyEvent::new(KeyCode::Enter));
+        let bookmark = &state.bookmarks[&id].items[0];
+        assert_eq!((bookmark.text.as_str(), bookmark.note.as_str()), ("two", "n"));
+
+        state.active_panel = Panel::SessionList;
+        state.jump_to_bookmark(id, 0);
+        assert_eq!(state.active_panel, Panel::Log);
+
+        // The same line now holds other output
+        state.active_panel = Panel::SessionList;
+        let arrived = state.log_store.arrived(&id).to_vec();
+        let lines = vec!["one".to_string(), "three".to_string(), String::new()];
+        state.log_store.restore(id, 0, lines, arrived);
+        state.jump_to_bookmark(id, 0);
+        assert_eq!(state.active_panel, Panel::SessionList);
+        assert!(state.notice.as_deref().is_some_and(|n| n.contains("見つかりません")));
+    }
+
+    #[test]
+    fn export_asks_before_overwriting() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dir = tmp.path();
+        let mut state = state(dir);
+        state
+            .sessions
+            .push(Session::new("s".to_string(), dir.to_path_buf(), &dir.join("logs")));
+        let out = dir.join("out.md");
+        std::fs::write(&out, "keep me").unwrap();
+
+        state.exporting = true;
+        state.export_path = out.display().to_string();
+        state.handle_export_prompt(KeyEvent::new(KeyCode::Enter));
+        assert!(state.exporting && state.export_overwrite);
+        // n goes back to the path, leaving the file alone
+        state.handle_export_prompt(KeyEvent::new(KeyCode::Char('n')));
+        assert!(state.exporting && !state.export_overwrite);
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "keep me");
+
+        state.handle_export_prompt(KeyEvent::new(KeyCode::Enter));
+        state.handle_export_prompt(KeyEvent::new(KeyCode::Char('y')));
+        assert!(!state.exporting);
+        assert!(std::fs::read_to_string(&out).unwrap().contains("# s"));
+    }
+
+    #[test]
+    fn zoomed_log_keeps_focus_on_visible_panels() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dir = tmp.path();
+        let mut state = state(dir);
+        state.active_panel = Panel::Log;
+        state.handle_key(KeyEvent::new(KeyCode::Char('z')));
+        assert!(state.log_zoomed);
+
+        state.handle_key(KeyEvent::new(KeyCode::Tab));
+        assert_eq!(state.active_panel, Panel::Input);
+        state.handle_key(KeyEvent::new(KeyCode::Tab));
+        assert_eq!(state.active_panel, Panel::Log);
+        state.handle_key(KeyEvent::new(KeyCode::Char('k')).with_modifiers(Modifiers::CTRL));
+        assert_eq!(state.active_panel, Panel::Input);
+        state.handle_key(KeyEvent::new(KeyCode::Escape));
+        assert_eq!(state.active_panel, Panel::Log);
+
+        // Unzoomed, focus goes round every panel again
+        state.handle_key(KeyEvent::new(KeyCode::Char('z')));
+        state.handle_key(KeyEvent::new(KeyCode::Tab));
+        assert_eq!(state.active_panel, Panel::Input);
+        state.handle_key(KeyEvent::new(KeyCode::Tab));
+        assert_eq!(state.active_panel, Panel::SessionList);
+    }
+}
@@ -0,0 +1,144 @@
+use crate::config::AppConfig;
+use crate::engine::SessionEngine;
+use crate::protocol::{self, Event, Request};
+use crate::server;
+
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+
+/// How long to wait for a freshly spawned server to accept connections.
+const SERVER_START_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where the TUI's sessions live: in this process or in `deck server`.
+pub enum Backend {
+    /// In-process engine (`deck --local`); sessions end with the TUI.
+    Local {
+        engine: Box<SessionEngine>,
+        pending: Vec<Event>,
+    },
+    /// Attached to the daemon over its Unix socket.
+    Remote(RemoteEngine),
+}
+
+impl Backend {
+    pub fn local(config: AppConfig) -> Self {
+        let engine = SessionEngine::new(config);
+        let pending = vec![engine.snapshot()];
+        Self::Local {
+            engine: Box::new(engine),
+            pending,
+        }
+    }
+
+    /// Attach to the running server, starting one in the background if needed.
+    pub fn attach(config: &AppConfig) -> std::io::Result<Self> {
+        if let Ok(remote) = RemoteEngine::connect(&config.socket_path) {
+            return Ok(Self::Remote(remote));
+        }
+        server::spawn_detached(config)?;
+        let deadline = Instant::now() + SERVER_START_TIMEOUT;
+        loop {
+            match RemoteEngine::connect(&config.socket_path) {
+                Ok(remote) => return Ok(Self::Remote(remote)),
+                Err(_) if Instant::now() < deadline => {
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    pub fn send(&mut self, request: Request) {
+        match self {
+            Self::Local { engine, pending } => {
+                engine.handle(request);
+                pending.extend(engine.take_events());
+            }
+            Self::Remote(remote) => remote.send(&request),
+        }
+    }
+
+    /// Events since the last call. The local engine is ticked here.
+    pub fn poll(&mut self) -> Vec<Event> {
+        match self {
+            Self::Local { engine, pending } => {
+                engine.tick();
+                pending.extend(engine.take_events());
+                std::mem::take(pending)
+            }
+            Self::Remote(remote) => remote.poll(),
+        }
+    }
+
+    /// Sessions keep running after the TUI exits.
+    pub fn is_remote(&self) -> bool {
+        matches!(self, Self::Remote(_))
+    }
+
+    pub fn is_connected(&self) -> bool {
+        match self {
+            Self::Local { .. } => true,
+            Self::Remote(remote) => remote.connected.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Client end of a server connection: requests are written as JSON lines,
+/// events are read on a background thread.
+pub struct RemoteEngine {
+    writer: UnixStream,
+    events: mpsc::Receiver<Event>,
+    connected: Arc<AtomicBool>,
+}
+
+impl RemoteEngine {
+    pub fn connect(socket_path: &Path) -> std::io::Result<Self> {
+        let writer = UnixStream::connect(socket_path)?;
+        let reader = writer.try_clone()?;
+        let connected = Arc::new(AtomicBool::new(true));
+        let (tx, rx) = mpsc::channel();
+
+        let flag = Arc::clone(&connected);
+        std::thread::spawn(move || {
+            for line in BufReader::new(reader).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                let Ok(event) = serde_json::from_str::<Event>(&line) else {
+                    continue;
+                };
+                if tx.send(event).is_err() {
+                    break;
+                }
+            }
+            flag.store(false, Ordering::Relaxed);
+        });
+
+        Ok(Self {
+            writer,
+            events: rx,
+            connected,
+        })
+    }
+
+    pub fn send(&mut self, request: &Request) {
+        if self.writer.write_all(&protocol::encode_line(request)).is_err() {
+            self.connected.store(false, Ordering::Relaxed);
+        }
+    }
+
+    pub fn poll(&mut self) -> Vec<Event> {
+        self.events.try_iter().collect()
+    }
+}
+
+impl Drop for RemoteEngine {
+    fn drop(&mut self) {
+        // Also ends the reader thread's clone, so the server sees the detach
+        let _ = self.writer.shutdown(std::net::Shutdown::Both);
+    }
+}
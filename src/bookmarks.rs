@@ -0,0 +1,162 @@
+use crate::log_retention;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A marked line of a session's log with the user's note.
+///
+/// The line is addressed in the log file it was in: `segment` is the number
+/// that file gets when it is rotated, and `line` counts from its top (header
+/// included), as `LogPager` does. `text` is the line as it was shown, so a
+/// bookmark whose file has been rotated away can still be told apart.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub segment: u64,
+    pub line: usize,
+    pub text: String,
+    pub note: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Bookmark {
+    /// Whether `line` still reads like the bookmarked line (whitespace aside:
+    /// the log view and the transcript expand tabs differently).
+    pub fn matches(&self, line: &str) -> bool {
+        self.text.split_whitespace().eq(line.split_whitespace())
+    }
+}
+
+/// The bookmark file that sits next to a session's `.log` file.
+pub fn bookmarks_path(log_path: &Path) -> PathBuf {
+    log_path.with_extension("bookmarks.json")
+}
+
+/// A session's bookmarks in log order (none if the file doesn't exist).
+pub fn read(log_path: &Path) -> io::Result<Vec<Bookmark>> {
+    let path = bookmarks_path(log_path);
+    match std::fs::read_to_string(&path) {
+        Ok(data) => serde_json::from_str(&data).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} を読み込めません: {}", path.display(), e),
+            )
+        }),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// One session's bookmarks, saved to disk on every change.
+pub struct Bookmarks {
+    log_path: PathBuf,
+    pub items: Vec<Bookmark>,
+    /// Segment number of the current log file
+    segment: u64,
+}
+
+impl Bookmarks {
+    /// Unreadable bookmark files load as empty rather than blocking the session.
+    pub fn load(log_path: &Path) -> Self {
+        Self {
+            log_path: log_path.to_path_buf(),
+            items: read(log_path).unwrap_or_default(),
+            segment: log_retention::current_segment(log_path),
+        }
+    }
+
+    /// Segment number the log file has now (it changes when the log rotates).
+    pub fn refresh_segment(&mut self) {
+        self.segment = log_retention::current_segment(&self.log_path);
+    }
+
+    pub fn segment(&self) -> u64 {
+        self.segment
+    }
+
+    /// Bookmarks in the current log file, as (index in `items`, bookmark).
+    pub fn current(&self) -> impl Iterator<Item = (usize, &Bookmark)> {
+        self.items
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.segment == self.segment)
+    }
+
+    pub fn add(&mut self, bookmark: Bookmark) -> io::Result<()> {
+        let at = self
+            .items
+            .partition_point(|b| (b.segment, b.line) <= (bookmark.segment, bookmark.line));
+        self.items.insert(at, bookmark);
+        self.save()
+    }
+
+    pub fn remove(&mut self, index: usize) -> io::Result<()> {
+        if index < self.items.len() {
+            self.items.remove(index);
+        }
+        self.save()
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let path = bookmarks_path(&self.log_path);
+        if self.items.is_empty() {
+            return match std::fs::remove_file(&path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            };
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(&self.items)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bookmark(segment: u64, line: usize, note: &str) -> Bookmark {
+        Bookmark {
+            segment,
+            line,
+            text: format!("line {}", line),
+            note: note.to_string(),
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn saves_in_log_order_next_to_the_log() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let log_path = tmp.path().join("abc.log");
+        let mut bookmarks = Bookmarks::load(&log_path);
+        assert!(bookmarks.items.is_empty());
+        assert_eq!(bookmarks.segment(), 1);
+
+        bookmarks.add(bookmark(1, 40, "tests green")).unwrap();
+        bookmarks.add(bookmark(1, 7, "migrations")).unwrap();
+        assert_eq!(bookmarks_path(&log_path), tmp.path().join("abc.bookmarks.json"));
+        let saved = read(&log_path).unwrap();
+        assert_eq!(
+            saved.iter().map(|b| b.note.as_str()).collect::<Vec<_>>(),
+            ["migrations", "tests green"]
+        );
+
+        // After a rotation the bookmarks belong to the old segment
+        std::fs::write(tmp.path().join("abc.log.1.gz"), b"").unwrap();
+        let mut bookmarks = Bookmarks::load(&log_path);
+        assert_eq!(bookmarks.current().count(), 0);
+
+        bookmarks.remove(0).unwrap();
+        bookmarks.remove(0).unwrap();
+        assert!(!bookmarks_path(&log_path).exists());
+    }
+
+    #[test]
+    fn matches_ignoring_whitespace() {
+        let b = bookmark(1, 0, "");
+        assert!(b.matches("line\t0  "));
+        assert!(!b.matches("line 1"));
+    }
+}
@@ -1,36 +1,239 @@
+use crate::needs_input::{self, NeedsInputDetector, NeedsInputProfile};
+use crate::redact::Redactor;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
 
+/// Settings from `~/.config/deck/config.json`; missing keys keep their defaults.
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AppConfig {
     pub sessions_file_path: PathBuf,
     pub logs_root_path: PathBuf,
+    /// Unix socket of the `deck server` daemon
+    pub socket_path: PathBuf,
+    /// JSON Lines journal of session lifecycle events
+    pub journal_path: PathBuf,
     pub needs_input_timeout_sec: u64,
+    /// Needs-input detection profiles by name, added to the built-in ones or replacing them
+    pub needs_input_profiles: BTreeMap<String, NeedsInputProfile>,
+    /// Profile for sessions whose program no profile lists
+    pub needs_input_profile: String,
     pub br_poll_interval_sec: u64,
     pub editor: String,
+    /// Also record session output and input as asciicast v2 (`<session-id>.cast`)
+    pub record_sessions: bool,
+    /// Named filter expressions for the unified log, used as `@name`
+    pub log_filters: BTreeMap<String, String>,
+    /// A session log larger than this is rotated into a gzipped segment (0: never)
+    pub log_max_bytes: u64,
+    /// Rotated segments kept per session; older ones are removed
+    pub log_rotate_keep: usize,
+    /// Rotated segments, archives and logs without a session older than this are removed (0: never)
+    pub log_max_age_days: u64,
+    pub log_on_delete: LogOnDelete,
+    /// Replace API keys, tokens and passwords in output with `[REDACTED]`
+    pub redact_secrets: bool,
+    /// Extra regexes whose matches are redacted (also when `redact_secrets` is off)
+    pub redact_patterns: Vec<String>,
+}
+
+/// What happens to a session's logs when the session is deleted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogOnDelete {
+    /// Leave them in the logs directory (`deck gc` lists them)
+    #[default]
+    Keep,
+    Delete,
+    /// Compress them into `logs/archive/`
+    Archive,
+}
+
+fn config_dir() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("deck")
 }
 
 impl Default for AppConfig {
     fn default() -> Self {
-        let config_dir = dirs::config_dir()
-            .unwrap_or_else(|| PathBuf::from("."))
-            .join("deck");
+        let config_dir = config_dir();
         Self {
             sessions_file_path: config_dir.join("sessions.json"),
             logs_root_path: config_dir.join("logs"),
+            socket_path: config_dir.join("deck.sock"),
+            journal_path: config_dir.join("events.jsonl"),
             needs_input_timeout_sec: 30,
+            needs_input_profiles: BTreeMap::new(),
+            needs_input_profile: needs_input::DEFAULT_PROFILE.to_string(),
             br_poll_interval_sec: 3,
             editor: std::env::var("EDITOR").unwrap_or_else(|_| "vim".to_string()),
+            record_sessions: false,
+            log_filters: BTreeMap::new(),
+            log_max_bytes: 20 * 1024 * 1024,
+            log_rotate_keep: 5,
+            log_max_age_days: 0,
+            log_on_delete: LogOnDelete::Keep,
+            redact_secrets: true,
+            redact_patterns: Vec::new(),
         }
     }
 }
 
 impl AppConfig {
+    /// Read `config.json` from the config directory, or use the defaults if there is none.
+    pub fn load() -> std::io::Result<Self> {
+        Self::load_from(&config_dir().join("config.json"))
+    }
+
+    fn load_from(path: &std::path::Path) -> std::io::Result<Self> {
+        let config: Self = match std::fs::read_to_string(path) {
+            Ok(data) => serde_json::from_str(&data).map_err(|e| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("{} を読み込めません: {}", path.display(), e),
+                )
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+        config
+            .redactor()
+            .map(|_| ())
+            .and_then(|()| config.needs_input_detectors().map(|_| ()))
+            .map_err(|e| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("{}: {}", path.display(), e),
+                )
+            })?;
+        Ok(config)
+    }
+
+    pub fn redactor(&self) -> Result<Redactor, String> {
+        Redactor::new(self.redact_secrets, &self.redact_patterns)
+    }
+
+    /// The built-in detection profiles with the configured ones over them.
+    pub fn detection_profiles(&self) -> BTreeMap<String, NeedsInputProfile> {
+        let mut profiles = needs_input::builtin_profiles();
+        profiles.extend(self.needs_input_profiles.clone());
+        profiles
+    }
+
+    /// Profile a session running `program` uses unless it picked one: the
+    /// first listing the program, else `needs_input_profile`.
+    pub fn default_profile_for(&self, program: &str) -> String {
+        self.program_profiles()
+            .remove(needs_input::program_name(program))
+            .unwrap_or_else(|| self.needs_input_profile.clone())
+    }
+
+    /// Each program listed by a detection profile, with the first profile listing it.
+    pub fn program_profiles(&self) -> HashMap<String, String> {
+        let mut programs = HashMap::new();
+        for (name, profile) in self.detection_profiles() {
+            for program in profile.programs {
+                programs.entry(program).or_insert_with(|| name.clone());
+            }
+        }
+        programs
+    }
+
+    /// Every detection profile compiled; errors name the profile and the pattern.
+    pub fn needs_input_detectors(&self) -> Result<BTreeMap<String, NeedsInputDetector>, String> {
+        let profiles = self.detection_profiles();
+        if !profiles.contains_key(&self.needs_input_profile) {
+            return Err(format!(
+                "needs_input_profile の '{}' というプロファイルはありません",
+                self.needs_input_profile
+            ));
+        }
+        profiles
+            .iter()
+            .map(|(name, profile)| {
+                NeedsInputDetector::new(profile)
+                    .map(|detector| (name.clone(), detector))
+                    .map_err(|e| format!("needs_input_profiles.{}: {}", name, e))
+            })
+            .collect()
+    }
+
     pub fn ensure_dirs(&self) -> std::io::Result<()> {
         if let Some(parent) = self.sessions_file_path.parent() {
             std::fs::create_dir_all(parent)?;
         }
         std::fs::create_dir_all(&self.logs_root_path)?;
+        if let Some(parent) = self.journal_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
         Ok(())
     }
+
+    /// The defaults with every file deck writes kept in `dir`, for tests.
+    #[cfg(test)]
+    pub fn in_dir(dir: &std::path::Path) -> Self {
+        Self {
+            sessions_file_path: dir.join("sessions.json"),
+            logs_root_path: dir.join("logs"),
+            socket_path: dir.join("deck.sock"),
+            journal_path: dir.join("events.jsonl"),
+            ..Self::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_keys_keep_defaults() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("config.json");
+        std::fs::write(
+            &path,
+            r#"{"record_sessions": true, "needs_input_timeout_sec": 5, "log_on_delete": "archive"}"#,
+        )
+        .unwrap();
+        let config = AppConfig::load_from(&path).unwrap();
+        assert!(config.record_sessions);
+        assert_eq!(config.log_on_delete, LogOnDelete::Archive);
+        assert_eq!(config.needs_input_timeout_sec, 5);
+        assert_eq!(config.br_poll_interval_sec, AppConfig::default().br_poll_interval_sec);
+
+        assert!(config.redact_secrets);
+
+        std::fs::write(&path, r#"{"redact_patterns": ["corp-\\d+", "("]}"#).unwrap();
+        assert!(AppConfig::load_from(&path).is_err());
+        std::fs::write(&path, r#"{"needs_input_profiles": {"aider": {"include": ["[y/n"]}}}"#).unwrap();
+        let error = AppConfig::load_from(&path).unwrap_err().to_string();
+        assert!(error.contains("needs_input_profiles.aider: '[y/n' は正規表現として不正です"), "{}", error);
+        std::fs::write(&path, r#"{"needs_input_profile": "missing"}"#).unwrap();
+        assert!(AppConfig::load_from(&path).is_err());
+        std::fs::write(&path, "{not json").unwrap();
+        assert!(AppConfig::load_from(&path).is_err());
+        let defaults = AppConfig::load_from(&tmp.path().join("none.json")).unwrap();
+        assert!(!defaults.record_sessions);
+    }
+
+    #[test]
+    fn picks_detection_profiles_by_program() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("config.json");
+        std::fs::write(
+            &path,
+            r#"{"needs_input_profile": "generic",
+                "needs_input_profiles": {"aider": {"programs": ["aider"], "include": ["\\?$"]}}}"#,
+        )
+        .unwrap();
+        let config = AppConfig::load_from(&path).unwrap();
+        assert_eq!(config.default_profile_for("/usr/bin/claude"), "claude");
+        assert_eq!(config.default_profile_for("aider"), "aider");
+        assert_eq!(config.default_profile_for("bash"), "generic");
+        let detectors = config.needs_input_detectors().unwrap();
+        assert_eq!(detectors.keys().collect::<Vec<_>>(), ["aider", "claude", "generic"]);
+        assert!(detectors["aider"].check("Apply edits?").is_some());
+    }
 }
@@ -0,0 +1,153 @@
+use crate::launch::LaunchSpec;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+/// Claude prints `claude --resume <id>` when it exits; some versions also show `Session ID: <id>`.
+static OUTPUT_ID: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)(?:--resume|session[ _-]?id:?)\s+([0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12})",
+    )
+    .unwrap()
+});
+
+/// Flags that already pick a conversation; deck leaves such commands alone.
+const CONVERSATION_FLAGS: &[&str] = &["--resume", "-r", "--continue", "-c", "--session-id"];
+
+/// Whether the launch spec runs the Claude CLI (`claude` or a path ending in it).
+pub fn is_claude(launch: &LaunchSpec) -> bool {
+    Path::new(&launch.program)
+        .file_name()
+        .is_some_and(|name| name == "claude")
+}
+
+/// Claude's config directory: `$CLAUDE_CONFIG_DIR` or `~/.claude`.
+pub fn claude_home() -> Option<PathBuf> {
+    match std::env::var_os("CLAUDE_CONFIG_DIR") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => dirs::home_dir().map(|home| home.join(".claude")),
+    }
+}
+
+/// Directory holding the transcripts (`<id>.jsonl`) of conversations started in `root_path`.
+/// Claude names it after the working directory with every non-alphanumeric character
+/// replaced by `-` (`/home/me/my.app` → `-home-me-my-app`).
+pub fn project_dir(claude_home: &Path, root_path: &Path) -> PathBuf {
+    let cwd = root_path
+        .canonicalize()
+        .unwrap_or_else(|_| root_path.to_path_buf());
+    let encoded: String = cwd
+        .to_string_lossy()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    claude_home.join("projects").join(encoded)
+}
+
+/// Whether Claude has written the transcript of conversation `id` to `dir`.
+/// Transcripts appear once the first message is sent, so this is polled after spawning.
+pub fn has_transcript(dir: &Path, id: &str) -> bool {
+    dir.join(format!("{}.jsonl", id)).is_file()
+}
+
+/// Conversation id announced in a line of Claude's output, if any.
+pub fn id_from_output(line: &str) -> Option<String> {
+    OUTPUT_ID
+        .captures(line)
+        .map(|caps| caps[1].to_ascii_lowercase())
+}
+
+/// Whether Claude rejected `--resume` because the conversation no longer exists.
+pub fn is_resume_failure(line: &str) -> bool {
+    line.contains("No conversation found")
+}
+
+/// The launch spec with `--resume <id>` prepended to the arguments, unless the
+/// command is not Claude or already chooses a conversation itself.
+pub fn resume_launch(launch: &LaunchSpec, id: &str) -> LaunchSpec {
+    with_flag(launch, "--resume", id).unwrap_or_else(|| launch.clone())
+}
+
+/// The launch spec starting a new conversation with the id `id`
+/// (`--session-id <id>`), so its transcript can be told apart from those of
+/// Claude processes started elsewhere in the same directory. None when the
+/// command is not Claude or already chooses a conversation itself.
+pub fn new_conversation_launch(launch: &LaunchSpec, id: &str) -> Option<LaunchSpec> {
+    with_flag(launch, "--session-id", id)
+}
+
+fn with_flag(launch: &LaunchSpec, flag: &str, id: &str) -> Option<LaunchSpec> {
+    let picks_conversation = launch
+        .args
+        .iter()
+        .any(|arg| CONVERSATION_FLAGS.contains(&arg.as_str()));
+    if !is_claude(launch) || picks_conversation {
+        return None;
+    }
+    let mut with = launch.clone();
+    with.args.splice(0..0, [flag.to_string(), id.to_string()]);
+    Some(with)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "0b6f3c52-8d1e-4a8e-9a53-2f1d3c4b5a69";
+
+    #[test]
+    fn project_dir_encoding() {
+        let dir = project_dir(Path::new("/h/.claude"), Path::new("/nonexistent/my.app/sub_dir"));
+        assert_eq!(dir, PathBuf::from("/h/.claude/projects/-nonexistent-my-app-sub-dir"));
+    }
+
+    #[test]
+    fn detects_claude_program() {
+        assert!(is_claude(&LaunchSpec::default()));
+        assert!(is_claude(&LaunchSpec::parse("/usr/local/bin/claude", "").unwrap()));
+        assert!(!is_claude(&LaunchSpec::parse("bash", "").unwrap()));
+    }
+
+    #[test]
+    fn resume_prepends_flag() {
+        let launch = LaunchSpec::parse("claude --model opus", "").unwrap();
+        let resumed = resume_launch(&launch, ID);
+        assert_eq!(resumed.args, ["--resume", ID, "--model", "opus"]);
+
+        // Explicit conversation flags and other programs are left untouched
+        let launch = LaunchSpec::parse("claude --continue", "").unwrap();
+        assert_eq!(resume_launch(&launch, ID), launch);
+        let launch = LaunchSpec::parse("aider", "").unwrap();
+        assert_eq!(resume_launch(&launch, ID), launch);
+    }
+
+    #[test]
+    fn id_in_output() {
+        assert_eq!(
+            id_from_output(&format!("Resume this session with: claude --resume {}", ID)).as_deref(),
+            Some(ID)
+        );
+        assert_eq!(id_from_output(&format!("Session ID: {}", ID)).as_deref(), Some(ID));
+        assert!(id_from_output("claude --resume").is_none());
+    }
+
+    #[test]
+    fn new_conversations_get_their_id() {
+        let launch = LaunchSpec::parse("claude --model opus", "").unwrap();
+        let started = new_conversation_launch(&launch, ID).unwrap();
+        assert_eq!(started.args, ["--session-id", ID, "--model", "opus"]);
+        let launch = LaunchSpec::parse("claude --continue", "").unwrap();
+        assert!(new_conversation_launch(&launch, ID).is_none());
+        assert!(new_conversation_launch(&LaunchSpec::parse("aider", "").unwrap(), ID).is_none());
+    }
+
+    #[test]
+    fn finds_only_its_own_transcript() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        // Written by a claude started outside deck in the same directory
+        std::fs::write(tmp.path().join("1d9e6f02-5b7c-4f3e-8a1d-6c2b9e4f7a10.jsonl"), "{}").unwrap();
+        assert!(!has_transcript(tmp.path(), ID));
+        std::fs::write(tmp.path().join(format!("{}.jsonl", ID)), "{}").unwrap();
+        assert!(has_transcript(tmp.path(), ID));
+    }
+}
@@ -104,15 +104,15 @@ impl DirTree {
     }
 
     pub fn toggle(&mut self) {
-        if let Some(entry) = self.flat_cache.get(self.cursor) {
-            if entry.is_dir {
-                let path = entry.path.clone();
-                let show_hidden = self.show_hidden;
-                if let Some(root) = &mut self.root {
-                    toggle_node(root, &path, show_hidden);
-                }
-                self.rebuild_flat();
+        if let Some(entry) = self.flat_cache.get(self.cursor)
+            && entry.is_dir
+        {
+            let path = entry.path.clone();
+            let show_hidden = self.show_hidden;
+            if let Some(root) = &mut self.root {
+                toggle_node(root, &path, show_hidden);
             }
+            self.rebuild_flat();
         }
     }
 
@@ -159,8 +159,8 @@ fn load_children(node: &mut DirTreeNode, show_hidden: bool) {
             }
         }
 
-        dirs.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
-        files.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+        dirs.sort_by_key(|a| a.name.to_lowercase());
+        files.sort_by_key(|a| a.name.to_lowercase());
 
         node.children.extend(dirs);
         node.children.extend(files);
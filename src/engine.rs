@@ -0,0 +1,1364 @@
+use crate::config::{AppConfig, LogOnDelete};
+use crate::conversation;
+use crate::journal::{Journal, JournalEvent, NeedsInputSource};
+use crate::launch;
+use crate::log_retention;
+use crate::log_store::LogStore;
+use crate::needs_input::{self, NeedsInputDetector, NeedsInputReason};
+use crate::persistence;
+use crate::proc_tree::{self, ProcessStart, Quiet, TreeSample};
+use crate::protocol::{Event, LogSnapshot, Request, TerminalSnapshot};
+use crate::pty_manager::{self, PtyHandle};
+use crate::recording::{self, Recorder};
+use crate::redact::{RedactStream, Redactor};
+use crate::session::{Session, SessionStatus};
+use crate::terminal::TerminalStore;
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// How often expired logs (`log_max_age_days`) are pruned while the engine runs.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// How often a new Claude session's project directory is scanned for its transcript.
+const CONVERSATION_PROBE_INTERVAL: Duration = Duration::from_secs(1);
+/// Trailing output lines searched for Claude's resume hint when a session exits.
+const CONVERSATION_OUTPUT_LINES: usize = 20;
+/// How long an exited session's last output may take to arrive.
+const EXIT_DRAIN_TIMEOUT: Duration = Duration::from_millis(100);
+/// Output pause after which text held back for redaction is shown anyway.
+const REDACT_HOLD: Duration = Duration::from_millis(100);
+/// How often the process tree of a session that has gone quiet is sampled.
+const QUIET_PROBE_INTERVAL: Duration = Duration::from_secs(2);
+/// Time a stopped process group gets to exit after SIGTERM before SIGKILL.
+pub const STOP_GRACE: Duration = Duration::from_secs(5);
+
+/// Owns the sessions, their PTYs, output buffers and persistence.
+///
+/// Runs inside `deck server` (or in-process with `--local`). Clients drive it
+/// with `Request`s and mirror its state from the `Event`s it emits.
+pub struct SessionEngine {
+    config: AppConfig,
+    sessions: Vec<Session>,
+    log_store: LogStore,
+    terminals: TerminalStore,
+    pty_handles: HashMap<Uuid, PtyHandle>,
+    // Secrets are redacted from output before it is shown or written anywhere
+    redactor: Redactor,
+    redact_streams: HashMap<Uuid, RedactStream>,
+    // Lifecycle events for analytics (`journal_path`)
+    journal: Journal,
+    // needs_input detection, by profile name
+    needs_input_detectors: BTreeMap<String, NeedsInputDetector>,
+    // Default detection profile of each program the profiles list
+    program_profiles: HashMap<String, String>,
+    last_output_at: HashMap<Uuid, Instant>,
+    // Process trees of sessions quiet past the timeout: when last sampled, and the sample
+    quiet_samples: HashMap<Uuid, (Instant, TreeSample)>,
+    // Size (cols, rows) applied to each PTY
+    applied_sizes: HashMap<Uuid, (u16, u16)>,
+    // asciicast recordings of live sessions (when `record_sessions` is on)
+    recorders: HashMap<Uuid, Recorder>,
+    // New Claude conversations whose transcript hasn't appeared yet: the id
+    // they were started with and the last transcript scan
+    conversation_probes: HashMap<Uuid, (String, Instant)>,
+    // Stop requests: SIGTERM sent at, escalated to SIGKILL after STOP_GRACE
+    stopping: HashMap<Uuid, Instant>,
+    // Process groups left running by a previous run (session id, pgid) awaiting a decision
+    orphans: Vec<(Uuid, u32)>,
+    // Orphan groups sent SIGTERM, escalated like `stopping`
+    orphan_kills: Vec<(u32, ProcessStart, Instant)>,
+    // Last pruning of expired logs
+    pruned_at: Instant,
+    // Events produced since the last `take_events`
+    events: Vec<Event>,
+}
+
+impl SessionEngine {
+    pub fn new(config: AppConfig) -> Self {
+        let mut sessions = persistence::load_sessions(&config.sessions_file_path);
+        let orphans = detect_orphans(&mut sessions);
+        // `AppConfig::load` rejects bad patterns; a config built in code falls back to the built-ins
+        let redactor = config.redactor().unwrap_or_default();
+        let needs_input_detectors = config.needs_input_detectors().unwrap_or_default();
+        let program_profiles = config.program_profiles();
+        let journal = Journal::new(&config.journal_path);
+        let mut engine = Self {
+            config,
+            sessions,
+            redactor,
+            redact_streams: HashMap::new(),
+            journal,
+            log_store: LogStore::new(),
+            terminals: TerminalStore::new(),
+            pty_handles: HashMap::new(),
+            needs_input_detectors,
+            program_profiles,
+            last_output_at: HashMap::new(),
+            quiet_samples: HashMap::new(),
+            applied_sizes: HashMap::new(),
+            recorders: HashMap::new(),
+            conversation_probes: HashMap::new(),
+            stopping: HashMap::new(),
+            orphans,
+            orphan_kills: Vec::new(),
+            pruned_at: Instant::now(),
+            events: Vec::new(),
+        };
+        engine.prune_logs();
+        engine
+    }
+
+    /// Remove expired logs in the background.
+    fn prune_logs(&mut self) {
+        self.pruned_at = Instant::now();
+        if let Some(max_age) = log_retention::max_age(&self.config) {
+            let logs_root = self.config.logs_root_path.clone();
+            let live = self.sessions.iter().map(|s| s.id).collect();
+            std::thread::spawn(move || log_retention::prune_by_age(&logs_root, max_age, &live));
+        }
+    }
+
+    /// Everything a newly attached client needs to render the current state.
+    pub fn snapshot(&self) -> Event {
+        let logs = self
+            .sessions
+            .iter()
+            .map(|s| LogSnapshot {
+                id: s.id,
+                first_line: self.log_store.first_line(&s.id),
+                lines: self.log_store.lines(&s.id).to_vec(),
+                arrived: self.log_store.arrived(&s.id).to_vec(),
+            })
+            .filter(|log| !log.lines.is_empty())
+            .collect();
+        let terminals = self
+            .sessions
+            .iter()
+            .filter_map(|s| {
+                let (rows, cols, state) = self.terminals.snapshot(&s.id)?;
+                Some(TerminalSnapshot {
+                    id: s.id,
+                    rows,
+                    cols,
+                    state,
+                })
+            })
+            .collect();
+        Event::Snapshot {
+            sessions: self.sessions.clone(),
+            orphans: self.orphans.clone(),
+            logs,
+            terminals,
+        }
+    }
+
+    pub fn take_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Whether any session still has a PTY (or orphan kill) that needs polling.
+    pub fn is_busy(&self) -> bool {
+        !self.pty_handles.is_empty() || !self.orphan_kills.is_empty()
+    }
+
+    pub fn handle(&mut self, request: Request) {
+        match request {
+            Request::CreateSession { session } => {
+                if self.sessions.iter().all(|s| s.id != session.id) {
+                    self.journal.record(
+                        session.id,
+                        JournalEvent::Created {
+                            name: session.name.clone(),
+                            root_path: session.root_path.clone(),
+                        },
+                    );
+                    self.sessions.push(*session);
+                    self.sessions_changed();
+                }
+            }
+            Request::DeleteSession { id } => self.delete_session(id),
+            Request::RenameSession { id, name } => {
+                if let Some(session) = self.sessions.iter_mut().find(|s| s.id == id)
+                    && !name.is_empty()
+                    && session.name != name
+                {
+                    let from = std::mem::replace(&mut session.name, name.clone());
+                    self.journal.record(id, JournalEvent::Renamed { from, to: name });
+                    self.sessions_changed();
+                }
+            }
+            Request::Instruct {
+                id,
+                text,
+                cols,
+                rows,
+            } => self.instruct(id, &text, cols, rows),
+            Request::SendBytes { id, data } => self.send_raw(id, &data),
+            Request::Interrupt { id } => {
+                if let Some(handle) = self.pty_handles.get_mut(&id) {
+                    let _ = handle.send_sigint();
+                }
+            }
+            Request::ToggleNeedsInput { id } => self.toggle_needs_input(id),
+            Request::Stop { id } => self.stop_session(id),
+            Request::StopAll => self.stop_all(),
+            Request::Resize { id, cols, rows } => self.resize(id, cols, rows),
+            Request::ResolveOrphans { kill } => self.resolve_orphans(kill),
+        }
+    }
+
+    fn session_mut(&mut self, id: Uuid) -> Option<&mut Session> {
+        self.sessions.iter_mut().find(|s| s.id == id)
+    }
+
+    /// Detection profile of a session: its own pick while the config still has
+    /// it, else the one for its program.
+    fn profile_name(&self, session: &Session) -> String {
+        session
+            .needs_input_profile
+            .clone()
+            .filter(|name| self.needs_input_detectors.contains_key(name))
+            .or_else(|| {
+                let program = needs_input::program_name(&session.launch.program);
+                self.program_profiles.get(program).cloned()
+            })
+            .unwrap_or_else(|| self.config.needs_input_profile.clone())
+    }
+
+    /// What a session quiet past the timeout is doing, sampled at most every
+    /// `QUIET_PROBE_INTERVAL` (None until the next sample is due).
+    fn sample_quiet(&mut self, session_id: Uuid) -> Option<Quiet> {
+        if let Some((at, _)) = self.quiet_samples.get(&session_id)
+            && at.elapsed() < QUIET_PROBE_INTERVAL
+        {
+            return None;
+        }
+        let previous = self.quiet_samples.remove(&session_id).map(|(_, s)| s);
+        let pid = self.sessions.iter().find(|s| s.id == session_id)?.pty_pid;
+        match pid.and_then(|pid| proc_tree::sample(pid, previous.as_ref())) {
+            Some((quiet, sample)) => {
+                self.quiet_samples.insert(session_id, (Instant::now(), sample));
+                Some(quiet)
+            }
+            // Without a view of its processes, the silence is all there is to go by
+            None => Some(Quiet::Unseen),
+        }
+    }
+
+    fn save(&self) {
+        let _ = persistence::save_sessions(&self.config.sessions_file_path, &self.sessions);
+    }
+
+    /// Persist the session list and tell clients about it.
+    fn sessions_changed(&mut self) {
+        self.save();
+        for session in &self.sessions {
+            if let Some(recorder) = self.recorders.get_mut(&session.id) {
+                let _ = recorder.status(&session.status);
+            }
+        }
+        self.events.push(Event::Sessions {
+            sessions: self.sessions.clone(),
+        });
+    }
+
+    /// Record PTY output (or a notice) in the buffers and forward it to clients.
+    fn emit_output(&mut self, session_id: Uuid, data: &[u8]) {
+        if let Some(recorder) = self.recorders.get_mut(&session_id) {
+            let _ = recorder.output(data);
+        }
+        self.log_store.append(session_id, data);
+        self.terminals.process(session_id, data);
+        self.events.push(Event::Output {
+            id: session_id,
+            data: data.to_vec(),
+        });
+    }
+
+    /// Show a deck-generated message in the session's log and terminal view.
+    /// Also written to the log file, so it lines up with the in-memory log
+    /// when older lines are read back from disk.
+    fn push_notice(&mut self, session_id: Uuid, msg: &str) {
+        let data = msg.replace('\n', "\r\n");
+        if let Some(session) = self.sessions.iter().find(|s| s.id == session_id) {
+            let _ = persistence::append_log(&session.log_path, data.as_bytes());
+        }
+        self.emit_output(session_id, data.as_bytes());
+    }
+
+    fn delete_session(&mut self, session_id: Uuid) {
+        let Some(index) = self.sessions.iter().position(|s| s.id == session_id) else {
+            return;
+        };
+        // Don't leave the agent running behind a deleted session
+        if let Some(handle) = self.pty_handles.remove(&session_id) {
+            std::thread::spawn(move || handle.shutdown(STOP_GRACE));
+        }
+        self.stopping.remove(&session_id);
+        self.log_store.remove(&session_id);
+        self.redact_streams.remove(&session_id);
+        self.terminals.remove(&session_id);
+        self.applied_sizes.remove(&session_id);
+        self.recorders.remove(&session_id);
+        self.conversation_probes.remove(&session_id);
+        self.last_output_at.remove(&session_id);
+        self.quiet_samples.remove(&session_id);
+        let session = self.sessions.remove(index);
+        self.journal.record(session_id, JournalEvent::Deleted);
+        self.sessions_changed();
+
+        let action = self.config.log_on_delete;
+        if action != LogOnDelete::Keep {
+            let logs_root = self.config.logs_root_path.clone();
+            std::thread::spawn(move || {
+                log_retention::remove_session_logs(&session.log_path, &logs_root, action)
+            });
+        }
+    }
+
+    fn toggle_needs_input(&mut self, session_id: Uuid) {
+        let Some(session) = self.session_mut(session_id) else {
+            return;
+        };
+        match session.status {
+            SessionStatus::Running => {
+                let _ = session.transition_to(SessionStatus::NeedsInput);
+                session.needs_input_reason = Some(NeedsInputReason::Manual);
+                self.journal.record(
+                    session_id,
+                    JournalEvent::NeedsInput {
+                        source: NeedsInputSource::Manual,
+                    },
+                );
+            }
+            SessionStatus::NeedsInput => {
+                let _ = session.transition_to(SessionStatus::Running);
+                // Reset timeout timer on manual resume
+                self.last_output_at.insert(session_id, Instant::now());
+            }
+            _ => return,
+        }
+        self.sessions_changed();
+    }
+
+    /// Send an instruction line, spawning the session's agent first if needed.
+    fn instruct(&mut self, session_id: Uuid, text: &str, cols: u16, rows: u16) {
+        if !self.pty_handles.contains_key(&session_id) && !self.spawn(session_id, text, cols, rows) {
+            return;
+        }
+
+        // If session was NeedsInput, transition back to Running
+        self.resume_if_waiting(session_id);
+
+        let line = format!("{}\n", text);
+        if let Some(handle) = self.pty_handles.get_mut(&session_id) {
+            let _ = handle.send_input(&line);
+            if let Some(session) = self.sessions.iter().find(|s| s.id == session_id) {
+                let _ = persistence::write_instruction_marker(&session.log_path, text);
+            }
+            self.journal.record(
+                session_id,
+                JournalEvent::Instruction {
+                    text: text.to_string(),
+                },
+            );
+            if let Some(recorder) = self.recorders.get_mut(&session_id) {
+                let _ = recorder.instruction(text);
+                let _ = recorder.input(line.as_bytes());
+            }
+        }
+    }
+
+    /// Start the session's agent. Failures are reported in the session log and
+    /// leave it Failed; returns whether a PTY is now running.
+    fn spawn(&mut self, session_id: Uuid, instruction: &str, cols: u16, rows: u16) -> bool {
+        let Some(session) = self.sessions.iter().find(|s| s.id == session_id) else {
+            return false;
+        };
+        let root_path = session.root_path.clone();
+        let launch = session.launch.clone();
+        let resume_id = session
+            .conversation_id
+            .clone()
+            .filter(|_| conversation::is_claude(&launch));
+
+        // Validate directory still exists
+        if !root_path.is_dir() {
+            self.push_notice(
+                session_id,
+                &format!("エラー: ディレクトリが存在しません: {}\n", root_path.display()),
+            );
+            self.mark_failed(session_id);
+            return false;
+        }
+
+        // A new conversation gets its id from deck; it is recorded once Claude writes the transcript
+        let (spawn_launch, probe) = match &resume_id {
+            Some(id) => (conversation::resume_launch(&launch, id), None),
+            None => {
+                let id = Uuid::new_v4().to_string();
+                match conversation::new_conversation_launch(&launch, &id) {
+                    Some(started) => (started, Some(id)),
+                    None => (launch.clone(), None),
+                }
+            }
+        };
+        match PtyHandle::spawn(&root_path, &spawn_launch, cols, rows) {
+            Ok(handle) => {
+                let pid = handle.process_id();
+                self.pty_handles.insert(session_id, handle);
+                self.terminals.resize(session_id, rows, cols);
+                self.applied_sizes.insert(session_id, (cols, rows));
+                self.events.push(Event::Resized {
+                    id: session_id,
+                    cols,
+                    rows,
+                });
+                self.last_output_at.insert(session_id, Instant::now());
+                if let Some(id) = probe {
+                    self.conversation_probes.insert(session_id, (id, Instant::now()));
+                }
+                if let Some(session) = self.session_mut(session_id) {
+                    let _ = session.transition_to(SessionStatus::Running);
+                    session.pty_pid = pid;
+                    session.pty_started = pid.and_then(proc_tree::process_start);
+                    session.instruction = Some(instruction.to_string());
+                    if resume_id.is_none() {
+                        // Segments rotated out of the last run would read as this run's past
+                        let _ = log_retention::remove_segments(&session.log_path);
+                        let _ = persistence::write_log_header(&session.log_path, session);
+                    }
+                }
+                // Through the output path, so the log file and the in-memory
+                // lines stay aligned for paging back
+                if let Some(id) = &resume_id {
+                    self.push_notice(session_id, &persistence::resume_marker(id));
+                }
+                self.journal.record(
+                    session_id,
+                    JournalEvent::Spawned {
+                        program: launch.program.clone(),
+                        args: launch.args.clone(),
+                        pid,
+                        conversation_id: resume_id.clone(),
+                    },
+                );
+                self.start_recording(session_id, resume_id.is_some(), cols, rows);
+                self.sessions_changed();
+                true
+            }
+            Err(e) => {
+                let err_msg = format!("{}", e);
+                let not_found = err_msg.contains("No such file") || err_msg.contains("not found");
+                let msg = if not_found && launch.program == launch::DEFAULT_PROGRAM {
+                    "エラー: 'claude' コマンドが見つかりません。Claude Code をインストールしてください。\n"
+                        .to_string()
+                } else if not_found {
+                    format!("エラー: '{}' コマンドが見つかりません。\n", launch.program)
+                } else {
+                    format!("エラー: セッション開始に失敗: {}\n", e)
+                };
+                self.push_notice(session_id, &msg);
+                self.mark_failed(session_id);
+                false
+            }
+        }
+    }
+
+    /// Open the session's asciicast file: a fresh one for a new run, appended
+    /// to when the conversation is resumed (like the `.log` file).
+    fn start_recording(&mut self, session_id: Uuid, resumed: bool, cols: u16, rows: u16) {
+        if !self.config.record_sessions {
+            return;
+        }
+        let Some(session) = self.sessions.iter().find(|s| s.id == session_id) else {
+            return;
+        };
+        let path = recording::cast_path(&session.log_path);
+        let recorder = if resumed {
+            Recorder::resume(&path, cols, rows, &session.name)
+        } else {
+            Recorder::create(&path, cols, rows, &session.name)
+        };
+        match recorder {
+            Ok(recorder) => {
+                self.recorders.insert(session_id, recorder);
+            }
+            Err(e) => {
+                self.push_notice(session_id, &format!("エラー: 録画を開始できません: {}\n", e));
+            }
+        }
+    }
+
+    fn record_input(&mut self, session_id: Uuid, data: &[u8]) {
+        if let Some(recorder) = self.recorders.get_mut(&session_id) {
+            let _ = recorder.input(data);
+        }
+    }
+
+    fn mark_failed(&mut self, session_id: Uuid) {
+        if let Some(session) = self.session_mut(session_id) {
+            let _ = session.transition_to(SessionStatus::Running);
+            let _ = session.transition_to(SessionStatus::Failed);
+        }
+        self.sessions_changed();
+    }
+
+    /// Write raw bytes to a session's PTY and resume it if it was waiting for input.
+    fn send_raw(&mut self, session_id: Uuid, data: &[u8]) {
+        if let Some(handle) = self.pty_handles.get_mut(&session_id) {
+            let _ = handle.send_bytes(data);
+            self.record_input(session_id, data);
+            self.resume_if_waiting(session_id);
+        }
+    }
+
+    /// NeedsInput → Running once the user has answered, restarting the timeout timer.
+    fn resume_if_waiting(&mut self, session_id: Uuid) {
+        if let Some(session) = self.session_mut(session_id)
+            && session.status == SessionStatus::NeedsInput
+        {
+            let _ = session.transition_to(SessionStatus::Running);
+            self.last_output_at.insert(session_id, Instant::now());
+            self.sessions_changed();
+        }
+    }
+
+    /// Resize a live PTY and its terminal; clients follow via `Event::Resized`.
+    fn resize(&mut self, session_id: Uuid, cols: u16, rows: u16) {
+        let size = (cols.max(1), rows.max(1));
+        if self.applied_sizes.get(&session_id) == Some(&size) {
+            return;
+        }
+        if let Some(handle) = self.pty_handles.get(&session_id) {
+            let _ = handle.resize(size.0, size.1);
+            self.terminals.resize(session_id, size.1, size.0);
+            self.applied_sizes.insert(session_id, size);
+            if let Some(recorder) = self.recorders.get_mut(&session_id) {
+                let _ = recorder.resize(size.0, size.1);
+            }
+            self.events.push(Event::Resized {
+                id: session_id,
+                cols: size.0,
+                rows: size.1,
+            });
+        }
+    }
+
+    /// Drain PTY output, detect NeedsInput and exits, and run the periodic housekeeping.
+    pub fn tick(&mut self) {
+        // Collect info about active sessions (Running + NeedsInput) to avoid borrow conflicts
+        let active: Vec<(Uuid, std::path::PathBuf, SessionStatus, String)> = self
+            .sessions
+            .iter()
+            .filter(|s| {
+                s.status == SessionStatus::Running || s.status == SessionStatus::NeedsInput
+            })
+            .map(|s| (s.id, s.log_path.clone(), s.status.clone(), self.profile_name(s)))
+            .collect();
+
+        let mut finished: Vec<(Uuid, bool, u32)> = Vec::new();
+        let mut needs_input_detected: Vec<(Uuid, NeedsInputReason)> = Vec::new();
+        let mut state_changed = false;
+
+        for (session_id, log_path, status, profile) in &active {
+            let Some(handle) = self.pty_handles.get_mut(session_id) else {
+                continue;
+            };
+            // Drain output (including what was still buffered when the child exited)
+            let exit = handle.check_exit();
+            let mut chunks = handle.try_read();
+            if exit.is_some() {
+                chunks.extend(handle.drain_after_exit(EXIT_DRAIN_TIMEOUT));
+            }
+            let has_output = !chunks.is_empty();
+
+            // A pause in the output (or the exit) releases what was held back
+            let paused = !has_output
+                && self
+                    .last_output_at
+                    .get(session_id)
+                    .is_none_or(|last| last.elapsed() >= REDACT_HOLD);
+            let stream = self.redact_streams.entry(*session_id).or_default();
+            let mut output = stream.feed(&self.redactor, &chunks.concat());
+            if paused || exit.is_some() {
+                output.extend(stream.flush(&self.redactor));
+            }
+            if !output.is_empty() {
+                self.emit_output(*session_id, &output);
+                let _ = persistence::append_log(log_path, &output);
+            }
+            if has_output
+                && let Ok(Some(segment)) = log_retention::rotate_if_needed(
+                    log_path,
+                    self.config.log_max_bytes,
+                    self.config.log_rotate_keep,
+                )
+            {
+                std::thread::spawn(move || log_retention::compress(&segment));
+            }
+
+            if has_output {
+                self.last_output_at.insert(*session_id, Instant::now());
+                self.quiet_samples.remove(session_id);
+                if let Some(session) = self.session_mut(*session_id)
+                    && session.busy.take().is_some()
+                {
+                    state_changed = true;
+                }
+            }
+
+            // Check the bottom of the screen for a prompt (only for Running
+            // sessions), also when the output is a held-back prompt released
+            // by the pause after it
+            if !output.is_empty()
+                && *status == SessionStatus::Running
+                && let Some(detector) = self.needs_input_detectors.get(profile)
+                && let Some(reason) = detector.check_screen(
+                    &self
+                        .terminals
+                        .bottom_lines(session_id, needs_input::SCREEN_LINES),
+                )
+            {
+                needs_input_detected.push((*session_id, reason));
+            }
+
+            // Check exit
+            if let Some((success, code)) = exit {
+                finished.push((*session_id, success, code));
+            }
+        }
+
+        self.probe_conversations();
+        self.escalate_stops();
+        if self.pruned_at.elapsed() >= PRUNE_INTERVAL {
+            self.prune_logs();
+        }
+
+        // Running sessions without recent output wait for input only if they
+        // are blocked reading the terminal; otherwise they are just busy
+        let timeout = Duration::from_secs(self.config.needs_input_timeout_sec);
+        for (session_id, _, status, _) in &active {
+            if *status != SessionStatus::Running
+                || self
+                    .last_output_at
+                    .get(session_id)
+                    .is_none_or(|last| last.elapsed() <= timeout)
+            {
+                continue;
+            }
+            match self.sample_quiet(*session_id) {
+                Some(quiet @ (Quiet::WaitingForInput | Quiet::Unseen)) => needs_input_detected.push((
+                    *session_id,
+                    NeedsInputReason::Timeout {
+                        secs: self.config.needs_input_timeout_sec,
+                        unchecked: quiet == Quiet::Unseen,
+                    },
+                )),
+                Some(Quiet::Busy(process)) => {
+                    if let Some(session) = self.session_mut(*session_id)
+                        && session.busy.as_ref() != Some(&process)
+                    {
+                        session.busy = Some(process);
+                        state_changed = true;
+                    }
+                }
+                Some(Quiet::Idle) => {
+                    if let Some(session) = self.session_mut(*session_id)
+                        && session.busy.take().is_some()
+                    {
+                        state_changed = true;
+                    }
+                }
+                None => {}
+            }
+        }
+
+        // Transition to NeedsInput
+        for (id, reason) in needs_input_detected {
+            if let Some(session) = self.session_mut(id)
+                && session.status == SessionStatus::Running
+            {
+                let _ = session.transition_to(SessionStatus::NeedsInput);
+                let source = NeedsInputSource::from(&reason);
+                session.needs_input_reason = Some(reason);
+                self.journal.record(id, JournalEvent::NeedsInput { source });
+                state_changed = true;
+            }
+        }
+
+        // Handle finished sessions
+        for (id, success, code) in &finished {
+            self.pty_handles.remove(id);
+            self.redact_streams.remove(id);
+            self.applied_sizes.remove(id);
+            let recorder = self.recorders.remove(id);
+            self.conversation_probes.remove(id);
+            self.stopping.remove(id);
+            self.last_output_at.remove(id);
+            self.quiet_samples.remove(id);
+            self.record_conversation_from_output(*id);
+            self.journal.record(
+                *id,
+                JournalEvent::Exited {
+                    success: *success,
+                    code: *code as i32,
+                },
+            );
+            if let Some(session) = self.session_mut(*id) {
+                // NeedsInput → Running first if needed for valid transition
+                if session.status == SessionStatus::NeedsInput {
+                    let _ = session.transition_to(SessionStatus::Running);
+                }
+                if *success {
+                    let _ = session.transition_to(SessionStatus::Done);
+                } else {
+                    let _ = session.transition_to(SessionStatus::Failed);
+                }
+                session.exit_code = Some(*code as i32);
+                session.pty_pid = None;
+                session.pty_started = None;
+                if let Some(mut recorder) = recorder {
+                    let _ = recorder.status(&session.status);
+                }
+            }
+            state_changed = true;
+        }
+
+        if state_changed {
+            self.sessions_changed();
+        }
+    }
+
+    /// Stop a session's agent: SIGTERM to its process group now,
+    /// SIGKILL from `escalate_stops` if it is still running after STOP_GRACE.
+    fn stop_session(&mut self, session_id: Uuid) {
+        if self.stopping.contains_key(&session_id) {
+            return;
+        }
+        let Some(handle) = self.pty_handles.get(&session_id) else {
+            return;
+        };
+        match handle.terminate() {
+            Ok(()) => {
+                self.stopping.insert(session_id, Instant::now());
+                self.push_notice(session_id, "\nセッションを停止しています (SIGTERM)\n");
+            }
+            Err(e) => {
+                self.push_notice(session_id, &format!("\nエラー: 停止に失敗: {}\n", e));
+            }
+        }
+    }
+
+    /// SIGKILL process groups that ignored SIGTERM for longer than STOP_GRACE.
+    fn escalate_stops(&mut self) {
+        let overdue: Vec<Uuid> = self
+            .stopping
+            .iter()
+            .filter(|(_, since)| since.elapsed() >= STOP_GRACE)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in overdue {
+            self.stopping.remove(&id);
+            if let Some(handle) = self.pty_handles.get(&id) {
+                let _ = handle.kill();
+                self.push_notice(id, "応答がないため強制終了しました (SIGKILL)\n");
+            }
+        }
+
+        self.orphan_kills.retain(|(pgid, start, since)| {
+            if !pty_manager::group_started(*pgid, start) {
+                return false;
+            }
+            if since.elapsed() < STOP_GRACE {
+                return true;
+            }
+            let _ = pty_manager::signal_group(*pgid, libc::SIGKILL);
+            false
+        });
+    }
+
+    /// Shut down every live session in parallel, waiting at most STOP_GRACE.
+    fn stop_all(&mut self) {
+        let shutdowns: Vec<_> = self
+            .pty_handles
+            .drain()
+            .map(|(_, handle)| std::thread::spawn(move || handle.shutdown(STOP_GRACE)))
+            .collect();
+        for shutdown in shutdowns {
+            let _ = shutdown.join();
+        }
+        self.stopping.clear();
+        self.redact_streams.clear();
+        self.applied_sizes.clear();
+        self.conversation_probes.clear();
+        self.last_output_at.clear();
+        self.quiet_samples.clear();
+        for session in &mut self.sessions {
+            session.pty_pid = None;
+            session.pty_started = None;
+            // Not a transition of the state machine: the session can simply be run again
+            if session.status == SessionStatus::Running || session.status == SessionStatus::NeedsInput {
+                session.status = SessionStatus::Queued;
+                session.needs_input_reason = None;
+                session.busy = None;
+            }
+        }
+        // Recordings end with the status the sessions were stopped in
+        self.sessions_changed();
+        self.recorders.clear();
+    }
+
+    fn resolve_orphans(&mut self, kill: bool) {
+        let orphans = std::mem::take(&mut self.orphans);
+        if kill {
+            for (session_id, pgid) in orphans {
+                let Some(session) = self.session_mut(session_id) else {
+                    continue;
+                };
+                session.pty_pid = None;
+                let Some(start) = session.pty_started.take() else {
+                    continue;
+                };
+                // Check again: the group may have exited and its id been reused
+                // while the dialog was open
+                if pty_manager::group_started(pgid, &start)
+                    && pty_manager::signal_group(pgid, libc::SIGTERM).is_ok()
+                {
+                    self.orphan_kills.push((pgid, start, Instant::now()));
+                }
+            }
+            self.sessions_changed();
+        }
+        self.events.push(Event::Orphans {
+            orphans: Vec::new(),
+        });
+    }
+
+    /// Record the conversation id deck started a Claude session with once its
+    /// transcript shows up in the project directory (the conversation exists
+    /// only after the first message, so resuming it earlier would fail).
+    fn probe_conversations(&mut self) {
+        let Some(claude_home) = conversation::claude_home() else {
+            return;
+        };
+        let due: Vec<(Uuid, String)> = self
+            .conversation_probes
+            .iter()
+            .filter(|(_, (_, last))| last.elapsed() >= CONVERSATION_PROBE_INTERVAL)
+            .map(|(id, (conversation_id, _))| (*id, conversation_id.clone()))
+            .collect();
+
+        let mut found_any = false;
+        for (id, conversation_id) in due {
+            self.conversation_probes
+                .insert(id, (conversation_id.clone(), Instant::now()));
+            let Some(session) = self.sessions.iter().find(|s| s.id == id) else {
+                continue;
+            };
+            let dir = conversation::project_dir(&claude_home, &session.root_path);
+            if !conversation::has_transcript(&dir, &conversation_id) {
+                continue;
+            }
+            if let Some(session) = self.session_mut(id) {
+                session.conversation_id = Some(conversation_id);
+            }
+            self.conversation_probes.remove(&id);
+            found_any = true;
+        }
+        if found_any {
+            self.sessions_changed();
+        }
+    }
+
+    /// Check the last lines a finished Claude session printed for its resume hint,
+    /// and forget the conversation if Claude could not resume it.
+    fn record_conversation_from_output(&mut self, session_id: Uuid) {
+        let lines = self.log_store.lines(&session_id);
+        let start = lines.len().saturating_sub(CONVERSATION_OUTPUT_LINES);
+        let mut failed = false;
+        let mut announced = None;
+        for line in lines[start..].iter().rev() {
+            if conversation::is_resume_failure(line) {
+                failed = true;
+                break;
+            }
+            if let Some(id) = conversation::id_from_output(line) {
+                announced = Some(id);
+                break;
+            }
+        }
+
+        let Some(session) = self.session_mut(session_id) else {
+            return;
+        };
+        if failed {
+            if let Some(old) = session.conversation_id.take() {
+                self.push_notice(
+                    session_id,
+                    &format!("会話 {} を再開できませんでした。次の指示で新しい会話を開始します。\n", old),
+                );
+            }
+        } else if announced.is_some() {
+            session.conversation_id = announced;
+        }
+    }
+}
+
+/// Sessions whose process group from a previous run is still alive.
+/// Recorded pids whose group is gone, or can't be shown to be the one the
+/// session started, are cleared.
+fn detect_orphans(sessions: &mut [Session]) -> Vec<(Uuid, u32)> {
+    let mut orphans = Vec::new();
+    for session in sessions {
+        if let Some(pgid) = session.pty_pid {
+            let ours = session
+                .pty_started
+                .as_ref()
+                .is_some_and(|start| pty_manager::group_started(pgid, start));
+            if ours {
+                orphans.push((session.id, pgid));
+            } else {
+                session.pty_pid = None;
+                session.pty_started = None;
+            }
+        }
+    }
+    orphans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::launch::LaunchSpec;
+
+    fn engine(dir: &std::path::Path) -> SessionEngine {
+        SessionEngine::new(AppConfig::in_dir(dir))
+    }
+
+    fn new_session(dir: &std::path::Path, command: &str) -> Session {
+        let mut session = Session::new("s".to_string(), dir.to_path_buf(), &dir.join("logs"));
+        session.launch = LaunchSpec::parse(command, "").unwrap();
+        session
+    }
+
+    /// Tick until `done` holds for the collected events (or give up after 5s).
+    fn tick_until(engine: &mut SessionEngine, done: impl Fn(&[Event]) -> bool) -> Vec<Event> {
+        let mut events = Vec::new();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !done(&events) && Instant::now() < deadline {
+            engine.tick();
+            events.extend(engine.take_events());
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        events
+    }
+
+    #[test]
+    fn session_changes_are_persisted_and_broadcast() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let mut engine = engine(tmp.path());
+        let session = new_session(tmp.path(), "sh");
+        let id = session.id;
+
+        engine.handle(Request::CreateSession {
+            session: Box::new(session),
+        });
+        engine.handle(Request::RenameSession {
+            id,
+            name: "renamed".to_string(),
+        });
+        let events = engine.take_events();
+        assert!(matches!(
+            events.last(),
+            Some(Event::Sessions { sessions }) if sessions[0].name == "renamed"
+        ));
+        let saved = persistence::load_sessions(&tmp.path().join("sessions.json"));
+        assert_eq!(saved[0].name, "renamed");
+
+        engine.handle(Request::DeleteSession { id });
+        assert!(matches!(
+            engine.take_events().last(),
+            Some(Event::Sessions { sessions }) if sessions.is_empty()
+        ));
+    }
+
+    #[test]
+    fn secrets_split_across_reads_are_redacted() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let mut engine = engine(tmp.path());
+        let session = new_session(
+            tmp.path(),
+            "sh -c 'read x; printf API_KEY=abc; sleep 0.05; echo defghijkl done'",
+        );
+        let (id, log_path) = (session.id, session.log_path.clone());
+        engine.handle(Request::CreateSession {
+            session: Box::new(session),
+        });
+        engine.handle(Request::Instruct {
+            id,
+            text: "go".to_string(),
+            cols: 40,
+            rows: 10,
+        });
+        let events = tick_until(&mut engine, |events| {
+            events.iter().any(|e| {
+                matches!(e, Event::Sessions { sessions } if sessions[0].status == SessionStatus::Done)
+            })
+        });
+        let output: Vec<u8> = events
+            .iter()
+            .filter_map(|e| match e {
+                Event::Output { data, .. } => Some(data.clone()),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        let output = String::from_utf8_lossy(&output);
+        assert!(output.contains("API_KEY=[REDACTED] done"), "{}", output);
+        let log = std::fs::read_to_string(&log_path).unwrap();
+        assert!(log.contains("API_KEY=[REDACTED] done") && !log.contains("abc"));
+    }
+
+    #[test]
+    fn resumed_runs_page_back_in_line_with_memory() {
+        use crate::log_pager::LogHistory;
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::TempDir::new().unwrap();
+        let claude = tmp.path().join("claude");
+        std::fs::write(&claude, "#!/bin/sh\nread x\necho \"run $x\"\n").unwrap();
+        std::fs::set_permissions(&claude, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let mut engine = engine(tmp.path());
+        let session = new_session(tmp.path(), &claude.display().to_string());
+        let (id, log_path) = (session.id, session.log_path.clone());
+        engine.handle(Request::CreateSession {
+            session: Box::new(session),
+        });
+
+        let mut history = LogHistory::new(&log_path);
+        for (run, text) in ["one", "two"].into_iter().enumerate() {
+            if run == 1 {
+                // As if requeued by a restart, with the conversation to resume
+                let session = engine.session_mut(id).unwrap();
+                session.status = SessionStatus::Queued;
+                session.conversation_id = Some("0b6f3c52-8d1e-4a8e-9a53-2f1d3c4b5a69".to_string());
+            }
+            engine.handle(Request::Instruct {
+                id,
+                text: text.to_string(),
+                cols: 40,
+                rows: 10,
+            });
+            let done = format!("run {}", text);
+            tick_until(&mut engine, |events| {
+                events.iter().any(|e| {
+                    matches!(e, Event::Output { data, .. } if String::from_utf8_lossy(data).contains(&done))
+                }) && events.iter().any(|e| {
+                    matches!(e, Event::Sessions { sessions } if sessions[0].status == SessionStatus::Done)
+                })
+            });
+            // The client fixes the offset while the first run is in memory
+            let memory = engine.log_store.lines(&id);
+            history
+                .sync(engine.log_store.first_line(&id), memory.len())
+                .unwrap();
+        }
+
+        let memory = engine.log_store.lines(&id).to_vec();
+        assert!(memory.iter().any(|l| l.starts_with("# Resumed:")), "{:?}", memory);
+        let mut paged = Vec::new();
+        history.read(0..memory.len(), |_, lines| paged.extend(lines)).unwrap();
+        assert_eq!(paged, memory);
+    }
+
+    #[test]
+    fn sessions_detect_prompts_with_their_profile() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let mut config = AppConfig::in_dir(tmp.path());
+        config.needs_input_profiles.insert(
+            "deploy".to_string(),
+            crate::needs_input::NeedsInputProfile {
+                include: vec![r"^Ship it\?".to_string()],
+                ..Default::default()
+            },
+        );
+        let mut engine = SessionEngine::new(config);
+        let mut session = new_session(tmp.path(), "sh -c 'read x; echo Ship it?; sleep 5'");
+        session.needs_input_profile = Some("deploy".to_string());
+        let id = session.id;
+        engine.handle(Request::CreateSession {
+            session: Box::new(session),
+        });
+        engine.handle(Request::Instruct {
+            id,
+            text: "go".to_string(),
+            cols: 40,
+            rows: 10,
+        });
+        tick_until(&mut engine, |events| {
+            events.iter().any(|e| {
+                matches!(e, Event::Sessions { sessions } if sessions[0].status == SessionStatus::NeedsInput)
+            })
+        });
+        assert_eq!(engine.sessions[0].status, SessionStatus::NeedsInput);
+        assert_eq!(
+            engine.sessions[0].needs_input_reason,
+            Some(NeedsInputReason::Pattern {
+                pattern: r"^Ship it\?".to_string(),
+                text: "Ship it?".to_string(),
+            })
+        );
+        engine.handle(Request::ToggleNeedsInput { id });
+        assert_eq!(engine.sessions[0].needs_input_reason, None);
+        engine.handle(Request::ToggleNeedsInput { id });
+        assert_eq!(engine.sessions[0].needs_input_reason, Some(NeedsInputReason::Manual));
+        engine.handle(Request::DeleteSession { id });
+    }
+
+    #[test]
+    fn prompts_without_a_trailing_newline_are_detected() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let mut engine = engine(tmp.path());
+        let session = new_session(tmp.path(), "sh -c \"read x; printf 'Proceed?'; sleep 5\"");
+        let id = session.id;
+        engine.handle(Request::CreateSession {
+            session: Box::new(session),
+        });
+        engine.handle(Request::Instruct {
+            id,
+            text: "go".to_string(),
+            cols: 40,
+            rows: 10,
+        });
+        tick_until(&mut engine, |events| {
+            events.iter().any(|e| {
+                matches!(e, Event::Sessions { sessions } if sessions[0].status == SessionStatus::NeedsInput)
+            })
+        });
+        assert!(matches!(
+            &engine.sessions[0].needs_input_reason,
+            Some(NeedsInputReason::Pattern { text, .. }) if text == "Proceed?"
+        ));
+        engine.handle(Request::DeleteSession { id });
+    }
+
+    #[test]
+    fn stop_all_requeues_sessions_without_their_wait_state() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let mut engine = engine(tmp.path());
+        let session = new_session(tmp.path(), "sh -c 'read x; sleep 30'");
+        let id = session.id;
+        engine.handle(Request::CreateSession {
+            session: Box::new(session),
+        });
+        engine.handle(Request::Instruct {
+            id,
+            text: "go".to_string(),
+            cols: 40,
+            rows: 10,
+        });
+        let _ = engine.sessions[0].transition_to(SessionStatus::NeedsInput);
+        engine.sessions[0].needs_input_reason = Some(NeedsInputReason::Manual);
+        engine.sessions[0].busy = Some("sleep".to_string());
+
+        engine.handle(Request::StopAll);
+        let session = &engine.sessions[0];
+        assert_eq!(session.status, SessionStatus::Queued);
+        assert_eq!((&session.needs_input_reason, &session.busy), (&None, &None));
+        assert!(engine.last_output_at.is_empty() && engine.redact_streams.is_empty());
+        // Nor are they saved for the next start
+        let saved = persistence::load_sessions(&tmp.path().join("sessions.json"));
+        assert_eq!(saved[0].needs_input_reason, None);
+    }
+
+    #[test]
+    fn quiet_sessions_wait_only_when_reading_the_terminal() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let mut engine = SessionEngine::new(AppConfig {
+            needs_input_timeout_sec: 0,
+            ..AppConfig::in_dir(tmp.path())
+        });
+        let working = new_session(tmp.path(), "sh -c 'read x; sleep 5'");
+        let waiting = new_session(tmp.path(), "sh -c 'read x; read y'");
+        let ids = [working.id, waiting.id];
+        for session in [working, waiting] {
+            let id = session.id;
+            engine.handle(Request::CreateSession {
+                session: Box::new(session),
+            });
+            engine.handle(Request::Instruct {
+                id,
+                text: "go".to_string(),
+                cols: 40,
+                rows: 10,
+            });
+        }
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while Instant::now() < deadline
+            && (engine.sessions[0].busy.is_none()
+                || engine.sessions[1].status != SessionStatus::NeedsInput)
+        {
+            engine.tick();
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(engine.sessions[0].status, SessionStatus::Running);
+        assert_eq!(engine.sessions[0].busy.as_deref(), Some("sleep"));
+        assert_eq!(engine.sessions[1].status, SessionStatus::NeedsInput);
+        assert_eq!(
+            engine.sessions[1].needs_input_reason,
+            Some(NeedsInputReason::Timeout {
+                secs: 0,
+                unchecked: false
+            })
+        );
+        for id in ids {
+            engine.handle(Request::DeleteSession { id });
+        }
+    }
+
+    #[test]
+    fn instruct_spawns_and_streams_output() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let mut engine = engine(tmp.path());
+        let session = new_session(tmp.path(), "sh -c 'read line; echo got:$line'");
+        let id = session.id;
+        engine.handle(Request::CreateSession {
+            session: Box::new(session),
+        });
+        engine.handle(Request::Instruct {
+            id,
+            text: "hello".to_string(),
+            cols: 40,
+            rows: 10,
+        });
+        assert!(engine
+            .take_events()
+            .contains(&Event::Resized { id, cols: 40, rows: 10 }));
+
+        let events = tick_until(&mut engine, |events| {
+            events.iter().any(|e| {
+                matches!(e, Event::Sessions { sessions } if sessions[0].status == SessionStatus::Done)
+            })
+        });
+        let output: Vec<u8> = events
+            .iter()
+            .filter_map(|e| match e {
+                Event::Output { data, .. } => Some(data.clone()),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        assert!(String::from_utf8_lossy(&output).contains("got:hello"));
+        assert!(!engine.is_busy());
+
+        // The journal has the run from spawn to exit
+        let journal = std::fs::read_to_string(tmp.path().join("events.jsonl")).unwrap();
+        let events: Vec<String> = journal
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .inspect(|entry| assert_eq!(entry["session_id"], id.to_string()))
+            .map(|entry| entry["event"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(events, ["created", "spawned", "instruction", "exited"]);
+
+        // A later client sees the same output in its snapshot
+        let Event::Snapshot { logs, terminals, .. } = engine.snapshot() else {
+            panic!("expected snapshot");
+        };
+        assert!(logs[0].lines.iter().any(|l| l == "got:hello"));
+        assert_eq!((terminals[0].rows, terminals[0].cols), (10, 40));
+    }
+
+    #[test]
+    fn records_session_as_asciicast() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let mut engine = SessionEngine::new(AppConfig {
+            record_sessions: true,
+            ..AppConfig::in_dir(tmp.path())
+        });
+        let session = new_session(tmp.path(), "sh -c 'read line; echo got:$line'");
+        let id = session.id;
+        let cast = recording::cast_path(&session.log_path);
+        engine.handle(Request::CreateSession {
+            session: Box::new(session),
+        });
+        engine.handle(Request::Instruct {
+            id,
+            text: "hello".to_string(),
+            cols: 40,
+            rows: 10,
+        });
+        tick_until(&mut engine, |events| {
+            events.iter().any(|e| {
+                matches!(e, Event::Sessions { sessions } if sessions[0].status == SessionStatus::Done)
+            })
+        });
+
+        let content = std::fs::read_to_string(&cast).unwrap();
+        let mut lines = content.lines();
+        assert!(lines.next().unwrap().contains(r#""width":40"#));
+        let events: Vec<(f64, String, String)> =
+            lines.map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert!(events.iter().any(|(_, code, data)| code == "i" && data == "hello\n"));
+        assert!(events.iter().any(|(_, code, data)| code == "o" && data.contains("got:hello")));
+    }
+
+    #[test]
+    fn missing_directory_fails_session() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let mut engine = engine(tmp.path());
+        let session = new_session(&tmp.path().join("gone"), "sh");
+        let id = session.id;
+        engine.handle(Request::CreateSession {
+            session: Box::new(session),
+        });
+        engine.handle(Request::Instruct {
+            id,
+            text: "hi".to_string(),
+            cols: 80,
+            rows: 24,
+        });
+        let events = engine.take_events();
+        assert!(events.iter().any(|e| matches!(e, Event::Output { .. })));
+        assert!(matches!(
+            events.last(),
+            Some(Event::Sessions { sessions }) if sessions[0].status == SessionStatus::Failed
+        ));
+    }
+
+    #[test]
+    fn orphans_must_match_their_recorded_start() {
+        use std::os::unix::process::CommandExt;
+        let tmp = tempfile::TempDir::new().unwrap();
+        // In its own group, so its pid is the group id
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        let pid = child.id();
+        let start = proc_tree::process_start(pid).unwrap();
+
+        let mut ours = new_session(tmp.path(), "sh");
+        ours.pty_pid = Some(pid);
+        ours.pty_started = Some(start.clone());
+        let mut reused = new_session(tmp.path(), "sh");
+        reused.pty_pid = Some(pid);
+        reused.pty_started = Some(ProcessStart {
+            ticks: start.ticks.saturating_sub(1),
+            ..start
+        });
+        let mut unverified = new_session(tmp.path(), "sh");
+        unverified.pty_pid = Some(pid);
+
+        let mut sessions = vec![ours, reused, unverified];
+        let orphans = detect_orphans(&mut sessions);
+        assert_eq!(orphans, vec![(sessions[0].id, pid)]);
+        assert!(sessions[1].pty_pid.is_none() && sessions[1].pty_started.is_none());
+        assert!(sessions[2].pty_pid.is_none());
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
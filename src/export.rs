@@ -0,0 +1,729 @@
+use crate::bookmarks::{self, Bookmark};
+use crate::log_retention;
+use crate::persistence;
+use crate::session::Session;
+use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use vte::{Params, Parser, Perform};
+
+/// Transcript file format, picked by the file extension (`.html`/`.htm`, else Markdown).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Markdown,
+    Html,
+}
+
+impl Format {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("html") || ext.eq_ignore_ascii_case("htm") => {
+                Self::Html
+            }
+            _ => Self::Markdown,
+        }
+    }
+}
+
+/// A piece of a session transcript, in log order.
+#[derive(Debug, PartialEq)]
+pub enum Entry {
+    /// An instruction sent to the agent (marked in the log by the engine)
+    Instruction { at: Option<DateTime<Utc>>, text: String },
+    /// A line of output, split where its style changes
+    Line(Vec<Run>),
+    /// The user's bookmark on the line before it
+    Bookmark(Bookmark),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Run {
+    pub text: String,
+    pub pen: Pen,
+}
+
+/// SGR attributes of the output text.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Pen {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub inverse: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Color {
+    Idx(u8),
+    Rgb(u8, u8, u8),
+}
+
+/// Where the export prompt suggests writing the transcript: the current
+/// directory, named after the session and the time.
+pub fn default_path(session: &Session) -> PathBuf {
+    let name: String = session
+        .name
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .collect();
+    let file = format!("{}-{}.md", name.trim_matches('-'), Utc::now().format("%Y%m%d-%H%M"));
+    std::env::current_dir().unwrap_or_default().join(file)
+}
+
+/// The path typed at the export prompt, with a leading `~` standing for the
+/// home directory as in a shell.
+pub fn expand_path(typed: &str) -> PathBuf {
+    let typed = typed.trim();
+    let rest = match typed.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => rest.trim_start_matches('/'),
+        _ => return PathBuf::from(typed),
+    };
+    match dirs::home_dir() {
+        Some(home) => home.join(rest),
+        None => PathBuf::from(typed),
+    }
+}
+
+/// Write the session's transcript to `path` in the format its extension asks for.
+pub fn export(session: &Session, path: &Path) -> io::Result<()> {
+    let entries = read_transcript(&session.log_path)?;
+    let text = match Format::from_path(path) {
+        Format::Markdown => markdown(session, &entries),
+        Format::Html => html(session, &entries),
+    };
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, text)
+}
+
+/// The session's whole log (rotated segments first) as transcript entries,
+/// with its bookmarks and without the header deck writes at the top of the log.
+pub fn read_transcript(log_path: &Path) -> io::Result<Vec<Entry>> {
+    let mut parser = Parser::new();
+    let mut builder = TranscriptBuilder::default();
+    // Transcript line each file starts at, by segment number
+    let mut starts = Vec::new();
+    for (n, segment) in log_retention::numbered_segments(log_path) {
+        starts.push((n, builder.lines));
+        let mut bytes = Vec::new();
+        let file = fs::File::open(&segment)?;
+        if segment.extension().is_some_and(|e| e == "gz") {
+            GzDecoder::new(file).read_to_end(&mut bytes)?;
+        } else {
+            io::BufReader::new(file).read_to_end(&mut bytes)?;
+        }
+        parser.advance(&mut builder, &bytes);
+    }
+    starts.push((log_retention::current_segment(log_path), builder.lines));
+    match fs::read(log_path) {
+        Ok(bytes) => parser.advance(&mut builder, &bytes),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    let mut entries = insert_bookmarks(builder.finish(), &starts, bookmarks::read(log_path)?);
+    skip_log_header(&mut entries);
+    Ok(entries)
+}
+
+/// Put each bookmark after the line it marks, leaving out those whose line
+/// is no longer in the log.
+fn insert_bookmarks(entries: Vec<Entry>, starts: &[(u64, usize)], bookmarks: Vec<Bookmark>) -> Vec<Entry> {
+    let mut marked: Vec<(usize, Bookmark)> = bookmarks
+        .into_iter()
+        .filter_map(|b| {
+            let (_, start) = starts.iter().find(|(n, _)| *n == b.segment)?;
+            Some((start + b.line, b))
+        })
+        .collect();
+    marked.sort_by_key(|(line, _)| *line);
+    let mut marked = marked.into_iter().peekable();
+
+    let mut out = Vec::with_capacity(entries.len());
+    let mut line = 0;
+    for entry in entries {
+        let text = match &entry {
+            Entry::Line(runs) => Some(line_text(runs)),
+            _ => None,
+        };
+        out.push(entry);
+        if let Some(text) = text {
+            while let Some((_, bookmark)) = marked.next_if(|(n, _)| *n <= line) {
+                if bookmark.matches(&text) {
+                    out.push(Entry::Bookmark(bookmark));
+                }
+            }
+            line += 1;
+        }
+    }
+    out
+}
+
+/// Drop the `# Session: ...` block up to its `---` line.
+fn skip_log_header(entries: &mut Vec<Entry>) {
+    let starts_with_header = matches!(entries.first(), Some(Entry::Line(runs)) if line_text(runs).starts_with("# Session: "));
+    if !starts_with_header {
+        return;
+    }
+    if let Some(end) = entries
+        .iter()
+        .position(|e| matches!(e, Entry::Line(runs) if line_text(runs) == "---"))
+    {
+        entries.drain(..=end);
+    }
+}
+
+fn line_text(runs: &[Run]) -> String {
+    runs.iter().map(|run| run.text.as_str()).collect()
+}
+
+pub fn markdown(session: &Session, entries: &[Entry]) -> String {
+    let mut out = format!("# {}\n\n", session.name);
+    for (label, value) in metadata(session) {
+        out.push_str(&format!("- **{}:** {}\n", label, value));
+    }
+    for section in sections(entries) {
+        out.push('\n');
+        if let Some((n, at, text)) = section.instruction {
+            out.push_str(&format!("## {}\n\n", instruction_title(n, at)));
+            for line in text.lines() {
+                match line {
+                    "" => out.push_str(">\n"),
+                    line => out.push_str(&format!("> {}\n", line)),
+                }
+            }
+            out.push('\n');
+        } else {
+            out.push_str("## Output\n\n");
+        }
+        // Output in code blocks, split by the bookmarks between its lines
+        let chunks: Vec<String> = section
+            .items
+            .chunk_by(|a, b| matches!((a, b), (Item::Line(_), Item::Line(_))))
+            .map(|chunk| match chunk {
+                [Item::Bookmark(bookmark)] => format!("> {}\n", bookmark_label(bookmark, true)),
+                lines => {
+                    let lines: Vec<String> = lines
+                        .iter()
+                        .filter_map(|item| match item {
+                            Item::Line(runs) => Some(line_text(runs)),
+                            Item::Bookmark(_) => None,
+                        })
+                        .collect();
+                    let fence = "`".repeat(longest_backtick_run(&lines).max(2) + 1);
+                    let mut block = format!("{}text\n", fence);
+                    for line in &lines {
+                        block.push_str(line.trim_end());
+                        block.push('\n');
+                    }
+                    block.push_str(&fence);
+                    block.push('\n');
+                    block
+                }
+            })
+            .collect();
+        out.push_str(&chunks.join("\n"));
+    }
+    out
+}
+
+pub fn html(session: &Session, entries: &[Entry]) -> String {
+    let mut out = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n",
+    );
+    out.push_str(&format!("<title>{}</title>\n", escape_html(&session.name)));
+    out.push_str(concat!(
+        "<style>\n",
+        "body { font-family: sans-serif; max-width: 120ch; margin: 2em auto; }\n",
+        "pre { background: #1e1e1e; color: #e5e5e5; padding: 1em; overflow-x: auto; }\n",
+        "blockquote { border-left: 4px solid #ccc; margin: 0; padding: 0 1em; white-space: pre-wrap; }\n",
+        "mark.bookmark { background: #ffd75f; color: #1e1e1e; }\n",
+        "</style>\n</head>\n<body>\n",
+    ));
+    out.push_str(&format!("<h1>{}</h1>\n<dl>\n", escape_html(&session.name)));
+    for (label, value) in metadata(session) {
+        out.push_str(&format!(
+            "<dt>{}</dt><dd>{}</dd>\n",
+            label,
+            escape_html(&value)
+        ));
+    }
+    out.push_str("</dl>\n");
+    for section in sections(entries) {
+        match section.instruction {
+            Some((n, at, text)) => out.push_str(&format!(
+                "<h2>{}</h2>\n<blockquote>{}</blockquote>\n",
+                escape_html(&instruction_title(n, at)),
+                escape_html(text)
+            )),
+            None => out.push_str("<h2>Output</h2>\n"),
+        }
+        out.push_str("<pre>");
+        for item in section.items {
+            let runs = match item {
+                Item::Line(runs) => runs,
+                Item::Bookmark(bookmark) => {
+                    out.push_str(&format!(
+                        "<mark class=\"bookmark\">{}</mark>\n",
+                        escape_html(&bookmark_label(bookmark, false))
+                    ));
+                    continue;
+                }
+            };
+            for run in runs {
+                let text = escape_html(&run.text);
+                match css(&run.pen) {
+                    Some(style) => out.push_str(&format!("<span style=\"{}\">{}</span>", style, text)),
+                    None => out.push_str(&text),
+                }
+            }
+            out.push('\n');
+        }
+        out.push_str("</pre>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+fn metadata(session: &Session) -> Vec<(&'static str, String)> {
+    vec![
+        ("Directory", session.root_path.display().to_string()),
+        ("Created", session.created_at.format("%Y-%m-%d %H:%M:%S UTC").to_string()),
+        ("Status", format!("{:?}", session.status)),
+        (
+            "Exit code",
+            session.exit_code.map_or("-".to_string(), |code| code.to_string()),
+        ),
+    ]
+}
+
+fn instruction_title(n: usize, at: Option<DateTime<Utc>>) -> String {
+    match at {
+        Some(at) => format!("Instruction {} ({})", n, at.format("%Y-%m-%d %H:%M:%S UTC")),
+        None => format!("Instruction {}", n),
+    }
+}
+
+/// `🔖 note (time)`, the note in bold for Markdown.
+fn bookmark_label(bookmark: &Bookmark, markdown: bool) -> String {
+    let at = bookmark.created_at.format("%Y-%m-%d %H:%M:%S UTC");
+    match bookmark.note.as_str() {
+        "" => format!("🔖 ({})", at),
+        note if markdown => format!("🔖 **{}** ({})", note, at),
+        note => format!("🔖 {} ({})", note, at),
+    }
+}
+
+/// Output grouped under the instruction it followed (none for output before
+/// the first instruction), blank lines at either end dropped.
+struct Section<'a> {
+    instruction: Option<(usize, Option<DateTime<Utc>>, &'a str)>,
+    items: Vec<Item<'a>>,
+}
+
+/// An output line of a section, or a bookmark between them.
+enum Item<'a> {
+    Line(&'a [Run]),
+    Bookmark(&'a Bookmark),
+}
+
+fn sections(entries: &[Entry]) -> Vec<Section<'_>> {
+    let mut sections = vec![Section {
+        instruction: None,
+        items: Vec::new(),
+    }];
+    let mut count = 0;
+    for entry in entries {
+        match entry {
+            Entry::Instruction { at, text } => {
+                count += 1;
+                sections.push(Section {
+                    instruction: Some((count, *at, text.as_str())),
+                    items: Vec::new(),
+                });
+            }
+            Entry::Line(runs) => sections.last_mut().expect("starts with one").items.push(Item::Line(runs)),
+            Entry::Bookmark(bookmark) => sections
+                .last_mut()
+                .expect("starts with one")
+                .items
+                .push(Item::Bookmark(bookmark)),
+        }
+    }
+    let blank = |item: &Item| match item {
+        Item::Line(runs) => runs.iter().all(|run| run.text.trim().is_empty()),
+        Item::Bookmark(_) => false,
+    };
+    for section in &mut sections {
+        let start = section.items.iter().position(|i| !blank(i)).unwrap_or(section.items.len());
+        let end = section.items.iter().rposition(|i| !blank(i)).map_or(start, |i| i + 1);
+        section.items.truncate(end);
+        section.items.drain(..start);
+    }
+    sections.retain(|s| s.instruction.is_some() || !s.items.is_empty());
+    sections
+}
+
+fn longest_backtick_run(lines: &[String]) -> usize {
+    lines
+        .iter()
+        .flat_map(|line| line.split(|c| c != '`'))
+        .map(str::len)
+        .max()
+        .unwrap_or(0)
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Inline CSS for a run; None for plain text.
+fn css(pen: &Pen) -> Option<String> {
+    let (mut fg, mut bg) = (pen.fg.map(css_color), pen.bg.map(css_color));
+    if pen.inverse {
+        (fg, bg) = (
+            Some(bg.unwrap_or_else(|| "#1e1e1e".to_string())),
+            Some(fg.unwrap_or_else(|| "#e5e5e5".to_string())),
+        );
+    }
+    let mut style = Vec::new();
+    if let Some(fg) = fg {
+        style.push(format!("color:{}", fg));
+    }
+    if let Some(bg) = bg {
+        style.push(format!("background:{}", bg));
+    }
+    if pen.bold {
+        style.push("font-weight:bold".to_string());
+    }
+    if pen.dim {
+        style.push("opacity:0.6".to_string());
+    }
+    if pen.italic {
+        style.push("font-style:italic".to_string());
+    }
+    if pen.underline {
+        style.push("text-decoration:underline".to_string());
+    }
+    (!style.is_empty()).then(|| style.join(";"))
+}
+
+const ANSI_PALETTE: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 49, 49),
+    (13, 188, 121),
+    (229, 229, 16),
+    (36, 114, 200),
+    (188, 63, 188),
+    (17, 168, 205),
+    (229, 229, 229),
+    (102, 102, 102),
+    (241, 76, 76),
+    (35, 209, 139),
+    (245, 245, 67),
+    (59, 142, 234),
+    (214, 112, 214),
+    (41, 184, 219),
+    (255, 255, 255),
+];
+
+fn css_color(color: Color) -> String {
+    let (r, g, b) = match color {
+        Color::Rgb(r, g, b) => (r, g, b),
+        Color::Idx(i) if i < 16 => ANSI_PALETTE[i as usize],
+        Color::Idx(i) if i < 232 => {
+            // 6x6x6 color cube
+            let i = i - 16;
+            let level = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+            (level(i / 36), level((i / 6) % 6), level(i % 6))
+        }
+        Color::Idx(i) => {
+            let v = 8 + (i - 232) * 10;
+            (v, v, v)
+        }
+    };
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// Builds transcript entries from raw log bytes. Lines are handled like
+/// `ansi::LineDecoder` does (`\r` rewinds, erase-in-line truncates), but each
+/// character keeps its SGR attributes and instruction markers become entries.
+#[derive(Default)]
+struct TranscriptBuilder {
+    cells: Vec<(char, Pen)>,
+    col: usize,
+    pen: Pen,
+    entries: Vec<Entry>,
+    /// Lines ended so far
+    lines: usize,
+}
+
+impl TranscriptBuilder {
+    fn put(&mut self, c: char) {
+        if self.col < self.cells.len() {
+            self.cells[self.col] = (c, self.pen);
+        } else {
+            self.cells.push((c, self.pen));
+        }
+        self.col += 1;
+    }
+
+    fn end_line(&mut self) {
+        let mut runs: Vec<Run> = Vec::new();
+        for (c, pen) in self.cells.drain(..) {
+            match runs.last_mut() {
+                Some(run) if run.pen == pen => run.text.push(c),
+                _ => runs.push(Run {
+                    text: c.to_string(),
+                    pen,
+                }),
+            }
+        }
+        self.entries.push(Entry::Line(runs));
+        self.lines += 1;
+        self.col = 0;
+    }
+
+    fn finish(mut self) -> Vec<Entry> {
+        if !self.cells.is_empty() {
+            self.end_line();
+        }
+        self.entries
+    }
+}
+
+impl Perform for TranscriptBuilder {
+    fn print(&mut self, c: char) {
+        self.put(c);
+    }
+
+    fn execute(&mut self, byte: u8) {
+        match byte {
+            b'\n' => self.end_line(),
+            b'\r' => self.col = 0,
+            b'\t' => {
+                let next = (self.col / 8 + 1) * 8;
+                while self.col < next {
+                    self.put(' ');
+                }
+            }
+            0x08 => self.col = self.col.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    fn csi_dispatch(&mut self, params: &Params, _intermediates: &[u8], _ignore: bool, c: char) {
+        match c {
+            'm' => self.pen.apply_sgr(params),
+            'K' => match params.iter().next().and_then(|p| p.first().copied()).unwrap_or(0) {
+                0 => self.cells.truncate(self.col),
+                2 => {
+                    self.cells.clear();
+                    self.col = 0;
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn osc_dispatch(&mut self, params: &[&[u8]], _bell_terminated: bool) {
+        let [command, payload] = params else {
+            return;
+        };
+        if *command != persistence::INSTRUCTION_OSC.as_bytes() {
+            return;
+        }
+        let Ok(value) = serde_json::from_slice::<serde_json::Value>(payload) else {
+            return;
+        };
+        if let Some(text) = value["text"].as_str() {
+            let at = value["at"].as_str().and_then(|at| at.parse().ok());
+            self.entries.push(Entry::Instruction {
+                at,
+                text: text.to_string(),
+            });
+        }
+    }
+}
+
+impl Pen {
+    fn apply_sgr(&mut self, params: &Params) {
+        if params.is_empty() {
+            *self = Self::default();
+            return;
+        }
+        let mut iter = params.iter();
+        while let Some(param) = iter.next() {
+            match param[0] {
+                0 => *self = Self::default(),
+                1 => self.bold = true,
+                2 => self.dim = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                7 => self.inverse = true,
+                22 => (self.bold, self.dim) = (false, false),
+                23 => self.italic = false,
+                24 => self.underline = false,
+                27 => self.inverse = false,
+                n @ 30..=37 => self.fg = Some(Color::Idx((n - 30) as u8)),
+                38 => self.fg = extended_color(param, &mut iter),
+                39 => self.fg = None,
+                n @ 40..=47 => self.bg = Some(Color::Idx((n - 40) as u8)),
+                48 => self.bg = extended_color(param, &mut iter),
+                49 => self.bg = None,
+                n @ 90..=97 => self.fg = Some(Color::Idx((n - 90 + 8) as u8)),
+                n @ 100..=107 => self.bg = Some(Color::Idx((n - 100 + 8) as u8)),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// The color of a `38`/`48` parameter: `5;N` or `2;R;G;B`, as following
+/// parameters or as `:` subparameters of this one.
+fn extended_color<'a>(param: &[u16], rest: &mut impl Iterator<Item = &'a [u16]>) -> Option<Color> {
+    let values: Vec<u16> = if param.len() > 1 {
+        param[1..].to_vec()
+    } else {
+        let mode = rest.next()?[0];
+        let count = if mode == 5 { 1 } else { 3 };
+        std::iter::once(mode)
+            .chain(rest.take(count).map(|p| p[0]))
+            .collect()
+    };
+    match values.as_slice() {
+        [5, n, ..] => Some(Color::Idx(*n as u8)),
+        // `38:2:<colorspace>:R:G:B` has the color space id in front
+        [2, .., r, g, b] => Some(Color::Rgb(*r as u8, *g as u8, *b as u8)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transcript(bytes: &[u8]) -> Vec<Entry> {
+        let mut parser = Parser::new();
+        let mut builder = TranscriptBuilder::default();
+        parser.advance(&mut builder, bytes);
+        builder.finish()
+    }
+
+    #[test]
+    fn expands_home_in_typed_paths() {
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(expand_path(" ~/Desktop/a.md "), home.join("Desktop/a.md"));
+        assert_eq!(expand_path("~"), home);
+        // Only a leading ~ on its own is the home directory
+        assert_eq!(expand_path("~bob/a.md"), PathBuf::from("~bob/a.md"));
+        assert_eq!(expand_path("out/~/a.md"), PathBuf::from("out/~/a.md"));
+    }
+
+    fn session(tmp: &Path) -> Session {
+        let mut session = Session::new("api fix".to_string(), tmp.to_path_buf(), tmp);
+        session.exit_code = Some(0);
+        session
+    }
+
+    #[test]
+    fn keeps_styles_and_instruction_markers() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let log = tmp.path().join("s.log");
+        persistence::append_log(&log, b"before\r\n").unwrap();
+        persistence::write_instruction_marker(&log, "run; the tests").unwrap();
+        persistence::append_log(&log, b"\x1b[1;31mFAIL\x1b[0m ok\x1b[38;5;196mx\x1b[38:2:1:2:3my\r\n").unwrap();
+
+        let entries = read_transcript(&log).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert!(matches!(&entries[1], Entry::Instruction { at: Some(_), text } if text == "run; the tests"));
+        let Entry::Line(runs) = &entries[2] else {
+            panic!("expected a line");
+        };
+        let red = Pen {
+            fg: Some(Color::Idx(1)),
+            bold: true,
+            ..Pen::default()
+        };
+        assert_eq!(runs[0], Run { text: "FAIL".to_string(), pen: red });
+        assert_eq!(runs[1].text, " ok");
+        assert_eq!(runs[2].pen.fg, Some(Color::Idx(196)));
+        assert_eq!(runs[3].pen.fg, Some(Color::Rgb(1, 2, 3)));
+    }
+
+    #[test]
+    fn drops_the_log_header_and_overwritten_text() {
+        let mut entries = transcript(b"# Session: s\n# Directory: /tmp\n---\n50%\r100%\nend");
+        skip_log_header(&mut entries);
+        let lines: Vec<String> = entries
+            .iter()
+            .map(|e| match e {
+                Entry::Line(runs) => line_text(runs),
+                Entry::Instruction { text, .. } => text.clone(),
+                Entry::Bookmark(bookmark) => bookmark.note.clone(),
+            })
+            .collect();
+        assert_eq!(lines, vec!["100%", "end"]);
+    }
+
+    #[test]
+    fn renders_markdown_and_html() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let session = session(tmp.path());
+        let mut entries = transcript(b"boot\n\n");
+        entries.push(Entry::Instruction {
+            at: None,
+            text: "fix <it>".to_string(),
+        });
+        entries.extend(transcript(b"``` code\n\x1b[32mdone\x1b[0m\n"));
+
+        let md = markdown(&session, &entries);
+        assert!(md.starts_with("# api fix\n"));
+        assert!(md.contains("- **Exit code:** 0\n"));
+        assert!(md.contains("## Output\n\n```text\nboot\n```\n"));
+        assert!(md.contains("## Instruction 1\n\n> fix <it>\n\n````text\n``` code\ndone\n````\n"));
+
+        let html = html(&session, &entries);
+        assert!(html.contains("<blockquote>fix &lt;it&gt;</blockquote>"));
+        assert!(html.contains("<span style=\"color:#0dbc79\">done</span>\n"));
+        assert_eq!(Format::from_path(Path::new("t.HTML")), Format::Html);
+        assert_eq!(Format::from_path(Path::new("t.md")), Format::Markdown);
+    }
+
+    #[test]
+    fn puts_bookmarks_after_their_lines() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let session = session(tmp.path());
+        let log = tmp.path().join("s.log");
+        persistence::append_log(&log, b"# Session: s\n---\nfirst\nsecond\nthird\n").unwrap();
+        let mut marks = bookmarks::Bookmarks::load(&log);
+        let bookmark = |line: usize, text: &str, note: &str| Bookmark {
+            segment: 1,
+            line,
+            text: text.to_string(),
+            note: note.to_string(),
+            created_at: Utc::now(),
+        };
+        marks.add(bookmark(3, "second", "green <here>")).unwrap();
+        // The line changed since (the log was rotated away): left out
+        marks.add(bookmark(2, "gone", "stale")).unwrap();
+
+        let entries = read_transcript(&log).unwrap();
+        assert_eq!(entries.len(), 4);
+        assert!(matches!(&entries[2], Entry::Bookmark(b) if b.note == "green <here>"));
+
+        let md = markdown(&session, &entries);
+        assert!(md.contains("```text\nfirst\nsecond\n```\n\n> 🔖 **green <here>** ("));
+        assert!(md.contains(" UTC)\n\n```text\nthird\n```\n"));
+        let html = html(&session, &entries);
+        assert!(html.contains("second\n<mark class=\"bookmark\">🔖 green &lt;here&gt; ("));
+    }
+}
@@ -0,0 +1,131 @@
+use crate::needs_input::NeedsInputReason;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// One line of the journal: what happened to which session, and when.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub at: DateTime<Utc>,
+    pub session_id: Uuid,
+    #[serde(flatten)]
+    pub event: JournalEvent,
+}
+
+/// Session lifecycle events, tagged by `"event"` in the JSON.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum JournalEvent {
+    Created {
+        name: String,
+        root_path: PathBuf,
+    },
+    /// The agent was started (`conversation_id` when resuming one)
+    Spawned {
+        program: String,
+        args: Vec<String>,
+        pid: Option<u32>,
+        conversation_id: Option<String>,
+    },
+    Instruction {
+        text: String,
+    },
+    NeedsInput {
+        source: NeedsInputSource,
+    },
+    Exited {
+        success: bool,
+        code: i32,
+    },
+    Renamed {
+        from: String,
+        to: String,
+    },
+    Deleted,
+}
+
+/// What put a session into NeedsInput.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NeedsInputSource {
+    /// A prompt pattern in the output
+    Pattern,
+    /// No output for `needs_input_timeout_sec`
+    Timeout,
+    /// Toggled by the user
+    Manual,
+}
+
+impl From<&NeedsInputReason> for NeedsInputSource {
+    fn from(reason: &NeedsInputReason) -> Self {
+        match reason {
+            NeedsInputReason::Pattern { .. } | NeedsInputReason::Dialog { .. } => Self::Pattern,
+            NeedsInputReason::Timeout { .. } => Self::Timeout,
+            NeedsInputReason::Manual => Self::Manual,
+        }
+    }
+}
+
+/// Append-only JSON Lines record of session lifecycle events, for analytics
+/// outside deck. Write errors are ignored, like the session logs'.
+pub struct Journal {
+    path: PathBuf,
+}
+
+impl Journal {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+        }
+    }
+
+    pub fn record(&self, session_id: Uuid, event: JournalEvent) {
+        let entry = JournalEntry {
+            at: Utc::now(),
+            session_id,
+            event,
+        };
+        let _ = self.append(&entry);
+    }
+
+    fn append(&self, entry: &JournalEntry) -> std::io::Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        // One write per line so concurrent writers don't interleave within a line
+        file.write_all(&line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn appends_one_tagged_line_per_event() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("events.jsonl");
+        let journal = Journal::new(&path);
+        let id = Uuid::new_v4();
+        journal.record(
+            id,
+            JournalEvent::NeedsInput {
+                source: NeedsInputSource::Timeout,
+            },
+        );
+        journal.record(id, JournalEvent::Exited { success: false, code: 2 });
+
+        let data = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = data.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains(r#""event":"needs_input","source":"timeout""#));
+        let entry: JournalEntry = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(entry.session_id, id);
+        assert_eq!(entry.event, JournalEvent::Exited { success: false, code: 2 });
+    }
+}
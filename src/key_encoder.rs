@@ -0,0 +1,212 @@
+use ftui_core::event::{KeyCode, KeyEvent, Modifiers};
+
+/// Whether `key` is the reserved key that leaves passthrough mode (Ctrl+]).
+///
+/// Terminals send Ctrl+] as 0x1D, which crossterm reports as Ctrl+5.
+pub fn is_passthrough_exit(key: &KeyEvent) -> bool {
+    key.modifiers.contains(Modifiers::CTRL) && matches!(key.code, KeyCode::Char(']' | '5'))
+}
+
+/// Encode a key event as the bytes an xterm-compatible terminal would send.
+///
+/// `application_cursor` follows the session's DECCKM mode, which switches
+/// unmodified arrow keys from `CSI` to `SS3` sequences.
+/// Returns None for keys that have no terminal encoding.
+pub fn encode(key: &KeyEvent, application_cursor: bool) -> Option<Vec<u8>> {
+    let mods = key.modifiers;
+    let alt = mods.contains(Modifiers::ALT);
+    let ctrl = mods.contains(Modifiers::CTRL);
+
+    let mut bytes = match key.code {
+        KeyCode::Char(c) => {
+            let mut bytes = if ctrl {
+                vec![control_byte(c)?]
+            } else {
+                c.to_string().into_bytes()
+            };
+            if alt {
+                bytes.insert(0, 0x1b);
+            }
+            return Some(bytes);
+        }
+        KeyCode::Enter => vec![b'\r'],
+        KeyCode::Tab if mods.contains(Modifiers::SHIFT) => b"\x1b[Z".to_vec(),
+        KeyCode::Tab => vec![b'\t'],
+        KeyCode::BackTab => b"\x1b[Z".to_vec(),
+        KeyCode::Backspace if ctrl => vec![0x08],
+        KeyCode::Backspace => vec![0x7f],
+        KeyCode::Escape => vec![0x1b],
+        KeyCode::Null => vec![0x00],
+        KeyCode::Up => return Some(cursor_key(b'A', mods, application_cursor)),
+        KeyCode::Down => return Some(cursor_key(b'B', mods, application_cursor)),
+        KeyCode::Right => return Some(cursor_key(b'C', mods, application_cursor)),
+        KeyCode::Left => return Some(cursor_key(b'D', mods, application_cursor)),
+        KeyCode::Home => return Some(cursor_key(b'H', mods, application_cursor)),
+        KeyCode::End => return Some(cursor_key(b'F', mods, application_cursor)),
+        KeyCode::Insert => return Some(tilde_key(2, mods)),
+        KeyCode::Delete => return Some(tilde_key(3, mods)),
+        KeyCode::PageUp => return Some(tilde_key(5, mods)),
+        KeyCode::PageDown => return Some(tilde_key(6, mods)),
+        KeyCode::F(n) => return function_key(n, mods),
+        _ => return None,
+    };
+
+    // Alt prefixes the plain sequence with ESC (Enter, Tab, Backspace, Escape)
+    if alt {
+        bytes.insert(0, 0x1b);
+    }
+    Some(bytes)
+}
+
+/// xterm modifier parameter: 1 + Shift(1) + Alt(2) + Ctrl(4).
+fn modifier_param(mods: Modifiers) -> u8 {
+    let mut param = 1;
+    if mods.contains(Modifiers::SHIFT) {
+        param += 1;
+    }
+    if mods.contains(Modifiers::ALT) {
+        param += 2;
+    }
+    if mods.contains(Modifiers::CTRL) {
+        param += 4;
+    }
+    param
+}
+
+fn control_byte(c: char) -> Option<u8> {
+    match c {
+        'a'..='z' => Some(c as u8 - b'a' + 1),
+        'A'..='Z' => Some(c as u8 - b'A' + 1),
+        '@' | ' ' | '2' => Some(0x00),
+        '[' | '3' => Some(0x1b),
+        '\\' | '4' => Some(0x1c),
+        ']' | '5' => Some(0x1d),
+        '^' | '6' => Some(0x1e),
+        '_' | '7' | '/' => Some(0x1f),
+        '?' | '8' => Some(0x7f),
+        _ => None,
+    }
+}
+
+fn cursor_key(final_byte: u8, mods: Modifiers, application_cursor: bool) -> Vec<u8> {
+    let param = modifier_param(mods);
+    if param > 1 {
+        format!("\x1b[1;{}{}", param, final_byte as char).into_bytes()
+    } else if application_cursor {
+        vec![0x1b, b'O', final_byte]
+    } else {
+        vec![0x1b, b'[', final_byte]
+    }
+}
+
+fn tilde_key(code: u8, mods: Modifiers) -> Vec<u8> {
+    let param = modifier_param(mods);
+    if param > 1 {
+        format!("\x1b[{};{}~", code, param).into_bytes()
+    } else {
+        format!("\x1b[{}~", code).into_bytes()
+    }
+}
+
+fn function_key(n: u8, mods: Modifiers) -> Option<Vec<u8>> {
+    let param = modifier_param(mods);
+    if (1..=4).contains(&n) {
+        let final_byte = (b'P' + n - 1) as char;
+        return Some(if param > 1 {
+            format!("\x1b[1;{}{}", param, final_byte).into_bytes()
+        } else {
+            format!("\x1bO{}", final_byte).into_bytes()
+        });
+    }
+    let code = match n {
+        5 => 15,
+        6 => 17,
+        7 => 18,
+        8 => 19,
+        9 => 20,
+        10 => 21,
+        11 => 23,
+        12 => 24,
+        _ => return None,
+    };
+    Some(tilde_key(code, mods))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code)
+    }
+
+    fn enc(key: KeyEvent) -> Vec<u8> {
+        encode(&key, false).unwrap()
+    }
+
+    #[test]
+    fn plain_characters() {
+        assert_eq!(enc(key(KeyCode::Char('y'))), b"y");
+        assert_eq!(enc(key(KeyCode::Char('1'))), b"1");
+        assert_eq!(enc(key(KeyCode::Char('あ'))), "あ".as_bytes());
+    }
+
+    #[test]
+    fn control_characters() {
+        let ctrl = |c| key(KeyCode::Char(c)).with_modifiers(Modifiers::CTRL);
+        assert_eq!(enc(ctrl('c')), [0x03]);
+        assert_eq!(enc(ctrl('a')), [0x01]);
+        assert_eq!(enc(ctrl('[')), [0x1b]);
+    }
+
+    #[test]
+    fn alt_prefixes_escape() {
+        let k = key(KeyCode::Char('b')).with_modifiers(Modifiers::ALT);
+        assert_eq!(enc(k), b"\x1bb");
+        let k = key(KeyCode::Enter).with_modifiers(Modifiers::ALT);
+        assert_eq!(enc(k), b"\x1b\r");
+    }
+
+    #[test]
+    fn editing_keys() {
+        assert_eq!(enc(key(KeyCode::Enter)), b"\r");
+        assert_eq!(enc(key(KeyCode::Tab)), b"\t");
+        assert_eq!(enc(key(KeyCode::BackTab)), b"\x1b[Z");
+        assert_eq!(
+            enc(key(KeyCode::Tab).with_modifiers(Modifiers::SHIFT)),
+            b"\x1b[Z"
+        );
+        assert_eq!(enc(key(KeyCode::Backspace)), [0x7f]);
+        assert_eq!(enc(key(KeyCode::Escape)), [0x1b]);
+    }
+
+    #[test]
+    fn arrow_keys_follow_cursor_mode() {
+        assert_eq!(enc(key(KeyCode::Up)), b"\x1b[A");
+        assert_eq!(encode(&key(KeyCode::Up), true).unwrap(), b"\x1bOA");
+        let k = key(KeyCode::Right).with_modifiers(Modifiers::CTRL);
+        assert_eq!(encode(&k, true).unwrap(), b"\x1b[1;5C");
+    }
+
+    #[test]
+    fn navigation_and_function_keys() {
+        assert_eq!(enc(key(KeyCode::PageUp)), b"\x1b[5~");
+        assert_eq!(enc(key(KeyCode::Delete)), b"\x1b[3~");
+        assert_eq!(enc(key(KeyCode::F(1))), b"\x1bOP");
+        assert_eq!(enc(key(KeyCode::F(5))), b"\x1b[15~");
+        assert_eq!(
+            enc(key(KeyCode::F(12)).with_modifiers(Modifiers::SHIFT)),
+            b"\x1b[24;2~"
+        );
+        assert!(encode(&key(KeyCode::F(20)), false).is_none());
+    }
+
+    #[test]
+    fn exit_key() {
+        let k = key(KeyCode::Char(']')).with_modifiers(Modifiers::CTRL);
+        assert!(is_passthrough_exit(&k));
+        let k = key(KeyCode::Char('5')).with_modifiers(Modifiers::CTRL);
+        assert!(is_passthrough_exit(&k));
+        assert!(!is_passthrough_exit(&key(KeyCode::Char(']'))));
+    }
+}
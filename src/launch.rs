@@ -0,0 +1,234 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_PROGRAM: &str = "claude";
+
+/// How a session's agent process is started: program, arguments and environment.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LaunchSpec {
+    pub program: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: Vec<(String, String)>,
+    /// `KEY=VALUE` file loaded before `env`; relative paths resolve against the session root.
+    #[serde(default)]
+    pub env_file: Option<PathBuf>,
+}
+
+impl Default for LaunchSpec {
+    fn default() -> Self {
+        Self {
+            program: DEFAULT_PROGRAM.to_string(),
+            args: Vec::new(),
+            env: Vec::new(),
+            env_file: None,
+        }
+    }
+}
+
+impl LaunchSpec {
+    /// Build a spec from a command line (`claude --model opus`) and an env line
+    /// (`KEY=VALUE ... @path/to/.env`). Empty command falls back to `claude`.
+    pub fn parse(command: &str, env: &str) -> Result<Self, &'static str> {
+        let mut words = split_args(command)?;
+        let program = if words.is_empty() {
+            DEFAULT_PROGRAM.to_string()
+        } else {
+            words.remove(0)
+        };
+
+        let mut spec = Self {
+            program,
+            args: words,
+            ..Self::default()
+        };
+        for word in split_args(env)? {
+            if let Some(path) = word.strip_prefix('@') {
+                spec.env_file = Some(PathBuf::from(path));
+            } else {
+                let (key, value) = word.split_once('=').ok_or("環境変数は KEY=VALUE 形式で指定してください")?;
+                if key.is_empty() {
+                    return Err("環境変数名が空です");
+                }
+                spec.env.push((key.to_string(), value.to_string()));
+            }
+        }
+        Ok(spec)
+    }
+
+    /// Program and arguments as a single shell-style line.
+    pub fn command_line(&self) -> String {
+        std::iter::once(&self.program)
+            .chain(&self.args)
+            .map(|w| quote(w))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Environment passed to the child: env file entries first, explicit vars override.
+    pub fn resolved_env(&self, root_path: &Path) -> std::io::Result<Vec<(String, String)>> {
+        let mut vars = Vec::new();
+        if let Some(file) = &self.env_file {
+            let content = std::fs::read_to_string(root_path.join(file))?;
+            vars.extend(parse_env_file(&content));
+        }
+        vars.extend(self.env.iter().cloned());
+        Ok(vars)
+    }
+}
+
+/// Split a command line into words, honouring single/double quotes and backslash escapes.
+pub fn split_args(line: &str) -> Result<Vec<String>, &'static str> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(ch) => current.push(ch),
+                        None => return Err("引用符が閉じられていません"),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(ch) => current.push(ch),
+                            None => return Err("引用符が閉じられていません"),
+                        },
+                        Some(ch) => current.push(ch),
+                        None => return Err("引用符が閉じられていません"),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                if let Some(ch) = chars.next() {
+                    current.push(ch);
+                }
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                current.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(current);
+    }
+    Ok(words)
+}
+
+/// Parse `.env` style content: `KEY=VALUE` lines, `#` comments, optional `export ` and quotes.
+pub fn parse_env_file(content: &str) -> Vec<(String, String)> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .filter_map(|l| {
+            let l = l.strip_prefix("export ").unwrap_or(l);
+            let (key, value) = l.split_once('=')?;
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+                .unwrap_or(value);
+            Some((key.trim().to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+fn quote(word: &str) -> String {
+    if !word.is_empty() && !word.contains(|c: char| c.is_whitespace() || "'\"\\".contains(c)) {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_is_claude() {
+        let spec = LaunchSpec::parse("", "").unwrap();
+        assert_eq!(spec, LaunchSpec::default());
+        assert_eq!(spec.command_line(), "claude");
+    }
+
+    #[test]
+    fn parses_program_and_args() {
+        let spec = LaunchSpec::parse("claude --model opus --add-dir '../other repo'", "").unwrap();
+        assert_eq!(spec.program, "claude");
+        assert_eq!(spec.args, ["--model", "opus", "--add-dir", "../other repo"]);
+        assert_eq!(
+            spec.command_line(),
+            "claude --model opus --add-dir '../other repo'"
+        );
+    }
+
+    #[test]
+    fn split_handles_quotes_and_escapes() {
+        assert_eq!(
+            split_args(r#"a "b c" d\ e 'f"g'"#).unwrap(),
+            ["a", "b c", "d e", "f\"g"]
+        );
+        assert!(split_args("'unterminated").is_err());
+    }
+
+    #[test]
+    fn parses_env_line() {
+        let spec = LaunchSpec::parse("bash", "FOO=1 BAR='two words' @.env.local").unwrap();
+        assert_eq!(
+            spec.env,
+            [
+                ("FOO".to_string(), "1".to_string()),
+                ("BAR".to_string(), "two words".to_string())
+            ]
+        );
+        assert_eq!(spec.env_file, Some(PathBuf::from(".env.local")));
+        assert!(LaunchSpec::parse("bash", "NOVALUE").is_err());
+    }
+
+    #[test]
+    fn env_file_format() {
+        let vars = parse_env_file("# comment\nexport A=1\nB=\"quoted\"\n\nC='single'\n");
+        assert_eq!(
+            vars,
+            [
+                ("A".to_string(), "1".to_string()),
+                ("B".to_string(), "quoted".to_string()),
+                ("C".to_string(), "single".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn explicit_env_overrides_file() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        std::fs::write(tmp.path().join(".env"), "A=file\nB=file\n").unwrap();
+        let spec = LaunchSpec::parse("bash", "B=explicit @.env").unwrap();
+        let vars = spec.resolved_env(tmp.path()).unwrap();
+        // Later entries win when applied in order
+        assert_eq!(vars.last().unwrap(), &("B".to_string(), "explicit".to_string()));
+        assert_eq!(vars.len(), 3);
+    }
+}
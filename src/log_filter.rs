@@ -0,0 +1,169 @@
+use crate::session::{Session, SessionStatus};
+use regex::{Regex, RegexBuilder};
+use std::collections::BTreeMap;
+
+/// A parsed filter expression for the unified log.
+///
+/// Terms are separated by spaces:
+/// - `session:NAME` (or `s:NAME`): sessions whose name contains NAME
+/// - `#TAG`: sessions with that tag (a `#word` in the session name)
+/// - `status:STATUS`: sessions in a status (`running`, `needsinput`, ... or a prefix)
+/// - `-PATTERN`: drop lines matching the regex
+/// - `@NAME`: the terms of the named filter NAME from the config
+/// - anything else: keep only lines matching the regex
+///
+/// Session and tag terms match any of them, as do status terms; line patterns
+/// must all match. Regexes ignore case.
+pub struct LogFilter {
+    /// The expression as typed, for the panel title
+    pub expr: String,
+    sessions: Vec<String>,
+    tags: Vec<String>,
+    statuses: Vec<String>,
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+}
+
+impl LogFilter {
+    pub fn parse(expr: &str, named: &BTreeMap<String, String>) -> Result<Self, String> {
+        let mut filter = Self {
+            expr: expr.trim().to_string(),
+            sessions: Vec::new(),
+            tags: Vec::new(),
+            statuses: Vec::new(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+        };
+        for term in expr.split_whitespace() {
+            match term.strip_prefix('@') {
+                Some(name) => {
+                    let terms = named
+                        .get(name)
+                        .ok_or_else(|| format!("名前付きフィルタ '{}' がありません", name))?;
+                    for term in terms.split_whitespace() {
+                        if term.starts_with('@') {
+                            return Err(format!("'{}' の中で他のフィルタは参照できません", name));
+                        }
+                        filter.add_term(term)?;
+                    }
+                }
+                None => filter.add_term(term)?,
+            }
+        }
+        Ok(filter)
+    }
+
+    fn add_term(&mut self, term: &str) -> Result<(), String> {
+        if let Some(name) = term
+            .strip_prefix("session:")
+            .or_else(|| term.strip_prefix("s:"))
+        {
+            self.sessions.push(name.to_lowercase());
+        } else if let Some(status) = term.strip_prefix("status:") {
+            self.statuses.push(status.to_lowercase());
+        } else if let Some(tag) = term.strip_prefix('#').filter(|t| !t.is_empty()) {
+            self.tags.push(tag.to_lowercase());
+        } else if let Some(pattern) = term.strip_prefix('-').filter(|p| !p.is_empty()) {
+            self.exclude.push(compile(pattern)?);
+        } else {
+            self.include.push(compile(term)?);
+        }
+        Ok(())
+    }
+
+    /// Whether the session's lines belong in the filtered log.
+    pub fn matches_session(&self, session: &Session) -> bool {
+        let name = session.name.to_lowercase();
+        let tags = session_tags(&session.name);
+        let by_session = (self.sessions.is_empty() && self.tags.is_empty())
+            || self.sessions.iter().any(|s| name.contains(s.as_str()))
+            || self.tags.iter().any(|t| tags.contains(t));
+        let status = status_name(&session.status);
+        let by_status =
+            self.statuses.is_empty() || self.statuses.iter().any(|s| status.starts_with(s.as_str()));
+        by_session && by_status
+    }
+
+    pub fn matches_line(&self, line: &str) -> bool {
+        self.include.iter().all(|re| re.is_match(line))
+            && !self.exclude.iter().any(|re| re.is_match(line))
+    }
+}
+
+fn compile(pattern: &str) -> Result<Regex, String> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| format!("正規表現エラー: {}", e.to_string().lines().last().unwrap_or_default()))
+}
+
+/// Tags of a session: the `#words` in its name, lowercased.
+fn session_tags(name: &str) -> Vec<String> {
+    name.split_whitespace()
+        .filter_map(|word| word.strip_prefix('#'))
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn status_name(status: &SessionStatus) -> String {
+    format!("{:?}", status).to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::{Path, PathBuf};
+
+    fn session(name: &str, status: SessionStatus) -> Session {
+        let mut session = Session::new(name.to_string(), PathBuf::from("/tmp"), Path::new("/tmp"));
+        session.status = status;
+        session
+    }
+
+    fn parse(expr: &str) -> LogFilter {
+        LogFilter::parse(expr, &BTreeMap::new()).unwrap()
+    }
+
+    #[test]
+    fn filters_sessions_by_name_tag_and_status() {
+        let api = session("api #backend", SessionStatus::Running);
+        let web = session("web #frontend", SessionStatus::NeedsInput);
+
+        let f = parse("s:API");
+        assert!(f.matches_session(&api) && !f.matches_session(&web));
+        let f = parse("#frontend session:api");
+        assert!(f.matches_session(&api) && f.matches_session(&web));
+        let f = parse("status:needs");
+        assert!(!f.matches_session(&api) && f.matches_session(&web));
+        let f = parse("#backend status:done");
+        assert!(!f.matches_session(&api));
+        assert!(parse("").matches_session(&web));
+    }
+
+    #[test]
+    fn filters_lines_by_include_and_exclude_patterns() {
+        let f = parse("error -retry");
+        assert!(f.matches_line("ERROR: disk full"));
+        assert!(!f.matches_line("error, will retry"));
+        assert!(!f.matches_line("ok"));
+
+        let f = parse(r"^\d+ passed");
+        assert!(f.matches_line("12 passed"));
+        assert!(LogFilter::parse("(", &BTreeMap::new()).is_err());
+    }
+
+    #[test]
+    fn expands_named_filters() {
+        let mut named = BTreeMap::new();
+        named.insert("errors".to_string(), "error -warning".to_string());
+        named.insert("loop".to_string(), "@errors".to_string());
+
+        let f = LogFilter::parse("@errors s:api", &named).unwrap();
+        assert_eq!(f.expr, "@errors s:api");
+        assert!(f.matches_line("error here"));
+        assert!(!f.matches_line("error warning"));
+        assert!(LogFilter::parse("@missing", &named).is_err());
+        assert!(LogFilter::parse("@loop", &named).is_err());
+    }
+}
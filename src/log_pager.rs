@@ -0,0 +1,382 @@
+use crate::ansi::LineDecoder;
+use crate::log_retention;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::os::unix::fs::MetadataExt;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// Lines per page: the unit the log file is indexed, read and cached in.
+const PAGE_LINES: usize = 256;
+/// Decoded pages kept in memory (a screen's worth plus some scrolling around it).
+const CACHE_PAGES: usize = 8;
+const READ_CHUNK: usize = 64 * 1024;
+
+/// Reads a session's log file a page at a time. Only the byte offset of each
+/// page is kept, so memory stays small however long the log grows, and any
+/// line can be reached with one seek.
+pub struct LogPager {
+    path: PathBuf,
+    /// Inode of the file indexed, which changes when the log is rotated
+    inode: Option<u64>,
+    /// Byte offset where each page starts
+    page_offsets: Vec<u64>,
+    /// Bytes of the file indexed so far
+    indexed_len: u64,
+    /// `\n`-terminated lines in the indexed bytes
+    complete_lines: usize,
+    /// Decoded pages, most recently used last
+    cache: VecDeque<(usize, Vec<String>)>,
+}
+
+impl LogPager {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            inode: None,
+            page_offsets: vec![0],
+            indexed_len: 0,
+            complete_lines: 0,
+            cache: VecDeque::new(),
+        }
+    }
+
+    /// Index what was appended to the file since the last call. A file that
+    /// got shorter or was replaced (rotated) was rewritten and is indexed from
+    /// scratch; returns whether that happened.
+    pub fn refresh(&mut self) -> std::io::Result<bool> {
+        let mut file = File::open(&self.path)?;
+        let metadata = file.metadata()?;
+        let len = metadata.len();
+        let rewritten =
+            len < self.indexed_len || self.inode.is_some_and(|inode| inode != metadata.ino());
+        if rewritten {
+            *self = Self::new(&self.path.clone());
+        }
+        self.inode = Some(metadata.ino());
+        if len == self.indexed_len {
+            return Ok(rewritten);
+        }
+
+        // Pages that were still growing have to be decoded again
+        let complete_pages = self.page_offsets.len() - 1;
+        self.cache.retain(|(page, _)| *page < complete_pages);
+
+        file.seek(SeekFrom::Start(self.indexed_len))?;
+        let mut buf = vec![0; READ_CHUNK];
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            for (i, _) in buf[..n].iter().enumerate().filter(|(_, b)| **b == b'\n') {
+                self.complete_lines += 1;
+                if self.complete_lines.is_multiple_of(PAGE_LINES) {
+                    self.page_offsets.push(self.indexed_len + i as u64 + 1);
+                }
+            }
+            self.indexed_len += n as u64;
+        }
+        Ok(rewritten)
+    }
+
+    /// Lines in the file: the complete ones plus the unterminated last line
+    /// (possibly empty), counted the way `LogStore` does.
+    pub fn line_count(&self) -> usize {
+        self.complete_lines + 1
+    }
+
+    /// Make sure the pages holding `lines` are in the cache.
+    pub fn load(&mut self, lines: Range<usize>) -> std::io::Result<()> {
+        for page in Self::pages(&lines) {
+            if page >= self.page_offsets.len() {
+                break;
+            }
+            match self.cache.iter().position(|(p, _)| *p == page) {
+                Some(i) => {
+                    let entry = self.cache.remove(i).expect("index from position");
+                    self.cache.push_back(entry);
+                }
+                None => {
+                    let decoded = self.read_page(page)?;
+                    self.cache.push_back((page, decoded));
+                    if self.cache.len() > CACHE_PAGES {
+                        self.cache.pop_front();
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// A line from the cache; None if its page isn't loaded.
+    pub fn line(&self, n: usize) -> Option<&str> {
+        let page = n / PAGE_LINES;
+        self.cache
+            .iter()
+            .find(|(p, _)| *p == page)
+            .and_then(|(_, lines)| lines.get(n % PAGE_LINES))
+            .map(|line| line.as_str())
+    }
+
+    /// Read `lines` straight from the file, one page at a time, without
+    /// touching the cache (for going through the whole log).
+    pub fn read(
+        &self,
+        lines: Range<usize>,
+        mut each: impl FnMut(usize, Vec<String>),
+    ) -> std::io::Result<()> {
+        for page in Self::pages(&lines) {
+            if page >= self.page_offsets.len() {
+                break;
+            }
+            let first = page * PAGE_LINES;
+            let page_lines = self.read_page(page)?;
+            let start = lines.start.saturating_sub(first);
+            let end = (lines.end - first).min(page_lines.len());
+            if start < end {
+                each(first + start, page_lines[start..end].to_vec());
+            }
+        }
+        Ok(())
+    }
+
+    fn pages(lines: &Range<usize>) -> Range<usize> {
+        if lines.is_empty() {
+            return 0..0;
+        }
+        lines.start / PAGE_LINES..(lines.end - 1) / PAGE_LINES + 1
+    }
+
+    fn read_page(&self, page: usize) -> std::io::Result<Vec<String>> {
+        let start = self.page_offsets[page];
+        let last = page + 1 == self.page_offsets.len();
+        let end = match self.page_offsets.get(page + 1) {
+            Some(next) => *next,
+            None => self.indexed_len,
+        };
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(start))?;
+        let mut bytes = Vec::new();
+        file.take(end - start).read_to_end(&mut bytes)?;
+
+        let mut decoder = LineDecoder::new();
+        let mut lines = decoder.feed(&bytes);
+        if last {
+            lines.push(decoder.partial());
+        } else {
+            // A newline swallowed by an escape sequence must not shift later pages
+            lines.resize(PAGE_LINES, String::new());
+        }
+        Ok(lines)
+    }
+}
+
+/// The lines a session dropped from memory, paged in from its log file.
+///
+/// Absolute line numbers (as in `LogStore`) are mapped to file lines by an
+/// offset fixed when the history is first needed: the file also holds the log
+/// header and earlier runs, and ends with the lines still in memory.
+/// Once the log is rotated, history starts with the current file: older lines
+/// are only in the (gzipped) segments, which the transcript export reads.
+pub struct LogHistory {
+    pager: LogPager,
+    /// File line number of absolute line 0
+    offset: Option<i64>,
+    /// Older output was moved to rotated segments
+    rotated: bool,
+}
+
+impl LogHistory {
+    pub fn new(log_path: &Path) -> Self {
+        Self {
+            pager: LogPager::new(log_path),
+            offset: None,
+            rotated: false,
+        }
+    }
+
+    /// Index new output and align the file with the in-memory lines
+    /// `first_line..first_line + memory_len`.
+    pub fn sync(&mut self, first_line: usize, memory_len: usize) -> std::io::Result<()> {
+        if self.pager.refresh()? || self.offset.is_none() {
+            let before_memory = self.pager.line_count() as i64 - memory_len as i64;
+            self.offset = Some(before_memory - first_line as i64);
+            self.rotated = !log_retention::rotated_segments(&self.pager.path).is_empty();
+        }
+        Ok(())
+    }
+
+    /// Whether lines before `range` were rotated out of the log file (and
+    /// can't be scrolled to or searched here).
+    pub fn rotated(&self) -> bool {
+        self.rotated
+    }
+
+    /// Absolute line numbers available from the file, up to the first line in memory.
+    pub fn range(&self, first_line: usize) -> Range<usize> {
+        let start = self.offset.map_or(first_line, |offset| (-offset).max(0) as usize);
+        start.min(first_line)..first_line
+    }
+
+    /// Cache the pages holding absolute `lines`.
+    pub fn load(&mut self, lines: Range<usize>) -> std::io::Result<()> {
+        let Some(file_lines) = self.to_file(lines) else {
+            return Ok(());
+        };
+        self.pager.load(file_lines)
+    }
+
+    /// An absolute line from the cache; None if it isn't loaded.
+    pub fn line(&self, n: usize) -> Option<&str> {
+        let file_line = self.to_file(n..n + 1)?.start;
+        self.pager.line(file_line)
+    }
+
+    /// File line number of absolute line `n`.
+    pub fn file_line(&self, n: usize) -> Option<usize> {
+        Some(self.to_file(n..n + 1)?.start)
+    }
+
+    /// Absolute line number of file line `n` (None if it is before line 0).
+    pub fn absolute_line(&self, file_line: usize) -> Option<usize> {
+        usize::try_from(file_line as i64 - self.offset?).ok()
+    }
+
+    /// Read absolute `lines` page by page, without caching them.
+    pub fn read(
+        &self,
+        lines: Range<usize>,
+        mut each: impl FnMut(usize, Vec<String>),
+    ) -> std::io::Result<()> {
+        let (Some(offset), Some(file_lines)) = (self.offset, self.to_file(lines)) else {
+            return Ok(());
+        };
+        self.pager
+            .read(file_lines, |first, page| each((first as i64 - offset) as usize, page))
+    }
+
+    fn to_file(&self, lines: Range<usize>) -> Option<Range<usize>> {
+        let offset = self.offset?;
+        let start = usize::try_from(lines.start as i64 + offset).ok()?;
+        let end = usize::try_from(lines.end as i64 + offset).ok()?;
+        Some(start..end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_lines(path: &Path, range: Range<usize>) {
+        let text: String = range.map(|i| format!("\x1b[32mline {}\x1b[0m\r\n", i)).collect();
+        crate::persistence::append_log(path, text.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn pages_lines_in_from_the_file() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("s.log");
+        write_lines(&path, 0..600);
+
+        let mut pager = LogPager::new(&path);
+        pager.refresh().unwrap();
+        assert_eq!(pager.line_count(), 601);
+        assert_eq!(pager.page_offsets.len(), 3);
+
+        pager.load(250..260).unwrap();
+        assert_eq!(pager.line(250), Some("line 250"));
+        assert_eq!(pager.line(259), Some("line 259"));
+        assert_eq!(pager.line(10), Some("line 10"));
+        assert_eq!(pager.line(520), None);
+
+        let mut read = Vec::new();
+        pager.read(590..601, |_, lines| read.extend(lines)).unwrap();
+        assert_eq!(read.len(), 11);
+        assert_eq!(read[0], "line 590");
+        assert_eq!(read[10], "");
+    }
+
+    #[test]
+    fn cache_stays_bounded() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("s.log");
+        write_lines(&path, 0..PAGE_LINES * (CACHE_PAGES + 4));
+
+        let mut pager = LogPager::new(&path);
+        pager.refresh().unwrap();
+        pager.load(0..pager.line_count()).unwrap();
+        assert_eq!(pager.cache.len(), CACHE_PAGES);
+        // The most recently loaded pages stay
+        let last = pager.line_count() - 2;
+        assert_eq!(pager.line(last), Some(format!("line {}", last).as_str()));
+        assert_eq!(pager.line(0), None);
+    }
+
+    #[test]
+    fn refresh_indexes_appends_and_restarts_on_rewrite() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("s.log");
+        write_lines(&path, 0..10);
+        let mut pager = LogPager::new(&path);
+        pager.refresh().unwrap();
+        pager.load(0..11).unwrap();
+        assert_eq!(pager.line(10), Some(""));
+
+        write_lines(&path, 10..300);
+        assert!(!pager.refresh().unwrap());
+        assert_eq!(pager.line_count(), 301);
+        pager.load(0..11).unwrap();
+        assert_eq!(pager.line(10), Some("line 10"));
+
+        std::fs::write(&path, "fresh\n").unwrap();
+        assert!(pager.refresh().unwrap());
+        assert_eq!(pager.line_count(), 2);
+    }
+
+    #[test]
+    fn history_maps_absolute_lines_to_the_file() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("s.log");
+        // A 2-line header, then 100 output lines of which 40.. are still in memory
+        std::fs::write(&path, "# Session: s\n---\n").unwrap();
+        write_lines(&path, 0..100);
+
+        let mut history = LogHistory::new(&path);
+        history.sync(40, 61).unwrap();
+        assert_eq!(history.range(40), 0..40);
+        history.load(30..40).unwrap();
+        assert_eq!(history.line(30), Some("line 30"));
+
+        let mut first = None;
+        history.read(0..40, |n, lines| {
+            first.get_or_insert((n, lines[0].clone()));
+        }).unwrap();
+        assert_eq!(first, Some((0, "line 0".to_string())));
+
+        assert_eq!(history.file_line(30), Some(32));
+        assert_eq!(history.absolute_line(32), Some(30));
+        assert_eq!(history.absolute_line(1), None);
+        assert!(!history.rotated());
+    }
+
+    #[test]
+    fn history_starts_again_after_rotation() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("s.log");
+        write_lines(&path, 0..100);
+        let mut history = LogHistory::new(&path);
+        history.sync(50, 51).unwrap();
+        assert_eq!(history.range(50), 0..50);
+
+        // Lines 0..100 go to a segment; 100..200 are written to a fresh file
+        log_retention::rotate_if_needed(&path, 1, 5).unwrap();
+        write_lines(&path, 100..200);
+        history.sync(150, 51).unwrap();
+        assert!(history.rotated());
+        assert_eq!(history.range(150), 100..150);
+        history.load(100..101).unwrap();
+        assert_eq!(history.line(100), Some("line 100"));
+    }
+}
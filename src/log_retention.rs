@@ -0,0 +1,368 @@
+use crate::bookmarks;
+use crate::config::{AppConfig, LogOnDelete};
+use crate::persistence;
+use crate::recording;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use uuid::Uuid;
+
+/// Where deleted sessions' logs go with `log_on_delete: archive`.
+pub fn archive_dir(logs_root: &Path) -> PathBuf {
+    logs_root.join("archive")
+}
+
+/// Rotated segments of a session log (`<id>.log.<n>`, `.gz` once compressed),
+/// oldest first.
+pub fn rotated_segments(log_path: &Path) -> Vec<PathBuf> {
+    numbered_segments(log_path)
+        .into_iter()
+        .map(|(_, path)| path)
+        .collect()
+}
+
+/// Rotated segments with their numbers, oldest first.
+pub fn numbered_segments(log_path: &Path) -> Vec<(u64, PathBuf)> {
+    let (Some(dir), Some(name)) = (
+        log_path.parent(),
+        log_path.file_name().and_then(|n| n.to_str()),
+    ) else {
+        return Vec::new();
+    };
+    let prefix = format!("{}.", name);
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut segments: Vec<(u64, PathBuf)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let file_name = entry.file_name();
+            let rest = file_name.to_str()?.strip_prefix(&prefix)?;
+            let n = rest.strip_suffix(".gz").unwrap_or(rest).parse().ok()?;
+            Some((n, entry.path()))
+        })
+        .collect();
+    segments.sort();
+    segments
+}
+
+/// Number the log file gets when it is next rotated, which tells its output
+/// apart from that of earlier segments.
+pub fn current_segment(log_path: &Path) -> u64 {
+    numbered_segments(log_path).last().map_or(1, |(n, _)| n + 1)
+}
+
+/// Once the log has grown past `max_bytes` (0: never), move it aside as the
+/// next segment and remove segments beyond the newest `keep`. Output goes to a
+/// fresh file from then on. Returns the new segment, still to be compressed.
+pub fn rotate_if_needed(log_path: &Path, max_bytes: u64, keep: usize) -> io::Result<Option<PathBuf>> {
+    if max_bytes == 0 || fs::metadata(log_path)?.len() <= max_bytes {
+        return Ok(None);
+    }
+    let segments = numbered_segments(log_path);
+    let segment = PathBuf::from(format!("{}.{}", log_path.display(), current_segment(log_path)));
+    fs::rename(log_path, &segment)?;
+
+    let excess = (segments.len() + 1).saturating_sub(keep);
+    for (_, old) in segments.iter().take(excess) {
+        let _ = fs::remove_file(old);
+    }
+    if keep == 0 {
+        fs::remove_file(&segment)?;
+        return Ok(None);
+    }
+    Ok(Some(segment))
+}
+
+/// Remove the rotated segments of a log that is being started over.
+pub fn remove_segments(log_path: &Path) -> io::Result<()> {
+    for segment in rotated_segments(log_path) {
+        fs::remove_file(segment)?;
+    }
+    Ok(())
+}
+
+/// Replace `path` with `<path>.gz`.
+pub fn compress(path: &Path) -> io::Result<PathBuf> {
+    let dest = PathBuf::from(format!("{}.gz", path.display()));
+    compress_to(path, &dest)?;
+    Ok(dest)
+}
+
+fn compress_to(src: &Path, dest: &Path) -> io::Result<()> {
+    let mut input = File::open(src)?;
+    let mut encoder = GzEncoder::new(File::create(dest)?, Compression::default());
+    io::copy(&mut input, &mut encoder)?;
+    encoder.finish()?;
+    fs::remove_file(src)
+}
+
+/// Delete or archive a deleted session's log, rotated segments, recording and
+/// bookmarks.
+pub fn remove_session_logs(log_path: &Path, logs_root: &Path, action: LogOnDelete) -> io::Result<()> {
+    let mut files = rotated_segments(log_path);
+    files.push(log_path.to_path_buf());
+    files.push(recording::cast_path(log_path));
+    files.push(bookmarks::bookmarks_path(log_path));
+    files.retain(|path| path.exists());
+
+    match action {
+        LogOnDelete::Keep => {}
+        LogOnDelete::Delete => {
+            for path in files {
+                fs::remove_file(path)?;
+            }
+        }
+        LogOnDelete::Archive => {
+            let archive = archive_dir(logs_root);
+            fs::create_dir_all(&archive)?;
+            for path in files {
+                let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                    continue;
+                };
+                if name.ends_with(".gz") {
+                    fs::rename(&path, archive.join(name))?;
+                } else {
+                    compress_to(&path, &archive.join(format!("{}.gz", name)))?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Session a file in the logs directory belongs to (the id its name starts with).
+fn file_session_id(path: &Path) -> Option<Uuid> {
+    let name = path.file_name()?.to_str()?;
+    let id = name.split('.').next()?;
+    Uuid::parse_str(id).ok()
+}
+
+/// Files in the logs directory whose session no longer exists.
+pub fn orphaned_files(logs_root: &Path, live: &HashSet<Uuid>) -> io::Result<Vec<PathBuf>> {
+    let mut orphans: Vec<PathBuf> = fs::read_dir(logs_root)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .filter(|path| file_session_id(path).is_some_and(|id| !live.contains(&id)))
+        .collect();
+    orphans.sort();
+    Ok(orphans)
+}
+
+/// Remove rotated segments, archives and orphaned files last modified more
+/// than `max_age` ago. Returns what was removed.
+pub fn prune_by_age(logs_root: &Path, max_age: Duration, live: &HashSet<Uuid>) -> io::Result<Vec<PathBuf>> {
+    let cutoff = SystemTime::now() - max_age;
+    let mut candidates: Vec<PathBuf> = fs::read_dir(logs_root)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let Some(id) = file_session_id(path) else {
+                return false;
+            };
+            !live.contains(&id) || is_segment(path)
+        })
+        .collect();
+    if let Ok(entries) = fs::read_dir(archive_dir(logs_root)) {
+        candidates.extend(entries.flatten().map(|entry| entry.path()));
+    }
+
+    let mut removed = Vec::new();
+    for path in candidates {
+        let modified = fs::metadata(&path).and_then(|m| m.modified());
+        if path.is_file() && modified.is_ok_and(|time| time < cutoff) {
+            fs::remove_file(&path)?;
+            removed.push(path);
+        }
+    }
+    removed.sort();
+    Ok(removed)
+}
+
+/// `<id>.log.<n>` or `<id>.log.<n>.gz`
+fn is_segment(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    let name = name.strip_suffix(".gz").unwrap_or(name);
+    name.rsplit_once('.')
+        .is_some_and(|(log, n)| log.ends_with(".log") && n.parse::<u64>().is_ok())
+}
+
+pub fn max_age(config: &AppConfig) -> Option<Duration> {
+    (config.log_max_age_days > 0).then(|| Duration::from_secs(config.log_max_age_days * 24 * 60 * 60))
+}
+
+/// `deck gc`: prune expired logs and list the files of sessions that no
+/// longer exist, removing them with `--delete`.
+pub fn run_gc(config: &AppConfig, delete: bool) -> io::Result<()> {
+    // An unreadable session list would make every log look orphaned
+    let live: HashSet<Uuid> = persistence::read_sessions(&config.sessions_file_path)?
+        .iter()
+        .map(|s| s.id)
+        .collect();
+
+    if let Some(max_age) = max_age(config) {
+        for path in prune_by_age(&config.logs_root_path, max_age, &live)? {
+            println!("期限切れのため削除: {}", path.display());
+        }
+    }
+
+    let orphans = orphaned_files(&config.logs_root_path, &live)?;
+    if orphans.is_empty() {
+        println!("セッションのないログファイルはありません");
+        return Ok(());
+    }
+    let mut total = 0;
+    for path in &orphans {
+        let size = fs::metadata(path).map_or(0, |m| m.len());
+        total += size;
+        println!("{:>10}  {}", format_size(size), path.display());
+    }
+    if delete {
+        for path in &orphans {
+            fs::remove_file(path)?;
+        }
+        println!("{} 個のファイル（{}）を削除しました", orphans.len(), format_size(total));
+    } else {
+        println!(
+            "セッションのないファイル {} 個（{}）。deck gc --delete で削除します",
+            orphans.len(),
+            format_size(total)
+        );
+    }
+    Ok(())
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn names(paths: &[PathBuf]) -> Vec<String> {
+        paths
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn rotates_compresses_and_keeps_newest_segments() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let log = tmp.path().join("s.log");
+
+        fs::write(&log, "small").unwrap();
+        assert_eq!(rotate_if_needed(&log, 100, 2).unwrap(), None);
+
+        for round in 0..3 {
+            fs::write(&log, format!("round {} {}", round, "x".repeat(200))).unwrap();
+            let segment = rotate_if_needed(&log, 100, 2).unwrap().unwrap();
+            assert!(!log.exists());
+            compress(&segment).unwrap();
+        }
+        let segments = rotated_segments(&log);
+        assert_eq!(names(&segments), vec!["s.log.2.gz", "s.log.3.gz"]);
+
+        let mut text = String::new();
+        flate2::read::GzDecoder::new(File::open(&segments[1]).unwrap())
+            .read_to_string(&mut text)
+            .unwrap();
+        assert!(text.starts_with("round 2 "));
+
+        // A new run starts over without the old segments
+        remove_segments(&log).unwrap();
+        assert!(rotated_segments(&log).is_empty());
+        assert_eq!(current_segment(&log), 1);
+    }
+
+    #[test]
+    fn deleted_session_logs_are_removed_or_archived() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let log = tmp.path().join("a.log");
+        let files = |log: &Path| {
+            fs::write(log, "log").unwrap();
+            fs::write(recording::cast_path(log), "cast").unwrap();
+            fs::write(bookmarks::bookmarks_path(log), "[]").unwrap();
+            fs::write(format!("{}.1.gz", log.display()), "gz").unwrap();
+        };
+
+        files(&log);
+        remove_session_logs(&log, tmp.path(), LogOnDelete::Keep).unwrap();
+        assert!(log.exists());
+        remove_session_logs(&log, tmp.path(), LogOnDelete::Delete).unwrap();
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 0);
+
+        files(&log);
+        remove_session_logs(&log, tmp.path(), LogOnDelete::Archive).unwrap();
+        let mut archived: Vec<PathBuf> = fs::read_dir(archive_dir(tmp.path()))
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .collect();
+        archived.sort();
+        assert_eq!(names(&archived), vec!["a.bookmarks.json.gz", "a.cast.gz", "a.log.1.gz", "a.log.gz"]);
+        assert!(!log.exists());
+    }
+
+    #[test]
+    fn finds_orphans_and_prunes_old_files() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let (live, gone) = (Uuid::new_v4(), Uuid::new_v4());
+        for name in [
+            format!("{}.log", live),
+            format!("{}.log.1.gz", live),
+            format!("{}.log", gone),
+            format!("{}.cast", gone),
+            "notes.txt".to_string(),
+        ] {
+            fs::write(tmp.path().join(name), "x").unwrap();
+        }
+        let live_ids = HashSet::from([live]);
+
+        let orphans = orphaned_files(tmp.path(), &live_ids).unwrap();
+        assert_eq!(names(&orphans), vec![format!("{}.cast", gone), format!("{}.log", gone)]);
+
+        assert!(prune_by_age(tmp.path(), Duration::from_secs(3600), &live_ids)
+            .unwrap()
+            .is_empty());
+        std::thread::sleep(Duration::from_millis(20));
+        let mut removed = names(&prune_by_age(tmp.path(), Duration::from_millis(10), &live_ids).unwrap());
+        removed.sort();
+        let mut expected = vec![
+            format!("{}.cast", gone),
+            format!("{}.log", gone),
+            format!("{}.log.1.gz", live),
+        ];
+        expected.sort();
+        assert_eq!(removed, expected);
+        // The live session's current log stays
+        assert!(tmp.path().join(format!("{}.log", live)).exists());
+        assert!(tmp.path().join("notes.txt").exists());
+    }
+
+    #[test]
+    fn formats_sizes() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(20 * 1024 * 1024), "20.0 MB");
+    }
+}
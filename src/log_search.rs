@@ -0,0 +1,260 @@
+use regex::{Regex, RegexBuilder};
+use std::ops::Range;
+use uuid::Uuid;
+
+/// One match: absolute line number and byte range within the line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Hit {
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Which log view the hits were collected from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchView {
+    Session(Uuid),
+    Unified,
+}
+
+/// Search state of the log panel: the query, its options and the matches
+/// in the lines it was last run over.
+pub struct LogSearch {
+    pub query: String,
+    /// Treat the query as a regular expression instead of literal text
+    pub regex: bool,
+    pub case_sensitive: bool,
+    /// Why the query doesn't compile (invalid regex)
+    pub error: Option<String>,
+    pub view: Option<SearchView>,
+    compiled: Option<Regex>,
+    hits: Vec<Hit>,
+    current: Option<usize>,
+    /// Lines covered by `hits` when they came from `update_log`
+    scanned: Option<Range<usize>>,
+}
+
+impl LogSearch {
+    pub fn new() -> Self {
+        Self {
+            query: String::new(),
+            regex: false,
+            case_sensitive: false,
+            error: None,
+            view: None,
+            compiled: None,
+            hits: Vec::new(),
+            current: None,
+            scanned: None,
+        }
+    }
+
+    /// Rebuild the matcher after the query or an option changed.
+    pub fn compile(&mut self) {
+        self.compiled = None;
+        self.error = None;
+        self.scanned = None;
+        if self.query.is_empty() {
+            return;
+        }
+        let pattern = if self.regex {
+            self.query.clone()
+        } else {
+            regex::escape(&self.query)
+        };
+        match RegexBuilder::new(&pattern)
+            .case_insensitive(!self.case_sensitive)
+            .build()
+        {
+            Ok(re) => self.compiled = Some(re),
+            Err(e) => self.error = Some(e.to_string()),
+        }
+    }
+
+    /// Non-empty match ranges within one line, for highlighting.
+    pub fn matches_in(&self, line: &str) -> Vec<Range<usize>> {
+        let Some(re) = &self.compiled else {
+            return Vec::new();
+        };
+        re.find_iter(line)
+            .filter(|m| !m.is_empty())
+            .map(|m| m.range())
+            .collect()
+    }
+
+    /// Collect the matches in `lines` (numbered from `first`), keeping the
+    /// current hit on the same match when it is still there.
+    pub fn update<S: AsRef<str>>(&mut self, view: SearchView, first: usize, lines: &[S]) {
+        let previous = self.current();
+        self.view = Some(view);
+        self.scanned = None;
+        self.hits.clear();
+        self.scan(first, lines);
+        self.current = previous.and_then(|hit| self.hits.iter().position(|h| *h == hit));
+    }
+
+    /// Like `update` for a log that only grows at the end, read through
+    /// `read(range, each)`: lines searched by the last call are not searched
+    /// again, except the last one, which may have been incomplete.
+    pub fn update_log<E>(
+        &mut self,
+        view: SearchView,
+        lines: Range<usize>,
+        read: impl FnOnce(Range<usize>, &mut dyn FnMut(usize, Vec<String>)) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let previous = self.current();
+        let resume = match &self.scanned {
+            Some(scanned) if self.view == Some(view) && scanned.start <= lines.start => {
+                scanned.end.saturating_sub(1).max(lines.start)
+            }
+            _ => lines.start,
+        };
+        self.view = Some(view);
+        self.hits.retain(|h| h.line >= lines.start && h.line < resume);
+        // Not searched yet if reading fails part way
+        self.scanned = None;
+        read(resume..lines.end, &mut |first, chunk| self.scan(first, &chunk))?;
+        self.scanned = Some(lines);
+        self.current = previous.and_then(|hit| self.hits.iter().position(|h| *h == hit));
+        Ok(())
+    }
+
+    fn scan<S: AsRef<str>>(&mut self, first: usize, lines: &[S]) {
+        for (i, line) in lines.iter().enumerate() {
+            let matches = self.matches_in(line.as_ref());
+            self.hits.extend(matches.into_iter().map(|range| Hit {
+                line: first + i,
+                start: range.start,
+                end: range.end,
+            }));
+        }
+    }
+
+    pub fn current(&self) -> Option<Hit> {
+        self.current.and_then(|i| self.hits.get(i).copied())
+    }
+
+    /// (1-based index of the current hit, number of hits) for the panel title.
+    pub fn counter(&self) -> (usize, usize) {
+        (self.current.map_or(0, |i| i + 1), self.hits.len())
+    }
+
+    /// Select the last hit on or above `line` (or the first hit if there is none).
+    pub fn select_at_or_before(&mut self, line: usize) -> Option<Hit> {
+        if self.hits.is_empty() {
+            self.current = None;
+            return None;
+        }
+        let after = self.hits.partition_point(|h| h.line <= line);
+        self.current = Some(after.saturating_sub(1));
+        self.current()
+    }
+
+    /// Move to the next hit below, wrapping to the top.
+    pub fn next(&mut self) -> Option<Hit> {
+        if self.hits.is_empty() {
+            return None;
+        }
+        self.current = Some(self.current.map_or(0, |i| (i + 1) % self.hits.len()));
+        self.current()
+    }
+
+    /// Move to the previous hit above, wrapping to the bottom.
+    pub fn prev(&mut self) -> Option<Hit> {
+        if self.hits.is_empty() {
+            return None;
+        }
+        let last = self.hits.len() - 1;
+        self.current = Some(self.current.map_or(last, |i| i.checked_sub(1).unwrap_or(last)));
+        self.current()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(query: &str, regex: bool, case_sensitive: bool) -> LogSearch {
+        let mut search = LogSearch::new();
+        search.query = query.to_string();
+        search.regex = regex;
+        search.case_sensitive = case_sensitive;
+        search.compile();
+        search
+    }
+
+    const LINES: [&str; 4] = ["Error: disk", "ok", "error again, ERROR", "done"];
+
+    #[test]
+    fn literal_search_ignores_case_by_default() {
+        let mut s = search("error", false, false);
+        s.update(SearchView::Unified, 10, &LINES);
+        let lines: Vec<usize> = s.hits.iter().map(|h| h.line).collect();
+        assert_eq!(lines, vec![10, 12, 12]);
+
+        let mut s = search("error", false, true);
+        s.update(SearchView::Unified, 0, &LINES);
+        assert_eq!(s.hits, &[Hit { line: 2, start: 0, end: 5 }]);
+
+        // Regex metacharacters are literal unless regex mode is on
+        let mut s = search("e.r", false, false);
+        s.update(SearchView::Unified, 0, &LINES);
+        assert!(s.hits.is_empty());
+    }
+
+    #[test]
+    fn regex_search_and_errors() {
+        let mut s = search(r"^(ok|done)$", true, false);
+        s.update(SearchView::Unified, 0, &LINES);
+        assert_eq!(s.hits.len(), 2);
+
+        let s = search("(", true, false);
+        assert!(s.error.is_some());
+        assert!(s.matches_in("(").is_empty());
+    }
+
+    #[test]
+    fn navigation_wraps_and_survives_updates() {
+        let mut s = search("error", false, false);
+        s.update(SearchView::Unified, 0, &LINES);
+        assert_eq!(s.select_at_or_before(1).map(|h| h.line), Some(0));
+        assert_eq!(s.counter(), (1, 3));
+        assert_eq!(s.prev().map(|h| (h.line, h.start)), Some((2, 13)));
+        assert_eq!(s.next().map(|h| h.line), Some(0));
+        s.next();
+        assert_eq!(s.counter(), (2, 3));
+
+        // New lines keep the selection on the same match
+        let more = ["Error: disk", "ok", "error again, ERROR", "done", "error"];
+        s.update(SearchView::Unified, 0, &more);
+        assert_eq!(s.counter(), (2, 4));
+    }
+
+    #[test]
+    fn log_updates_only_read_new_lines() {
+        let view = SearchView::Session(Uuid::new_v4());
+        let mut log: Vec<String> = ["error 1", "ok", "err"].map(String::from).to_vec();
+        let mut read_ranges = Vec::new();
+        let mut update = |s: &mut LogSearch, log: &[String], range: Range<usize>| {
+            s.update_log(view, range, |r: Range<usize>, each: &mut dyn FnMut(usize, Vec<String>)| {
+                read_ranges.push(r.clone());
+                each(r.start, log[r].to_vec());
+                Ok::<(), ()>(())
+            })
+            .unwrap();
+        };
+
+        let mut s = search("error", false, false);
+        update(&mut s, &log, 0..3);
+        assert_eq!(s.hits.len(), 1);
+        // The last line was still being written
+        log[2].push_str("or 2");
+        log.push("error 3".to_string());
+        update(&mut s, &log, 0..4);
+        assert_eq!(s.hits.len(), 3);
+        // Lines dropped from the front take their hits with them
+        update(&mut s, &log, 1..4);
+        assert_eq!(s.hits.len(), 2);
+        assert_eq!(read_ranges, vec![0..3, 2..4, 3..4]);
+    }
+}
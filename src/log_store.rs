@@ -1,64 +1,311 @@
-use std::collections::HashMap;
+use crate::ansi::LineDecoder;
+use chrono::{DateTime, Utc};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
 use uuid::Uuid;
 
 const MAX_LINES: usize = 10_000;
 
 pub struct LogStore {
-    logs: HashMap<Uuid, Vec<String>>,
+    logs: HashMap<Uuid, SessionLog>,
+    /// Bumped whenever a session's buffer is replaced or dropped, which a
+    /// `Timeline` can't follow incrementally
+    epoch: u64,
+    /// Source of `SessionLog::revision`
+    revisions: u64,
+}
+
+struct SessionLog {
+    /// Plain-text lines; the last entry is the line still being written.
+    lines: Vec<String>,
+    /// When each line started arriving (one per entry of `lines`)
+    arrived: Vec<DateTime<Utc>>,
+    /// Number of older lines dropped to stay under MAX_LINES
+    trimmed: usize,
+    /// Changes with every append, so a `Timeline` knows what to merge again
+    revision: u64,
+    decoder: LineDecoder,
 }
 
 impl LogStore {
     pub fn new() -> Self {
         Self {
             logs: HashMap::new(),
+            epoch: 0,
+            revisions: 0,
         }
     }
 
     /// Append raw PTY output bytes to the session's log buffer.
-    /// Handles partial lines by appending to the last line if it was incomplete.
+    /// Escape sequences are stripped and partial lines are continued on the next call.
     pub fn append(&mut self, session_id: Uuid, data: &[u8]) {
-        let text = String::from_utf8_lossy(data);
-        let buffer = self.logs.entry(session_id).or_default();
-        let parts: Vec<&str> = text.split('\n').collect();
+        self.append_at(session_id, data, Utc::now());
+    }
 
-        if parts.is_empty() {
-            return;
-        }
+    fn append_at(&mut self, session_id: Uuid, data: &[u8], now: DateTime<Utc>) {
+        self.revisions += 1;
+        let log = self.logs.entry(session_id).or_insert_with(|| SessionLog {
+            lines: Vec::new(),
+            arrived: Vec::new(),
+            trimmed: 0,
+            revision: 0,
+            decoder: LineDecoder::new(),
+        });
+        log.revision = self.revisions;
+        // Arrival times never go back, even if the clock does
+        let now = log.arrived.last().map_or(now, |last| now.max(*last));
 
-        // First part continues the last (possibly partial) line
-        if let Some(last) = buffer.last_mut() {
-            last.push_str(parts[0].trim_end_matches('\r'));
-        } else {
-            buffer.push(parts[0].trim_end_matches('\r').to_string());
+        // A line is stamped with the arrival of its first byte
+        let partial_started = match (log.lines.pop(), log.arrived.pop()) {
+            (Some(partial), Some(at)) if !partial.is_empty() => at,
+            _ => now,
+        };
+        let completed = log.decoder.feed(data);
+        let partial = log.decoder.partial();
+        let stamps = std::iter::once(partial_started).chain(std::iter::repeat(now));
+        log.arrived.extend(stamps.take(completed.len() + 1));
+        log.lines.extend(completed);
+        log.lines.push(partial);
+
+        // Trim oldest lines if over limit
+        if log.lines.len() > MAX_LINES {
+            let drain_count = log.lines.len() - MAX_LINES;
+            log.lines.drain(..drain_count);
+            log.arrived.drain(..drain_count);
+            log.trimmed += drain_count;
         }
+    }
+
+    /// Replace the session's buffer with lines received from elsewhere (an engine snapshot)
+    /// starting at line number `first_line`. The last line is treated as still being written.
+    pub fn restore(
+        &mut self,
+        session_id: Uuid,
+        first_line: usize,
+        lines: Vec<String>,
+        mut arrived: Vec<DateTime<Utc>>,
+    ) {
+        let decoder = LineDecoder::with_partial(lines.last().map_or("", |l| l.as_str()));
+        arrived.resize(lines.len(), Utc::now());
+        self.epoch += 1;
+        self.revisions += 1;
+        self.logs.insert(
+            session_id,
+            SessionLog {
+                lines,
+                arrived,
+                trimmed: first_line,
+                revision: self.revisions,
+                decoder,
+            },
+        );
+    }
 
-        // Remaining parts are new lines
-        for part in &parts[1..] {
-            buffer.push(part.trim_end_matches('\r').to_string());
+    pub fn remove(&mut self, session_id: &Uuid) {
+        if self.logs.remove(session_id).is_some() {
+            self.epoch += 1;
         }
+    }
 
-        // Trim oldest lines if over limit
-        if buffer.len() > MAX_LINES {
-            let drain_count = buffer.len() - MAX_LINES;
-            buffer.drain(..drain_count);
+    /// Keep only the sessions for which `keep` returns true.
+    pub fn retain(&mut self, keep: impl Fn(&Uuid) -> bool) {
+        let before = self.logs.len();
+        self.logs.retain(|id, _| keep(id));
+        if self.logs.len() != before {
+            self.epoch += 1;
         }
     }
 
     pub fn lines(&self, session_id: &Uuid) -> &[String] {
         self.logs
             .get(session_id)
-            .map(|v| v.as_slice())
+            .map(|log| log.lines.as_slice())
             .unwrap_or(&[])
     }
 
-    /// Get the last non-empty line for needs_input pattern detection.
-    pub fn last_non_empty_line(&self, session_id: &Uuid) -> Option<&str> {
+    /// Arrival time of each line in `lines()`.
+    pub fn arrived(&self, session_id: &Uuid) -> &[DateTime<Utc>] {
         self.logs
             .get(session_id)
-            .and_then(|lines| lines.iter().rev().find(|l| !l.is_empty()))
-            .map(|s| s.as_str())
+            .map(|log| log.arrived.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Line number of `lines()[0]`: how many older lines were dropped from memory.
+    pub fn first_line(&self, session_id: &Uuid) -> usize {
+        self.logs.get(session_id).map_or(0, |log| log.trimmed)
     }
+}
+
+/// A line of the unified log: which session printed it and when.
+pub struct TimelineLine<'a> {
+    pub session_id: Uuid,
+    pub arrived: DateTime<Utc>,
+    pub text: &'a str,
+}
+
+/// The non-empty lines of several sessions merged in order of arrival, each
+/// made into a `T` (or left out). Each session's lines keep their own order;
+/// ties go to the earlier session. `sync` only merges the lines that changed
+/// since the last call, so the unified log needn't be rebuilt every frame.
+pub struct Timeline<T> {
+    sessions: Vec<Uuid>,
+    epoch: u64,
+    synced: HashMap<Uuid, Synced>,
+    /// Sorted; one per entry of `items`
+    keys: Vec<TimelineKey>,
+    items: Vec<T>,
+}
 
+/// How far a session's lines were merged by the last `sync`.
+struct Synced {
+    revision: u64,
+    first_line: usize,
+    /// The line that was still being written, merged again when it changes
+    partial: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct TimelineKey {
+    arrived: DateTime<Utc>,
+    /// Index in `Timeline::sessions`
+    session: usize,
+    line: usize,
+}
+
+impl<T> Default for Timeline<T> {
+    fn default() -> Self {
+        Self {
+            sessions: Vec::new(),
+            epoch: 0,
+            synced: HashMap::new(),
+            keys: Vec::new(),
+            items: Vec::new(),
+        }
+    }
+}
+
+impl<T> Timeline<T> {
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    /// Bring the timeline of `sessions` up to date with `store`. Lines for
+    /// which `make` returns None are left out.
+    pub fn sync(
+        &mut self,
+        store: &LogStore,
+        sessions: &[Uuid],
+        mut make: impl FnMut(TimelineLine) -> Option<T>,
+    ) {
+        if self.sessions != sessions || self.epoch != store.epoch {
+            *self = Self {
+                sessions: sessions.to_vec(),
+                epoch: store.epoch,
+                ..Self::default()
+            };
+        }
+
+        // Where each changed session's lines have to be merged again from
+        let mut changed = Vec::new();
+        let mut trimmed = false;
+        for (session, id) in sessions.iter().enumerate() {
+            let Some(log) = store.logs.get(id) else {
+                continue;
+            };
+            let seen = self.synced.get(id);
+            if seen.is_some_and(|seen| seen.revision == log.revision) {
+                continue;
+            }
+            trimmed |= seen.is_some_and(|seen| seen.first_line < log.trimmed);
+            let from = seen.map_or(log.trimmed, |seen| seen.partial.max(log.trimmed));
+            self.synced.insert(
+                *id,
+                Synced {
+                    revision: log.revision,
+                    first_line: log.trimmed,
+                    partial: (log.trimmed + log.lines.len()).saturating_sub(1),
+                },
+            );
+            if from < log.trimmed + log.lines.len() {
+                changed.push((session, log, from));
+            }
+        }
+
+        if trimmed {
+            let first_lines: Vec<usize> = sessions
+                .iter()
+                .map(|id| self.synced.get(id).map_or(0, |s| s.first_line))
+                .collect();
+            let mut keep = self.keys.iter().map(|k| k.line >= first_lines[k.session]);
+            self.items.retain(|_| keep.next().unwrap_or(true));
+            self.keys.retain(|k| k.line >= first_lines[k.session]);
+        }
+        let key = |session: usize, log: &SessionLog, line: usize| TimelineKey {
+            arrived: log.arrived[line - log.trimmed],
+            session,
+            line,
+        };
+        let Some(earliest) = changed.iter().map(|&(s, log, from)| key(s, log, from)).min() else {
+            return;
+        };
+
+        // Everything from the earliest changed line on is merged again with
+        // the changed lines, without their entries from the last sync
+        let cut = self.keys.partition_point(|k| *k < earliest);
+        let stale = |k: &TimelineKey| {
+            changed
+                .iter()
+                .any(|&(s, _, from)| k.session == s && k.line >= from)
+        };
+        let old: Vec<(TimelineKey, T)> = self
+            .keys
+            .split_off(cut)
+            .into_iter()
+            .zip(self.items.split_off(cut))
+            .filter(|(k, _)| !stale(k))
+            .collect();
+
+        // k-way merge of the changed sessions' new lines
+        let mut heap: BinaryHeap<Reverse<(TimelineKey, usize)>> = changed
+            .iter()
+            .enumerate()
+            .map(|(run, &(s, log, from))| Reverse((key(s, log, from), run)))
+            .collect();
+        let mut new = Vec::new();
+        while let Some(Reverse((next, run))) = heap.pop() {
+            let (session, log, _) = changed[run];
+            if next.line + 1 < log.trimmed + log.lines.len() {
+                heap.push(Reverse((key(session, log, next.line + 1), run)));
+            }
+            let text = &log.lines[next.line - log.trimmed];
+            if text.is_empty() {
+                continue;
+            }
+            let line = TimelineLine {
+                session_id: sessions[session],
+                arrived: next.arrived,
+                text,
+            };
+            if let Some(item) = make(line) {
+                new.push((next, item));
+            }
+        }
+
+        let mut old = old.into_iter().peekable();
+        let mut new = new.into_iter().peekable();
+        loop {
+            let take_old = match (old.peek(), new.peek()) {
+                (Some((a, _)), Some((b, _))) => a < b,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => break,
+            };
+            let (k, item) = if take_old { old.next() } else { new.next() }.unwrap();
+            self.keys.push(k);
+            self.items.push(item);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -100,6 +347,110 @@ mod tests {
         assert!(store.lines(&id).is_empty());
     }
 
+    #[test]
+    fn strips_ansi_sequences() {
+        let mut store = LogStore::new();
+        let id = Uuid::new_v4();
+        store.append(id, b"\x1b[1;32mok\x1b[0m\n");
+        assert_eq!(store.lines(&id), &["ok", ""]);
+    }
+
+    #[test]
+    fn restore_continues_last_line() {
+        let mut store = LogStore::new();
+        let id = Uuid::new_v4();
+        store.restore(id, 7, vec!["done".to_string(), "part".to_string()], Vec::new());
+        store.append(id, b"ial\nnext");
+        assert_eq!(store.lines(&id), &["done", "partial", "next"]);
+        assert_eq!(store.first_line(&id), 7);
+    }
+
+    #[test]
+    fn counts_trimmed_lines() {
+        let mut store = LogStore::new();
+        let id = Uuid::new_v4();
+        for i in 0..MAX_LINES + 5 {
+            store.append(id, format!("{}\n", i).as_bytes());
+        }
+        assert_eq!(store.lines(&id).len(), MAX_LINES);
+        assert_eq!(store.first_line(&id), 6);
+        assert_eq!(store.lines(&id)[0], "6");
+    }
+
+    #[test]
+    fn stamps_lines_when_they_start() {
+        let mut store = LogStore::new();
+        let id = Uuid::new_v4();
+        let at = |s| DateTime::from_timestamp(s, 0).unwrap();
+        store.append_at(id, b"one\ntw", at(1));
+        store.append_at(id, b"o\n", at(2));
+        store.append_at(id, b"three\n", at(3));
+        assert_eq!(store.lines(&id), &["one", "two", "three", ""]);
+        assert_eq!(store.arrived(&id), &[at(1), at(1), at(3), at(3)]);
+    }
+
+    fn texts(timeline: &mut Timeline<(Uuid, String)>, store: &LogStore, ids: &[Uuid]) -> Vec<(Uuid, String)> {
+        timeline.sync(store, ids, |l| Some((l.session_id, l.text.to_string())));
+        timeline.items().to_vec()
+    }
+
+    #[test]
+    fn timeline_interleaves_sessions_by_arrival() {
+        let mut store = LogStore::new();
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let at = |s| DateTime::from_timestamp(s, 0).unwrap();
+        store.append_at(a, b"a1\n", at(1));
+        store.append_at(b, b"b1\n\n", at(2));
+        store.append_at(a, b"a2\n", at(3));
+        store.append_at(b, b"b2\n", at(3));
+
+        let mut timeline = Timeline::default();
+        let line = |id, text: &str| (id, text.to_string());
+        assert_eq!(
+            texts(&mut timeline, &store, &[a, b]),
+            [line(a, "a1"), line(b, "b1"), line(a, "a2"), line(b, "b2")]
+        );
+        assert_eq!(texts(&mut Timeline::default(), &store, &[b]).len(), 2);
+    }
+
+    #[test]
+    fn timeline_follows_new_output() {
+        let mut store = LogStore::new();
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let at = |s| DateTime::from_timestamp(s, 0).unwrap();
+        let line = |id, text: &str| (id, text.to_string());
+        let mut timeline = Timeline::default();
+
+        // A line still being written keeps its place when it grows
+        store.append_at(a, b"a1\nwai", at(1));
+        store.append_at(b, b"b1\n", at(2));
+        assert_eq!(
+            texts(&mut timeline, &store, &[a, b]),
+            [line(a, "a1"), line(a, "wai"), line(b, "b1")]
+        );
+        store.append_at(a, b"ting\na2\n", at(3));
+        store.append_at(b, b"b2\n", at(3));
+        assert_eq!(
+            texts(&mut timeline, &store, &[a, b]),
+            [line(a, "a1"), line(a, "waiting"), line(b, "b1"), line(a, "a2"), line(b, "b2")]
+        );
+
+        // Lines trimmed from memory leave the timeline
+        for i in 0..MAX_LINES {
+            store.append_at(b, format!("{}\n", i).as_bytes(), at(4));
+        }
+        let merged = texts(&mut timeline, &store, &[a, b]);
+        assert_eq!(merged.len(), 3 + MAX_LINES - 1);
+        assert_eq!(merged[..3], [line(a, "a1"), line(a, "waiting"), line(a, "a2")]);
+        assert_eq!(merged[3], line(b, "1"));
+
+        // Replacing a buffer starts over
+        store.restore(a, 0, vec!["x".to_string(), String::new()], vec![at(5), at(5)]);
+        let merged = texts(&mut timeline, &store, &[a, b]);
+        assert_eq!(merged.last(), Some(&line(a, "x")));
+        assert_eq!(merged.len(), MAX_LINES);
+    }
+
     #[test]
     fn handles_crlf() {
         let mut store = LogStore::new();
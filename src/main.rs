@@ -1,21 +1,65 @@
+mod ansi;
 mod app;
+mod backend;
+mod bookmarks;
 mod br_poller;
 mod config;
+mod conversation;
 mod dir_tree;
+mod engine;
+mod export;
 mod file_preview;
 mod input_history;
+mod journal;
+mod key_encoder;
+mod launch;
+mod log_filter;
+mod log_pager;
+mod log_retention;
+mod log_search;
 mod log_store;
 mod needs_input;
 mod persistence;
+mod proc_tree;
+mod protocol;
 mod pty_manager;
+mod recording;
+mod redact;
+mod replay;
+mod scrollback;
+mod selection;
+mod server;
 mod session;
+mod terminal;
 mod ui;
 
 use app::AppState;
+use backend::Backend;
 use config::AppConfig;
 use ftui_runtime::{App, ScreenMode};
 
 fn main() -> std::io::Result<()> {
+    let config = AppConfig::load()?;
+    config.ensure_dirs()?;
+
+    // `deck server` runs the session daemon, `deck --local` keeps sessions in this
+    // process, plain `deck` attaches to the daemon (starting it if needed)
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let backend = match args.first().map(|a| a.as_str()) {
+        Some("server") => return server::run(config),
+        Some("gc") => {
+            let delete = args.get(1).is_some_and(|a| a == "--delete");
+            return log_retention::run_gc(&config, delete);
+        }
+        Some("--local") => Backend::local(config.clone()),
+        None => Backend::attach(&config)?,
+        Some(other) => {
+            eprintln!("不明な引数: {}", other);
+            eprintln!("使い方: deck [server | gc [--delete] | --local]");
+            std::process::exit(2);
+        }
+    };
+
     // Panic hook: restore terminal on panic
     let default_hook = std::panic::take_hook();
     std::panic::set_hook(Box::new(move |info| {
@@ -26,10 +70,7 @@ fn main() -> std::io::Result<()> {
         default_hook(info);
     }));
 
-    let config = AppConfig::default();
-    config.ensure_dirs()?;
-
-    let model = AppState::new(config);
+    let model = AppState::new(config, backend);
 
     App::new(model)
         .screen_mode(ScreenMode::AltScreen)
@@ -1,27 +1,330 @@
 use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::LazyLock;
 
+/// Profile for sessions whose program no profile lists, unless the config says otherwise.
+pub const DEFAULT_PROFILE: &str = "claude";
+
+/// Lines at the bottom of a session's screen that detection looks at.
+pub const SCREEN_LINES: usize = 20;
+
+/// An option of a selection dialog: `❯ 1. Yes`, `  2) No`.
+static CHOICE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?:([❯›>▶])\s*)?(\d+)[.)]\s+(\S.*)$").unwrap());
+/// Alternatives in brackets (`(y/n)`, `[Yes/No/Always]`) or closing the line (`Yes/No?`).
+static ALTERNATIVES: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"[(\[]\s*([A-Za-z]+(?:\s*/\s*[A-Za-z]+)+)\s*[)\]]|\b([A-Z][a-z]{0,9}(?:/[A-Z][a-z]{0,9})+)\s*[?:]?\s*$")
+        .unwrap()
+});
+/// Alternatives marked by their key: `(Y)es/(N)o/(A)ll`.
+static MARKED_ALTERNATIVES: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\([A-Za-z]\)[a-z' ]*(?:\s*/\s*\([A-Za-z]\)[a-z' ]*)+").unwrap());
+static MARKED_ALTERNATIVE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\(([A-Za-z])\)([a-z' ]*)").unwrap());
+static PRESS_ENTER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)press (enter|return)\b").unwrap());
+
+/// How to tell that an agent is waiting for the user: a line matching any
+/// `include` pattern and none of the `exclude` patterns is a prompt.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NeedsInputProfile {
+    /// Programs (by file name) whose sessions use this profile by default
+    pub programs: Vec<String>,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+/// The file name of a launch program, which profiles list programs by.
+pub fn program_name(program: &str) -> &str {
+    Path::new(program)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or(program)
+}
+
+/// Profiles deck ships with; `needs_input_profiles` in the config adds to
+/// them or replaces one by name.
+pub fn builtin_profiles() -> BTreeMap<String, NeedsInputProfile> {
+    let patterns = |list: &[&str]| list.iter().map(|p| p.to_string()).collect();
+    BTreeMap::from([
+        (
+            DEFAULT_PROFILE.to_string(),
+            NeedsInputProfile {
+                programs: vec!["claude".to_string()],
+                include: patterns(&[
+                    r"\(y/n\)",
+                    r"\(Y/n\)",
+                    r"\(yes/no\)",
+                    r"(?i)\bconfirm\b.*\?",
+                    r"(?i)\bcontinue\s*\?",
+                    r"(?i)\bproceed\s*\?",
+                    r"(?i)\ballow\b.*\?",
+                    r"(?i)do you want to\b",
+                ]),
+                exclude: Vec::new(),
+            },
+        ),
+        (
+            "generic".to_string(),
+            NeedsInputProfile {
+                programs: Vec::new(),
+                include: patterns(&[
+                    r"(?i)[(\[]y(es)?/no?[)\]]",
+                    r"(?i)press (enter|return|any key)\b",
+                    r"(?i)\bpassword:\s*$",
+                ]),
+                exclude: Vec::new(),
+            },
+        ),
+    ])
+}
+
+/// Why a session went to NeedsInput, kept on the session to show the user.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum NeedsInputReason {
+    /// An `include` pattern of the profile (`pattern`) matched `text`
+    Pattern { pattern: String, text: String },
+    /// A selection dialog; `text` is its question, or its first option
+    Dialog { text: String },
+    /// No output for `secs` seconds while reading the terminal; `unchecked`
+    /// when its processes couldn't be seen to confirm that (no /proc)
+    Timeout {
+        secs: u64,
+        #[serde(default)]
+        unchecked: bool,
+    },
+    /// Toggled by the user
+    Manual,
+}
+
+impl NeedsInputReason {
+    /// Short form for the session list: `pattern: (y/n)`, `timeout 30s`.
+    pub fn label(&self) -> String {
+        match self {
+            Self::Pattern { text, .. } => format!("pattern: {}", text.trim()),
+            Self::Dialog { text } => format!("dialog: {}", text),
+            Self::Timeout { secs, unchecked: false } => format!("timeout {}s", secs),
+            Self::Timeout { secs, unchecked: true } => format!("timeout {}s (unchecked)", secs),
+            Self::Manual => "manual".to_string(),
+        }
+    }
+}
+
+/// A compiled `NeedsInputProfile`.
 pub struct NeedsInputDetector {
-    patterns: Vec<Regex>,
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
 }
 
 impl NeedsInputDetector {
-    pub fn new() -> Self {
-        let patterns = vec![
-            Regex::new(r"\(y/n\)").unwrap(),
-            Regex::new(r"\(Y/n\)").unwrap(),
-            Regex::new(r"\(yes/no\)").unwrap(),
-            Regex::new(r"(?i)\bconfirm\b.*\?").unwrap(),
-            Regex::new(r"(?i)\bcontinue\s*\?").unwrap(),
-            Regex::new(r"(?i)\bproceed\s*\?").unwrap(),
-            Regex::new(r"(?i)\ballow\b.*\?").unwrap(),
-            Regex::new(r"(?i)do you want to\b").unwrap(),
-        ];
-        Self { patterns }
+    pub fn new(profile: &NeedsInputProfile) -> Result<Self, String> {
+        Ok(Self {
+            include: compile(&profile.include)?,
+            exclude: compile(&profile.exclude)?,
+        })
     }
 
-    pub fn check(&self, text: &str) -> bool {
-        self.patterns.iter().any(|p| p.is_match(text))
+    /// The first `include` pattern matching `text`, unless an `exclude` one does.
+    pub fn check(&self, text: &str) -> Option<NeedsInputReason> {
+        if self.exclude.iter().any(|p| p.is_match(text)) {
+            return None;
+        }
+        self.include.iter().find_map(|p| {
+            p.find(text).map(|m| NeedsInputReason::Pattern {
+                pattern: p.as_str().to_string(),
+                text: m.as_str().to_string(),
+            })
+        })
     }
+
+    /// Whether the bottom of a session's screen (plain text lines, top to
+    /// bottom) shows a prompt: a selection dialog, or a prompt line that the
+    /// profile's patterns match.
+    pub fn check_screen(&self, lines: &[String]) -> Option<NeedsInputReason> {
+        choice_dialog(lines)
+            .map(|dialog| NeedsInputReason::Dialog {
+                text: dialog.question,
+            })
+            .or_else(|| self.check(&prompt_text(lines)?))
+    }
+}
+
+fn is_box_drawing(c: char) -> bool {
+    ('\u{2500}'..='\u{257f}').contains(&c)
+}
+
+/// A screen line without the box it is drawn in; rules and borders alone
+/// leave nothing.
+fn unboxed(line: &str) -> &str {
+    line.trim_matches(|c: char| is_box_drawing(c) || c.is_whitespace())
+}
+
+/// A spinner frame left below the prompt (`⠋`, `✻ Working…`, `|`).
+fn is_spinner(line: &str) -> bool {
+    let mut chars = line.chars();
+    match chars.next() {
+        Some(c) if ('\u{2800}'..='\u{28ff}').contains(&c) || "✻✶✳✢✽".contains(c) => {
+            chars.next().is_none_or(char::is_whitespace)
+        }
+        Some('|' | '/' | '-' | '\\') => chars.next().is_none(),
+        _ => false,
+    }
+}
+
+/// The prompt at the bottom of the screen: the last line with text on it,
+/// past box borders and spinner frames, joined with the line above when the
+/// last one is only an input marker such as `>`.
+fn prompt_text(lines: &[String]) -> Option<String> {
+    let mut content = lines
+        .iter()
+        .rev()
+        .map(|l| unboxed(l))
+        .filter(|l| !l.is_empty() && !is_spinner(l));
+    let last = content.next()?;
+    if last.chars().any(char::is_alphanumeric) {
+        return Some(last.to_string());
+    }
+    Some(match content.next() {
+        Some(above) => format!("{} {}", above, last),
+        None => last.to_string(),
+    })
+}
+
+/// The question and option texts of a selection dialog.
+struct ChoiceDialog {
+    question: String,
+    options: Vec<String>,
+}
+
+/// A selection dialog such as Claude Code's permission prompt, recognised by
+/// its shape rather than its wording: options numbered 1, 2, … (long ones may
+/// wrap), one of them pointed at, drawn in a box or under a rule, and nothing
+/// but a hint line or two below them. The question is the line above the
+/// options, or else the first option.
+fn choice_dialog(lines: &[String]) -> Option<ChoiceDialog> {
+    // (first line, last line, options, pointed at) of the last run of options
+    let mut run: Option<(usize, usize, Vec<String>, bool)> = None;
+    let mut open = false;
+    for (i, line) in lines.iter().enumerate() {
+        let text = unboxed(line);
+        if let Some(caps) = CHOICE.captures(text) {
+            let n: usize = caps[2].parse().unwrap_or(0);
+            let pointer = caps.get(1).is_some();
+            let option = caps[3].trim().to_string();
+            match &mut run {
+                Some((_, last, options, pointed)) if open && n == options.len() + 1 => {
+                    *last = i;
+                    options.push(option);
+                    *pointed |= pointer;
+                }
+                _ if n == 1 => {
+                    run = Some((i, i, vec![option], pointer));
+                    open = true;
+                }
+                _ => open = false,
+            }
+        } else if text.is_empty() {
+            open = false;
+        }
+        // Anything else is the wrapped text of an option
+    }
+    let (first, last, options, pointed) = run?;
+    if options.len() < 2 || !pointed {
+        return None;
+    }
+    let boxed = lines[first].trim_start().starts_with(is_box_drawing)
+        || lines[..first]
+            .iter()
+            .any(|l| !l.trim().is_empty() && unboxed(l).is_empty());
+    let below = lines[last + 1..]
+        .iter()
+        .filter(|l| !unboxed(l).is_empty())
+        .count();
+    if !boxed || below > 2 {
+        return None;
+    }
+    let question = lines[..first]
+        .last()
+        .map(|l| unboxed(l))
+        .filter(|l| !l.is_empty())
+        .unwrap_or(&options[0])
+        .to_string();
+    Some(ChoiceDialog { question, options })
+}
+
+/// A reply a prompt offers, to answer it with one key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Choice {
+    pub label: String,
+    /// Bytes to type into the session
+    pub reply: String,
+}
+
+impl Choice {
+    fn new(label: &str, reply: String) -> Self {
+        Self {
+            label: label.to_string(),
+            reply,
+        }
+    }
+}
+
+/// The replies the prompt at the bottom of a screen offers: a selection
+/// dialog's options (chosen by their number), or the alternatives of a prompt
+/// line such as `(y/n)`, `[Yes/No/Always]`, `(Y)es/(N)o` or "Press Enter"
+/// (typed, then Enter).
+pub fn choices(lines: &[String]) -> Vec<Choice> {
+    if let Some(dialog) = choice_dialog(lines) {
+        return dialog
+            .options
+            .iter()
+            .enumerate()
+            .map(|(i, option)| Choice::new(option, (i + 1).to_string()))
+            .collect();
+    }
+    let Some(prompt) = prompt_text(lines) else {
+        return Vec::new();
+    };
+    if let Some(caps) = MARKED_ALTERNATIVES.captures(&prompt) {
+        return MARKED_ALTERNATIVE
+            .captures_iter(&caps[0])
+            .map(|alt| {
+                let label = format!("{}{}", &alt[1], alt[2].trim_end());
+                Choice::new(&label, format!("{}\r", alt[1].to_lowercase()))
+            })
+            .collect();
+    }
+    if let Some(caps) = ALTERNATIVES.captures(&prompt) {
+        let words = caps.get(1).or(caps.get(2)).map_or("", |m| m.as_str());
+        return words
+            .split('/')
+            .map(str::trim)
+            .map(|word| Choice::new(word, format!("{}\r", word.to_lowercase())))
+            .collect();
+    }
+    if PRESS_ENTER.is_match(&prompt) {
+        return vec![Choice::new("Enter", "\r".to_string())];
+    }
+    Vec::new()
+}
+
+fn compile(patterns: &[String]) -> Result<Vec<Regex>, String> {
+    patterns
+        .iter()
+        .map(|p| {
+            Regex::new(p).map_err(|e| {
+                format!(
+                    "'{}' は正規表現として不正です: {}",
+                    p,
+                    e.to_string().lines().last().unwrap_or_default()
+                )
+            })
+        })
+        .collect()
 }
 
 #[cfg(test)]
@@ -29,48 +332,180 @@ mod tests {
     use super::*;
 
     fn detector() -> NeedsInputDetector {
-        NeedsInputDetector::new()
+        NeedsInputDetector::new(&builtin_profiles()[DEFAULT_PROFILE]).unwrap()
     }
 
     #[test]
     fn detects_yn() {
         let d = detector();
-        assert!(d.check("Continue? (y/n)"));
-        assert!(d.check("Overwrite file? (Y/n)"));
-        assert!(d.check("Are you sure? (yes/no)"));
+        assert!(d.check("Continue? (y/n)").is_some());
+        assert!(d.check("Overwrite file? (Y/n)").is_some());
+        assert!(d.check("Are you sure? (yes/no)").is_some());
     }
 
     #[test]
     fn detects_question_patterns() {
         let d = detector();
-        assert!(d.check("Do you want to proceed?"));
-        assert!(d.check("do you want to install this package?"));
-        assert!(d.check("Would you like to continue?"));
-        assert!(d.check("Shall we proceed?"));
+        assert!(d.check("Do you want to proceed?").is_some());
+        assert!(d.check("do you want to install this package?").is_some());
+        assert!(d.check("Would you like to continue?").is_some());
+        assert!(d.check("Shall we proceed?").is_some());
     }
 
     #[test]
     fn detects_confirm_allow() {
         let d = detector();
-        assert!(d.check("Please confirm?"));
-        assert!(d.check("Allow access to filesystem?"));
+        assert!(d.check("Please confirm?").is_some());
+        assert!(d.check("Allow access to filesystem?").is_some());
     }
 
     #[test]
     fn ignores_normal_output() {
         let d = detector();
-        assert!(!d.check("Building project..."));
-        assert!(!d.check("Test passed"));
-        assert!(!d.check("Compiling main.rs"));
-        assert!(!d.check(""));
+        assert!(d.check("Building project...").is_none());
+        assert!(d.check("Test passed").is_none());
+        assert!(d.check("Compiling main.rs").is_none());
+        assert!(d.check("").is_none());
     }
 
     #[test]
     fn ignores_partial_matches() {
         let d = detector();
         // "confirm" without question mark should not match
-        assert!(!d.check("Sending confirmation email"));
+        assert!(d.check("Sending confirmation email").is_none());
         // "allow" without question mark should not match
-        assert!(!d.check("This will allow faster builds"));
+        assert!(d.check("This will allow faster builds").is_none());
+    }
+
+    #[test]
+    fn exclude_patterns_and_invalid_regexes() {
+        let profile = NeedsInputProfile {
+            programs: vec!["aider".to_string()],
+            include: vec![r"\?\s*$".to_string()],
+            exclude: vec![r"^(What|Why)\b".to_string()],
+        };
+        let d = NeedsInputDetector::new(&profile).unwrap();
+        assert!(d.check("Apply these edits?").is_some());
+        assert!(d.check("Why did the test fail?").is_none());
+        assert_eq!(program_name("/usr/local/bin/aider"), "aider");
+        assert_eq!(program_name("claude"), "claude");
+
+        let broken = NeedsInputProfile {
+            include: vec!["(unclosed".to_string()],
+            ..NeedsInputProfile::default()
+        };
+        let error = NeedsInputDetector::new(&broken).err().unwrap();
+        assert!(error.starts_with("'(unclosed' は正規表現として不正です"));
+    }
+
+    #[test]
+    fn generic_profile_detects_common_prompts() {
+        let d = NeedsInputDetector::new(&builtin_profiles()["generic"]).unwrap();
+        assert!(d.check("Remove 3 files? [y/N]").is_some());
+        assert!(d.check("Press Enter to continue").is_some());
+        assert!(d.check("[sudo] password: ").is_some());
+        assert!(d.check("Building project...").is_none());
+    }
+
+    fn screen(text: &str) -> Vec<String> {
+        text.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn finds_the_prompt_past_borders_spinners_and_input_markers() {
+        let d = detector();
+        let reason = d.check_screen(&screen(
+            "Building...\n╭──────────────────────╮\n│ Overwrite? (y/n)     │\n╰──────────────────────╯",
+        ));
+        assert_eq!(
+            reason,
+            Some(NeedsInputReason::Pattern {
+                pattern: r"\(y/n\)".to_string(),
+                text: "(y/n)".to_string(),
+            })
+        );
+        assert_eq!(reason.unwrap().label(), "pattern: (y/n)");
+        assert!(d.check_screen(&screen("Do you want to proceed?\n> ")).is_some());
+        assert!(d.check_screen(&screen("Continue? (y/n)\n⠋")).is_some());
+        // An answered prompt further up doesn't count
+        assert!(d.check_screen(&screen("Continue? (y/n) y\nBuilding...")).is_none());
+        assert!(d.check_screen(&[]).is_none());
+    }
+
+    #[test]
+    fn timeout_reasons_say_when_unchecked() {
+        // Saved before the process tree was sampled
+        let old: NeedsInputReason = serde_json::from_str(r#"{"rule":"timeout","secs":30}"#).unwrap();
+        assert_eq!(old.label(), "timeout 30s");
+        let unchecked = NeedsInputReason::Timeout {
+            secs: 30,
+            unchecked: true,
+        };
+        assert_eq!(unchecked.label(), "timeout 30s (unchecked)");
+    }
+
+    #[test]
+    fn recognises_choice_dialogs_by_their_shape() {
+        // Matches none of the profile's patterns
+        let d = NeedsInputDetector::new(&NeedsInputProfile::default()).unwrap();
+        let boxed = "\
+╭───────────────────────────────────╮
+│ Bash command                      │
+│   rm -rf target                   │
+│ Run this?                         │
+│ ❯ 1. Yes                          │
+│   2. Yes, and don't ask again for │
+│      rm commands in /work         │
+│   3. No (esc)                     │
+╰───────────────────────────────────╯";
+        assert_eq!(
+            d.check_screen(&screen(boxed)),
+            Some(NeedsInputReason::Dialog {
+                text: "Run this?".to_string()
+            })
+        );
+        let ruled = "──────────\n Edit file\n ❯ 1. Yes\n   2. No\n\n Esc to cancel";
+        assert!(d.check_screen(&screen(ruled)).is_some());
+
+        // A numbered list in plain output is not a dialog
+        assert!(d.check_screen(&screen("Steps:\n1. build\n2. test")).is_none());
+        // Nor is one without a box, or one followed by more output
+        assert!(d.check_screen(&screen("❯ 1. Yes\n  2. No")).is_none());
+        assert!(d.check_screen(&screen(&format!("{}\nok\nok\nok", ruled))).is_none());
+    }
+
+    fn replies(text: &str) -> Vec<(String, String)> {
+        choices(&screen(text))
+            .into_iter()
+            .map(|c| (c.label, c.reply))
+            .collect()
+    }
+
+    fn pairs(list: &[(&str, &str)]) -> Vec<(String, String)> {
+        list.iter().map(|(l, r)| (l.to_string(), r.to_string())).collect()
+    }
+
+    #[test]
+    fn reads_the_choices_a_prompt_offers() {
+        assert_eq!(
+            replies("Overwrite file? (y/n)"),
+            pairs(&[("y", "y\r"), ("n", "n\r")])
+        );
+        assert_eq!(
+            replies("│ Apply edits? [Yes/No/Always] │\n╰──────╯"),
+            pairs(&[("Yes", "yes\r"), ("No", "no\r"), ("Always", "always\r")])
+        );
+        assert_eq!(
+            replies("Create new file? (Y)es/(N)o/(D)on't ask again [Yes]: "),
+            pairs(&[("Yes", "y\r"), ("No", "n\r"), ("Don't ask again", "d\r")])
+        );
+        assert_eq!(replies("Press Enter to continue"), pairs(&[("Enter", "\r")]));
+        assert_eq!(
+            replies("──────────\n Edit file?\n ❯ 1. Yes\n   2. No, tell Claude\n\n Esc to cancel"),
+            pairs(&[("Yes", "1"), ("No, tell Claude", "2")])
+        );
+        // Paths and plain questions offer nothing
+        assert!(replies("Edit src/main?").is_empty());
+        assert!(replies("Do you want to proceed?").is_empty());
     }
 }
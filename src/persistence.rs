@@ -2,20 +2,33 @@ use crate::session::{Session, SessionStatus};
 use std::path::Path;
 
 pub fn load_sessions(path: &Path) -> Vec<Session> {
-    let Ok(data) = std::fs::read_to_string(path) else {
-        return Vec::new();
-    };
-    let mut sessions: Vec<Session> = serde_json::from_str(&data).unwrap_or_default();
-    // Reset running/needs_input sessions to queued (PTY processes are gone after restart)
+    let mut sessions = read_sessions(path).unwrap_or_default();
+    // Reset running/needs_input sessions to queued (deck no longer owns their PTYs).
+    // `pty_pid` is kept so the caller can look for processes left running.
     for s in &mut sessions {
         if s.status == SessionStatus::Running || s.status == SessionStatus::NeedsInput {
             s.status = SessionStatus::Queued;
-            s.pty_pid = None;
+            s.needs_input_reason = None;
+            s.busy = None;
         }
     }
     sessions
 }
 
+/// The saved session list as is; no file is an empty list, a broken one an error.
+pub fn read_sessions(path: &Path) -> std::io::Result<Vec<Session>> {
+    match std::fs::read_to_string(path) {
+        Ok(data) => serde_json::from_str(&data).map_err(|e| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("{} を読み込めません: {}", path.display(), e),
+            )
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
 pub fn save_sessions(path: &Path, sessions: &[Session]) -> std::io::Result<()> {
     if let Some(parent) = path.parent() {
         std::fs::create_dir_all(parent)?;
@@ -37,6 +50,30 @@ pub fn write_log_header(log_path: &Path, session: &Session) -> std::io::Result<(
     Ok(())
 }
 
+/// Separator marking a resumed run in the existing log, which is appended to
+/// instead of truncated.
+pub fn resume_marker(conversation_id: &str) -> String {
+    format!(
+        "\n# Resumed: {} (conversation {})\n---\n",
+        chrono::Utc::now(),
+        conversation_id
+    )
+}
+
+/// OSC command of the instruction markers in session logs.
+pub const INSTRUCTION_OSC: &str = "deck-instruction";
+
+/// Note an instruction in the log for transcripts. The marker is an OSC
+/// sequence, which log readers drop, so it adds no lines; its payload is JSON
+/// with `;` escaped so it stays one OSC parameter.
+pub fn write_instruction_marker(log_path: &Path, text: &str) -> std::io::Result<()> {
+    let payload = serde_json::json!({ "at": chrono::Utc::now(), "text": text })
+        .to_string()
+        .replace(';', "\\u003b");
+    let marker = format!("\x1b]{};{}\x07", INSTRUCTION_OSC, payload);
+    append_log(log_path, marker.as_bytes())
+}
+
 pub fn append_log(log_path: &Path, data: &[u8]) -> std::io::Result<()> {
     use std::io::Write;
     let mut file = std::fs::OpenOptions::new()
@@ -45,3 +82,25 @@ pub fn append_log(log_path: &Path, data: &[u8]) -> std::io::Result<()> {
         .open(log_path)?;
     file.write_all(data)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_resets_live_sessions_but_keeps_pid() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("sessions.json");
+        let mut session = Session::new("s".to_string(), tmp.path().to_path_buf(), tmp.path());
+        session.transition_to(SessionStatus::Running).unwrap();
+        session.transition_to(SessionStatus::NeedsInput).unwrap();
+        session.needs_input_reason = Some(crate::needs_input::NeedsInputReason::Manual);
+        session.pty_pid = Some(4242);
+        save_sessions(&path, &[session]).unwrap();
+
+        let loaded = load_sessions(&path);
+        assert_eq!(loaded[0].status, SessionStatus::Queued);
+        assert_eq!(loaded[0].needs_input_reason, None);
+        assert_eq!(loaded[0].pty_pid, Some(4242));
+    }
+}
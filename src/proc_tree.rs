@@ -0,0 +1,364 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+/// CPU time a quiet tree may use between two samples and still count as idle
+/// (clock ticks, usually 1/100 s).
+const IDLE_TICKS: u64 = 5;
+
+/// Syscalls that read input. Only Linux has /proc; elsewhere no tree can be
+/// sampled and the engine reports the silence as unchecked.
+#[cfg(target_os = "linux")]
+const READ_SYSCALLS: &[libc::c_long] = &[libc::SYS_read, libc::SYS_readv, libc::SYS_pread64];
+#[cfg(not(target_os = "linux"))]
+const READ_SYSCALLS: &[libc::c_long] = &[];
+
+/// A process as `/proc/<pid>/stat` describes it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub comm: String,
+    /// R running, S sleeping, D uninterruptible (disk), Z zombie, …
+    pub state: char,
+    pub ppid: u32,
+    pub pgrp: u32,
+    /// Foreground process group of the controlling terminal
+    pub tpgid: i32,
+    /// utime + stime
+    pub cpu_ticks: u64,
+}
+
+pub fn parse_stat(stat: &str) -> Option<ProcessInfo> {
+    // comm may contain spaces and parentheses: it ends at the last ')'
+    let open = stat.find('(')?;
+    let close = stat.rfind(')')?;
+    let pid = stat[..open].trim().parse().ok()?;
+    let comm = stat.get(open + 1..close)?.to_string();
+    let fields: Vec<&str> = stat.get(close + 1..)?.split_whitespace().collect();
+    let field = |i: usize| fields.get(i).copied();
+    Some(ProcessInfo {
+        pid,
+        comm,
+        state: field(0)?.chars().next()?,
+        ppid: field(1)?.parse().ok()?,
+        pgrp: field(2)?.parse().ok()?,
+        tpgid: field(5)?.parse().ok()?,
+        cpu_ticks: field(11)?.parse::<u64>().ok()? + field(12)?.parse::<u64>().ok()?,
+    })
+}
+
+/// When a process started, which tells it apart from a later one that reuses
+/// its pid (after a wraparound or a reboot).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProcessStart {
+    pub boot_id: String,
+    /// Clock ticks after boot (field 22 of `/proc/<pid>/stat`)
+    pub ticks: u64,
+}
+
+fn start_ticks(stat: &str) -> Option<u64> {
+    stat.get(stat.rfind(')')? + 1..)?.split_whitespace().nth(19)?.parse().ok()
+}
+
+/// When `pid` started; None when it isn't running or there is no /proc.
+pub fn process_start(pid: u32) -> Option<ProcessStart> {
+    let boot_id = std::fs::read_to_string("/proc/sys/kernel/random/boot_id").ok()?;
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    Some(ProcessStart {
+        boot_id: boot_id.trim().to_string(),
+        ticks: start_ticks(&stat)?,
+    })
+}
+
+/// Start of every process in the group `pgid`.
+pub fn group_starts(pgid: u32) -> Vec<ProcessStart> {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|e| e.file_name().to_str()?.parse::<u32>().ok())
+        .filter(|pid| {
+            std::fs::read_to_string(format!("/proc/{}/stat", pid))
+                .ok()
+                .and_then(|stat| parse_stat(&stat))
+                .is_some_and(|p| p.pgrp == pgid)
+        })
+        .filter_map(process_start)
+        .collect()
+}
+
+/// `root` and all its descendants, root first (empty when it isn't running or
+/// there is no /proc).
+pub fn tree(root: u32) -> Vec<ProcessInfo> {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    let all: Vec<ProcessInfo> = entries
+        .flatten()
+        .filter_map(|e| e.file_name().to_str()?.parse::<u32>().ok())
+        .filter_map(|pid| parse_stat(&std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?))
+        .collect();
+    let Some(root) = all.iter().find(|p| p.pid == root).cloned() else {
+        return Vec::new();
+    };
+    let mut members = HashSet::from([root.pid]);
+    let mut found = vec![root];
+    // Parents may have larger pids than their children, so repeat until stable
+    loop {
+        let before = found.len();
+        for p in &all {
+            if !members.contains(&p.pid) && members.contains(&p.ppid) {
+                members.insert(p.pid);
+                found.push(p.clone());
+            }
+        }
+        if found.len() == before {
+            return found;
+        }
+    }
+}
+
+fn is_terminal(pid: u32, fd: u64) -> bool {
+    std::fs::read_link(format!("/proc/{}/fd/{}", pid, fd)).is_ok_and(|target| {
+        target.starts_with("/dev/pts") || target.starts_with(Path::new("/dev/tty"))
+    })
+}
+
+/// Whether the process is asleep in a blocking read of its stdin, which is
+/// the terminal. Waiting in `poll`/`epoll` doesn't count: event loops (Node,
+/// and so Claude Code) sit there whether or not they want input.
+fn reads_terminal(p: &ProcessInfo) -> bool {
+    if p.state != 'S' || !is_terminal(p.pid, 0) {
+        return false;
+    }
+    match std::fs::read_to_string(format!("/proc/{}/syscall", p.pid)) {
+        Ok(syscall) => reads_stdin(&syscall),
+        // Not allowed to see the syscall: go by where the kernel has it waiting
+        Err(_) => {
+            let wchan = std::fs::read_to_string(format!("/proc/{}/wchan", p.pid)).unwrap_or_default();
+            wchan.contains("tty_read") || wchan == "wait_woken"
+        }
+    }
+}
+
+/// Whether a `/proc/<pid>/syscall` line is a read of fd 0.
+fn reads_stdin(syscall: &str) -> bool {
+    let mut words = syscall.split_whitespace();
+    let nr = words.next().and_then(|n| n.parse::<libc::c_long>().ok());
+    let fd = words
+        .next()
+        .and_then(|a| u64::from_str_radix(a.trim_start_matches("0x"), 16).ok());
+    matches!((nr, fd), (Some(nr), Some(0)) if READ_SYSCALLS.contains(&nr))
+}
+
+/// What a session that has stopped printing is doing.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Quiet {
+    /// Its foreground process is blocked reading the terminal and nothing runs
+    WaitingForInput,
+    /// Still working; names the process doing the work
+    Busy(String),
+    /// Neither: asleep with nothing under it, but not in a read of the
+    /// terminal (an event loop, which may or may not want input)
+    Idle,
+    /// Its processes can't be seen (no /proc, or it is gone): only the
+    /// silence is known
+    Unseen,
+}
+
+/// CPU time of each process of a tree when it was last sampled.
+#[derive(Clone, Debug, Default)]
+pub struct TreeSample {
+    ticks: HashMap<u32, u64>,
+}
+
+/// Sample the process tree of a session's agent (`root`), comparing CPU time
+/// with the `previous` sample. None when the tree can't be seen (the process
+/// is gone, or there is no /proc), in which case the caller has only the
+/// silence to go by.
+pub fn sample(root: u32, previous: Option<&TreeSample>) -> Option<(Quiet, TreeSample)> {
+    let processes = tree(root);
+    if processes.is_empty() {
+        return None;
+    }
+    let quiet = judge(&processes, previous, reads_terminal);
+    let ticks = processes.iter().map(|p| (p.pid, p.cpu_ticks)).collect();
+    Some((quiet, TreeSample { ticks }))
+}
+
+/// Waiting for input only when a foreground process reads the terminal while
+/// nothing in the tree runs or uses CPU, and every other live process of the
+/// foreground group just waits for a reader to finish (a shell running
+/// `read`, say). Otherwise busy with the process that used the most CPU, is
+/// running, or else the newest foreground child that isn't waiting on a
+/// reader; idle when there is none.
+fn judge(
+    processes: &[ProcessInfo],
+    previous: Option<&TreeSample>,
+    reads_terminal: impl Fn(&ProcessInfo) -> bool,
+) -> Quiet {
+    let used = |p: &ProcessInfo| {
+        previous
+            .and_then(|s| s.ticks.get(&p.pid))
+            .map_or(0, |before| p.cpu_ticks.saturating_sub(*before))
+    };
+    let total: u64 = processes.iter().map(used).sum();
+    let running = processes.iter().any(|p| matches!(p.state, 'R' | 'D'));
+    let foreground = processes[0].tpgid;
+    let live: Vec<&ProcessInfo> = processes
+        .iter()
+        .filter(|p| p.state != 'Z' && (foreground <= 0 || p.pgrp as i32 == foreground))
+        .collect();
+
+    // The readers and the processes above them, which wait for them
+    let mut waiting: HashSet<u32> = live.iter().filter(|p| reads_terminal(p)).map(|p| p.pid).collect();
+    let reading = !waiting.is_empty();
+    let parents: HashMap<u32, u32> = processes.iter().map(|p| (p.pid, p.ppid)).collect();
+    for pid in waiting.clone() {
+        let mut pid = pid;
+        while let Some(&parent) = parents.get(&pid) {
+            waiting.insert(parent);
+            pid = parent;
+        }
+    }
+    let working = live.iter().any(|p| !waiting.contains(&p.pid));
+    if reading && !working && !running && total <= IDLE_TICKS {
+        return Quiet::WaitingForInput;
+    }
+    let busiest = processes
+        .iter()
+        .filter(|p| used(p) > 0)
+        .max_by_key(|p| used(p))
+        .or_else(|| processes.iter().find(|p| matches!(p.state, 'R' | 'D')))
+        .or_else(|| {
+            live.iter()
+                .filter(|p| p.pid != processes[0].pid && !waiting.contains(&p.pid))
+                .max_by_key(|p| p.pid)
+                .copied()
+        });
+    match busiest {
+        Some(p) => Quiet::Busy(p.comm.clone()),
+        None => Quiet::Idle,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(pid: u32, comm: &str, state: char, cpu_ticks: u64) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            comm: comm.to_string(),
+            state,
+            ppid: 1,
+            pgrp: 100,
+            tpgid: 100,
+            cpu_ticks,
+        }
+    }
+
+    #[test]
+    fn parses_stat_with_odd_command_names() {
+        let stat = "4242 (tmux: server) (1)) S 1 4242 4242 34817 4242 4194304 396 0 0 0 7 3 0 0 20 0";
+        let p = parse_stat(stat).unwrap();
+        assert_eq!((p.pid, p.comm.as_str(), p.state), (4242, "tmux: server) (1)", 'S'));
+        assert_eq!((p.ppid, p.pgrp, p.tpgid, p.cpu_ticks), (1, 4242, 4242, 10));
+        assert!(parse_stat("garbage").is_none());
+        assert_eq!(start_ticks(&format!("{} 1 0 9", stat)), Some(9));
+        assert_eq!(start_ticks(stat), None);
+    }
+
+    fn child(pid: u32, comm: &str, ppid: u32) -> ProcessInfo {
+        ProcessInfo {
+            ppid,
+            ..process(pid, comm, 'S', 0)
+        }
+    }
+
+    #[test]
+    fn waits_only_when_reading_the_terminal_with_nothing_running() {
+        let agent = process(100, "claude", 'S', 500);
+        let reads = |p: &ProcessInfo| p.pid == 100;
+        assert_eq!(judge(std::slice::from_ref(&agent), None, reads), Quiet::WaitingForInput);
+
+        // A build running under the agent
+        let build = process(101, "cargo", 'R', 40);
+        assert_eq!(
+            judge(&[agent.clone(), build.clone()], None, reads),
+            Quiet::Busy("cargo".to_string())
+        );
+
+        // Using CPU between samples, though asleep when sampled
+        let previous = TreeSample {
+            ticks: HashMap::from([(100, 500), (101, 10)]),
+        };
+        let build = ProcessInfo { state: 'S', ..build };
+        assert_eq!(
+            judge(&[agent.clone(), build], Some(&previous), reads),
+            Quiet::Busy("cargo".to_string())
+        );
+
+        // A quiet child in the foreground, though the agent could take input
+        let sleep = child(102, "sleep", 100);
+        assert_eq!(
+            judge(&[agent.clone(), sleep.clone()], None, reads),
+            Quiet::Busy("sleep".to_string())
+        );
+        // ... but not once it has exited, or when it runs in the background
+        let exited = ProcessInfo { state: 'Z', ..sleep.clone() };
+        assert_eq!(judge(&[agent.clone(), exited], None, reads), Quiet::WaitingForInput);
+        let background = ProcessInfo { pgrp: 102, ..sleep };
+        assert_eq!(judge(&[agent, background], None, reads), Quiet::WaitingForInput);
+    }
+
+    #[test]
+    fn event_loops_and_waiting_parents() {
+        // An event loop (Claude Code) reads nothing while it sleeps
+        let node = process(100, "node", 'S', 500);
+        let sleep = child(101, "sleep", 100);
+        let reads = |p: &ProcessInfo| p.comm == "read";
+        assert_eq!(
+            judge(&[node.clone(), sleep], None, reads),
+            Quiet::Busy("sleep".to_string())
+        );
+        assert_eq!(judge(&[node], None, reads), Quiet::Idle);
+
+        // A script waiting for its child, which reads the terminal
+        let sh = process(100, "sh", 'S', 0);
+        let reader = child(101, "read", 100);
+        assert_eq!(judge(&[sh, reader], None, reads), Quiet::WaitingForInput);
+    }
+
+    #[test]
+    fn only_reads_of_stdin_count() {
+        let read = format!("{} 0x0 0x7ffd5e2a 0x1", READ_SYSCALLS[0]);
+        assert!(reads_stdin(&read));
+        assert!(!reads_stdin(&format!("{} 0x3 0x7ffd5e2a 0x1", READ_SYSCALLS[0])));
+        assert!(!reads_stdin(&format!("{} 0x0 0x0 0x0", libc::SYS_epoll_pwait)));
+        assert!(!reads_stdin("running"));
+    }
+
+    #[test]
+    fn finds_child_processes() {
+        let mut child = std::process::Command::new("sleep").arg("5").spawn().unwrap();
+        let processes = tree(std::process::id());
+        assert_eq!(processes[0].pid, std::process::id());
+        assert!(processes.iter().any(|p| p.pid == child.id() && p.comm == "sleep"));
+        let _ = child.kill();
+        let _ = child.wait();
+        assert!(tree(u32::MAX).is_empty());
+    }
+
+    #[test]
+    fn start_tells_processes_apart() {
+        let mut child = std::process::Command::new("sleep").arg("5").spawn().unwrap();
+        let start = process_start(child.id()).unwrap();
+        assert_eq!(process_start(child.id()), Some(start.clone()));
+        assert!(process_start(std::process::id()).unwrap().ticks <= start.ticks);
+        let _ = child.kill();
+        let _ = child.wait();
+        assert!(process_start(u32::MAX).is_none());
+    }
+}
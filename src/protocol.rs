@@ -0,0 +1,134 @@
+use crate::session::Session;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Commands a client sends to the session engine (one JSON object per line on the socket).
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request {
+    /// Add a session built by the client (it picks the id so it can select it).
+    CreateSession { session: Box<Session> },
+    DeleteSession { id: Uuid },
+    RenameSession { id: Uuid, name: String },
+    /// Send an instruction line, spawning the agent at `cols`x`rows` if it isn't running.
+    Instruct {
+        id: Uuid,
+        text: String,
+        cols: u16,
+        rows: u16,
+    },
+    /// Raw bytes for the PTY (passthrough keys, pastes).
+    SendBytes {
+        id: Uuid,
+        #[serde(with = "base64_bytes")]
+        data: Vec<u8>,
+    },
+    /// Ctrl+C to the session's PTY.
+    Interrupt { id: Uuid },
+    /// Manual NeedsInput toggle.
+    ToggleNeedsInput { id: Uuid },
+    /// SIGTERM the session's process group, SIGKILL after a grace period.
+    Stop { id: Uuid },
+    /// Stop every live session and wait for them to exit.
+    StopAll,
+    Resize { id: Uuid, cols: u16, rows: u16 },
+    /// Kill or keep the process groups left by a previous run.
+    ResolveOrphans { kill: bool },
+}
+
+/// Updates the engine broadcasts to every attached client.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// Full state, sent once when a client attaches.
+    Snapshot {
+        sessions: Vec<Session>,
+        orphans: Vec<(Uuid, u32)>,
+        logs: Vec<LogSnapshot>,
+        terminals: Vec<TerminalSnapshot>,
+    },
+    /// The session list changed (status, rename, create, delete).
+    Sessions { sessions: Vec<Session> },
+    /// Raw PTY output, or a deck notice, for one session.
+    Output {
+        id: Uuid,
+        #[serde(with = "base64_bytes")]
+        data: Vec<u8>,
+    },
+    /// The session's PTY (and terminal) now has this size.
+    Resized { id: Uuid, cols: u16, rows: u16 },
+    Orphans { orphans: Vec<(Uuid, u32)> },
+}
+
+/// Plain-text log lines held in memory for a session.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct LogSnapshot {
+    pub id: Uuid,
+    /// Line number of `lines[0]` (older lines were dropped from memory)
+    pub first_line: usize,
+    pub lines: Vec<String>,
+    /// When each line arrived, for the unified timeline
+    pub arrived: Vec<DateTime<Utc>>,
+}
+
+/// A session's terminal screen as escape sequences that reproduce it.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct TerminalSnapshot {
+    pub id: Uuid,
+    pub rows: u16,
+    pub cols: u16,
+    #[serde(with = "base64_bytes")]
+    pub state: Vec<u8>,
+}
+
+/// Serialize a message as one newline-terminated JSON line.
+pub fn encode_line<T: Serialize>(msg: &T) -> Vec<u8> {
+    let mut line = serde_json::to_vec(msg).unwrap_or_default();
+    line.push(b'\n');
+    line
+}
+
+/// PTY bytes are not valid UTF-8 in general, so they travel base64-encoded.
+mod base64_bytes {
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(data))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        STANDARD.decode(encoded).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_roundtrip_as_base64() {
+        let event = Event::Output {
+            id: Uuid::new_v4(),
+            data: vec![0x1b, b'[', b'3', b'1', b'm', 0xff, 0x00],
+        };
+        let line = encode_line(&event);
+        assert_eq!(line.last(), Some(&b'\n'));
+        let text = std::str::from_utf8(&line).unwrap();
+        assert!(text.contains(r#""type":"output""#));
+        let decoded: Event = serde_json::from_str(text.trim_end()).unwrap();
+        assert_eq!(decoded, event);
+    }
+
+    #[test]
+    fn request_tags() {
+        let id = Uuid::new_v4();
+        let line = encode_line(&Request::Stop { id });
+        let decoded: Request = serde_json::from_slice(&line).unwrap();
+        assert_eq!(decoded, Request::Stop { id });
+        assert!(serde_json::from_str::<Request>(r#"{"type":"bogus"}"#).is_err());
+    }
+}
@@ -1,20 +1,32 @@
+use crate::launch::LaunchSpec;
+use crate::proc_tree::{self, ProcessStart};
+use anyhow::Context;
 use portable_pty::{native_pty_system, CommandBuilder, MasterPty, PtySize};
 use std::io::{Read, Write};
 use std::path::Path;
 use std::sync::mpsc;
 use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
 pub struct PtyHandle {
     writer: Box<dyn Write + Send>,
     output_rx: mpsc::Receiver<Vec<u8>>,
     child: Box<dyn portable_pty::Child + Send + Sync>,
-    _master: Box<dyn MasterPty>,
-    _reader_thread: JoinHandle<()>,
+    master: Box<dyn MasterPty>,
+    reader_thread: JoinHandle<()>,
 }
 
 impl PtyHandle {
-    /// Spawn `claude` in a PTY rooted at `root_path`.
-    pub fn spawn(root_path: &Path, cols: u16, rows: u16) -> anyhow::Result<Self> {
+    /// Spawn the session's agent (see `LaunchSpec`) in a PTY rooted at `root_path`.
+    pub fn spawn(
+        root_path: &Path,
+        launch: &LaunchSpec,
+        cols: u16,
+        rows: u16,
+    ) -> anyhow::Result<Self> {
+        let env = launch
+            .resolved_env(root_path)
+            .context("env ファイルを読み込めません")?;
         let pty_system = native_pty_system();
         let pair = pty_system.openpty(PtySize {
             rows,
@@ -23,8 +35,12 @@ impl PtyHandle {
             pixel_height: 0,
         })?;
 
-        let mut cmd = CommandBuilder::new("claude");
+        let mut cmd = CommandBuilder::new(&launch.program);
+        cmd.args(&launch.args);
         cmd.cwd(root_path);
+        for (key, value) in env {
+            cmd.env(key, value);
+        }
 
         let child = pair.slave.spawn_command(cmd)?;
         // Close slave end so reads on master get EOF when child exits
@@ -35,7 +51,7 @@ impl PtyHandle {
 
         let (tx, rx) = mpsc::channel();
 
-        let _reader_thread = std::thread::spawn(move || {
+        let reader_thread = std::thread::spawn(move || {
             let mut reader = reader;
             let mut buf = [0u8; 4096];
             loop {
@@ -55,8 +71,8 @@ impl PtyHandle {
             writer,
             output_rx: rx,
             child,
-            _master: pair.master,
-            _reader_thread,
+            master: pair.master,
+            reader_thread,
         })
     }
 
@@ -69,12 +85,38 @@ impl PtyHandle {
         chunks
     }
 
+    /// Output still in flight when the child exited: waits up to `timeout`
+    /// for the reader to reach EOF, then drains it.
+    pub fn drain_after_exit(&self, timeout: Duration) -> Vec<Vec<u8>> {
+        let deadline = Instant::now() + timeout;
+        while !self.reader_thread.is_finished() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        self.try_read()
+    }
+
     /// Send text to the PTY (e.g. user instruction + newline).
     pub fn send_input(&mut self, text: &str) -> std::io::Result<()> {
         self.writer.write_all(text.as_bytes())?;
         self.writer.flush()
     }
 
+    /// Write raw bytes (encoded keys, escape sequences) to the PTY.
+    pub fn send_bytes(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.writer.write_all(data)?;
+        self.writer.flush()
+    }
+
+    /// Resize the PTY; the kernel notifies the child with SIGWINCH.
+    pub fn resize(&self, cols: u16, rows: u16) -> anyhow::Result<()> {
+        self.master.resize(PtySize {
+            rows,
+            cols,
+            pixel_width: 0,
+            pixel_height: 0,
+        })
+    }
+
     /// Send Ctrl+C (0x03) to the PTY.
     pub fn send_sigint(&mut self) -> std::io::Result<()> {
         self.writer.write_all(&[0x03])?;
@@ -94,4 +136,138 @@ impl PtyHandle {
     pub fn process_id(&self) -> Option<u32> {
         self.child.process_id()
     }
+
+    /// Ask the agent and everything it started to exit (SIGTERM to the process group).
+    pub fn terminate(&self) -> std::io::Result<()> {
+        match self.process_id() {
+            Some(pid) => signal_group(pid, libc::SIGTERM),
+            None => Ok(()),
+        }
+    }
+
+    /// Force the whole process group down (SIGKILL).
+    pub fn kill(&self) -> std::io::Result<()> {
+        match self.process_id() {
+            Some(pid) => signal_group(pid, libc::SIGKILL),
+            None => Ok(()),
+        }
+    }
+
+    /// Terminate, wait up to `grace` for the child to exit, then kill.
+    /// Blocks; used when quitting or deleting a session.
+    pub fn shutdown(mut self, grace: Duration) {
+        let _ = self.terminate();
+        let deadline = Instant::now() + grace;
+        while Instant::now() < deadline {
+            if self.check_exit().is_some() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        let _ = self.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Send `signal` to the process group led by `pgid`.
+/// Agents are spawned as session leaders, so their pid is also their group id.
+pub fn signal_group(pgid: u32, signal: libc::c_int) -> std::io::Result<()> {
+    // SAFETY: kill(2) has no memory-safety preconditions
+    if unsafe { libc::kill(-(pgid as libc::pid_t), signal) } == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+/// Whether any process we may signal is left in the group led by `pgid`
+/// (the leader may be gone while its children keep running). A group of
+/// another user (EPERM) can't be ours, so it doesn't count.
+pub fn group_alive(pgid: u32) -> bool {
+    signal_group(pgid, 0).is_ok()
+}
+
+/// Whether the group led by `pgid` is still the one whose leader started at
+/// `start`, and not an unrelated group that reused the id after a reboot or a
+/// pid wraparound. Once the leader is gone, every process left in the group
+/// must have started after it.
+pub fn group_started(pgid: u32, start: &ProcessStart) -> bool {
+    if !group_alive(pgid) {
+        return false;
+    }
+    if let Some(leader) = proc_tree::process_start(pgid) {
+        return leader == *start;
+    }
+    let members = proc_tree::group_starts(pgid);
+    !members.is_empty()
+        && members
+            .iter()
+            .all(|m| m.boot_id == start.boot_id && m.ticks >= start.ticks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn terminate_stops_process_group() {
+        let launch = LaunchSpec::parse("sh -c 'sleep 30 & sleep 30'", "").unwrap();
+        let mut handle = PtyHandle::spawn(Path::new("/tmp"), &launch, 80, 24).unwrap();
+        let pid = handle.process_id().unwrap();
+        assert!(group_alive(pid));
+
+        handle.terminate().unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while handle.check_exit().is_none() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        // The background sleep shared the group, so it is gone too
+        while group_alive(pid) && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert!(!group_alive(pid));
+    }
+
+    #[test]
+    fn group_must_match_its_leader_start() {
+        let launch = LaunchSpec::parse("sleep 30", "").unwrap();
+        let handle = PtyHandle::spawn(Path::new("/tmp"), &launch, 80, 24).unwrap();
+        let pid = handle.process_id().unwrap();
+        let start = proc_tree::process_start(pid).unwrap();
+        assert!(group_started(pid, &start));
+
+        // The same pgid reused by a later process
+        let reused = ProcessStart {
+            ticks: start.ticks + 1,
+            ..start.clone()
+        };
+        assert!(!group_started(pid, &reused));
+        // ... or after a reboot
+        let rebooted = ProcessStart {
+            boot_id: "another boot".to_string(),
+            ..start.clone()
+        };
+        assert!(!group_started(pid, &rebooted));
+
+        handle.shutdown(Duration::from_millis(300));
+        // No such group
+        assert!(!group_started(u32::MAX >> 2, &start));
+    }
+
+    #[test]
+    fn shutdown_escalates_to_kill() {
+        let launch = LaunchSpec::parse("sh -c \"trap '' TERM; sleep 30\"", "").unwrap();
+        let handle = PtyHandle::spawn(Path::new("/tmp"), &launch, 80, 24).unwrap();
+        let pid = handle.process_id().unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+
+        let started = Instant::now();
+        handle.shutdown(Duration::from_millis(300));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while group_alive(pid) && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert!(!group_alive(pid));
+    }
 }
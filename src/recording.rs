@@ -0,0 +1,304 @@
+use crate::session::SessionStatus;
+use serde_json::json;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// The asciicast v2 recording that sits next to a session's `.log` file.
+pub fn cast_path(log_path: &Path) -> PathBuf {
+    log_path.with_extension("cast")
+}
+
+/// How much of a cast `Recorder::resume` reads at first when looking for the
+/// last event; the window grows until a complete event line fits.
+const TAIL_WINDOW: u64 = 16 * 1024;
+
+/// Label prefixes of the marker ("m") events deck adds for the replay viewer.
+const INSTRUCTION_PREFIX: &str = "instruction: ";
+const STATUS_PREFIX: &str = "status: ";
+
+/// A point of interest in a recording.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Marker {
+    /// An instruction sent from the input bar
+    Instruction(String),
+    /// The session's new status (`SessionStatus` name)
+    Status(String),
+}
+
+impl Marker {
+    pub fn parse(label: &str) -> Option<Self> {
+        if let Some(text) = label.strip_prefix(INSTRUCTION_PREFIX) {
+            Some(Self::Instruction(text.to_string()))
+        } else {
+            label
+                .strip_prefix(STATUS_PREFIX)
+                .map(|status| Self::Status(status.to_string()))
+        }
+    }
+}
+
+/// Writes a session's PTY traffic as asciicast v2: a JSON header line, then one
+/// `[seconds, code, data]` line per event ("o" output, "i" input, "r" resize,
+/// "m" marker).
+pub struct Recorder {
+    file: File,
+    started: Instant,
+    /// Time already recorded by earlier runs in the same file
+    offset: f64,
+    // Bytes of a UTF-8 sequence split across chunks, per stream
+    output_pending: Vec<u8>,
+    input_pending: Vec<u8>,
+    last_status: Option<SessionStatus>,
+}
+
+impl Recorder {
+    /// Start a new recording, replacing any previous one.
+    pub fn create(path: &Path, cols: u16, rows: u16, title: &str) -> std::io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = File::create(path)?;
+        let header = json!({
+            "version": 2,
+            "width": cols,
+            "height": rows,
+            "timestamp": chrono::Utc::now().timestamp(),
+            "title": title,
+        });
+        writeln!(file, "{}", header)?;
+        Ok(Self::with_offset(file, 0.0))
+    }
+
+    /// Continue an existing recording (a resumed conversation): new events are
+    /// timed after its last event and the current size is recorded.
+    pub fn resume(path: &Path, cols: u16, rows: u16, title: &str) -> std::io::Result<Self> {
+        let len = std::fs::metadata(path).map_or(0, |meta| meta.len());
+        if len == 0 {
+            return Self::create(path, cols, rows, title);
+        }
+        let mut file = OpenOptions::new().read(true).append(true).open(path)?;
+        let offset = last_event_time(&mut file, len)?;
+
+        // A run that died mid-write may have left a partial line
+        let mut last = [0u8];
+        file.seek(SeekFrom::Start(len - 1))?;
+        file.read_exact(&mut last)?;
+        if last[0] != b'\n' {
+            writeln!(file)?;
+        }
+        let mut recorder = Self::with_offset(file, offset);
+        recorder.resize(cols, rows)?;
+        Ok(recorder)
+    }
+
+    fn with_offset(file: File, offset: f64) -> Self {
+        Self {
+            file,
+            started: Instant::now(),
+            offset,
+            output_pending: Vec::new(),
+            input_pending: Vec::new(),
+            last_status: None,
+        }
+    }
+
+    pub fn output(&mut self, data: &[u8]) -> std::io::Result<()> {
+        let text = take_utf8(&mut self.output_pending, data);
+        self.event("o", &text)
+    }
+
+    pub fn input(&mut self, data: &[u8]) -> std::io::Result<()> {
+        let text = take_utf8(&mut self.input_pending, data);
+        self.event("i", &text)
+    }
+
+    pub fn resize(&mut self, cols: u16, rows: u16) -> std::io::Result<()> {
+        self.event("r", &format!("{}x{}", cols, rows))
+    }
+
+    pub fn instruction(&mut self, text: &str) -> std::io::Result<()> {
+        self.event("m", &format!("{}{}", INSTRUCTION_PREFIX, text))
+    }
+
+    /// Mark a status change; repeated reports of the same status are skipped.
+    pub fn status(&mut self, status: &SessionStatus) -> std::io::Result<()> {
+        if self.last_status.as_ref() == Some(status) {
+            return Ok(());
+        }
+        self.last_status = Some(status.clone());
+        self.event("m", &format!("{}{:?}", STATUS_PREFIX, status))
+    }
+
+    fn event(&mut self, code: &str, data: &str) -> std::io::Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        let time = self.offset + self.started.elapsed().as_secs_f64();
+        // Microsecond precision, like asciinema
+        let time = (time * 1_000_000.0).round() / 1_000_000.0;
+        writeln!(self.file, "{}", json!([time, code, data]))
+    }
+}
+
+/// Append `data` to `pending` and take everything up to a trailing incomplete
+/// UTF-8 sequence, which stays in `pending`. Invalid bytes become U+FFFD.
+fn take_utf8(pending: &mut Vec<u8>, data: &[u8]) -> String {
+    pending.extend_from_slice(data);
+    let mut text = String::new();
+    let mut keep = 0;
+    let mut chunks = pending.utf8_chunks().peekable();
+    while let Some(chunk) = chunks.next() {
+        text.push_str(chunk.valid());
+        let invalid = chunk.invalid();
+        if invalid.is_empty() {
+            continue;
+        }
+        let incomplete = chunks.peek().is_none()
+            && std::str::from_utf8(invalid).is_err_and(|e| e.error_len().is_none());
+        if incomplete {
+            keep = invalid.len();
+        } else {
+            text.push(char::REPLACEMENT_CHARACTER);
+        }
+    }
+    pending.drain(..pending.len() - keep);
+    text
+}
+
+/// Time of the last complete event in a cast of `len` bytes, read from the end
+/// of the file so a long recording isn't loaded whole. 0 when there is none.
+fn last_event_time(file: &mut File, len: u64) -> std::io::Result<f64> {
+    let mut window = TAIL_WINDOW;
+    loop {
+        let start = len.saturating_sub(window);
+        file.seek(SeekFrom::Start(start))?;
+        let mut tail = Vec::new();
+        Read::by_ref(file).take(len - start).read_to_end(&mut tail)?;
+        // Unless the window reaches the start, its first line may be cut
+        let lines = match tail.iter().position(|&b| b == b'\n') {
+            Some(newline) if start > 0 => &tail[newline + 1..],
+            None if start > 0 => &[][..],
+            _ => &tail[..],
+        };
+        let time = lines
+            .rsplit(|&b| b == b'\n')
+            .find_map(|line| serde_json::from_slice::<(f64, String, String)>(line).ok())
+            .map(|(time, _, _)| time);
+        match time {
+            Some(time) => return Ok(time),
+            None if start == 0 => return Ok(0.0),
+            None => window *= 4,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events(path: &Path) -> Vec<(f64, String, String)> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .skip(1)
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn writes_header_and_events() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("s.cast");
+        let mut recorder = Recorder::create(&path, 80, 24, "demo").unwrap();
+        recorder.output(b"\x1b[32mhi\x1b[0m\r\n").unwrap();
+        recorder.input(b"fix it\n").unwrap();
+        recorder.resize(100, 30).unwrap();
+        recorder.status(&SessionStatus::NeedsInput).unwrap();
+        recorder.status(&SessionStatus::NeedsInput).unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let header: serde_json::Value =
+            serde_json::from_str(content.lines().next().unwrap()).unwrap();
+        assert_eq!(header["version"], 2);
+        assert_eq!(header["width"], 80);
+        assert_eq!(header["height"], 24);
+        assert_eq!(header["title"], "demo");
+
+        let codes: Vec<(String, String)> =
+            events(&path).into_iter().map(|(_, c, d)| (c, d)).collect();
+        assert_eq!(
+            codes,
+            vec![
+                ("o".to_string(), "\x1b[32mhi\x1b[0m\r\n".to_string()),
+                ("i".to_string(), "fix it\n".to_string()),
+                ("r".to_string(), "100x30".to_string()),
+                ("m".to_string(), "status: NeedsInput".to_string()),
+            ]
+        );
+        assert_eq!(
+            Marker::parse("status: NeedsInput"),
+            Some(Marker::Status("NeedsInput".to_string()))
+        );
+        assert_eq!(Marker::parse("chapter 1"), None);
+    }
+
+    #[test]
+    fn utf8_split_across_chunks_is_joined() {
+        let mut pending = Vec::new();
+        let bytes = "日本".as_bytes();
+        assert_eq!(take_utf8(&mut pending, &bytes[..4]), "日");
+        assert_eq!(take_utf8(&mut pending, &bytes[4..]), "本");
+        assert!(pending.is_empty());
+        assert_eq!(take_utf8(&mut pending, b"a\xffb"), "a\u{fffd}b");
+    }
+
+    #[test]
+    fn resume_continues_after_last_event() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("s.cast");
+        std::fs::write(
+            &path,
+            "{\"version\":2,\"width\":80,\"height\":24}\n[12.5,\"o\",\"first run\"]\n",
+        )
+        .unwrap();
+
+        let mut recorder = Recorder::resume(&path, 90, 20, "demo").unwrap();
+        recorder.output(b"second run").unwrap();
+
+        let events = events(&path);
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[1].1, "r");
+        assert_eq!(events[1].2, "90x20");
+        assert!(events[2].0 >= 12.5);
+        assert_eq!(events[2].2, "second run");
+    }
+
+    #[test]
+    fn resume_reads_past_a_long_partial_tail() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("s.cast");
+        let mut cast = String::from("{\"version\":2,\"width\":80,\"height\":24}\n");
+        for i in 0..1000 {
+            cast.push_str(&format!("[{}.5,\"o\",\"line {}\"]\n", i, i));
+        }
+        // A killed run's cut-off event, longer than the first read window
+        cast.push_str(&format!("[2000.0,\"o\",\"{}", "x".repeat(TAIL_WINDOW as usize * 2)));
+        std::fs::write(&path, cast).unwrap();
+
+        let mut recorder = Recorder::resume(&path, 80, 24, "demo").unwrap();
+        recorder.output(b"second run").unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let tail: Vec<(f64, String, String)> = content
+            .lines()
+            .rev()
+            .take(2)
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(tail[0].2, "second run");
+        assert!(tail[0].0 >= 999.5 && tail[0].0 < 2000.0);
+        assert_eq!(tail[1].2, "80x24");
+    }
+}
@@ -0,0 +1,394 @@
+use crate::recording::Marker;
+use crate::terminal::TerminalStore;
+use std::path::Path;
+use std::time::Instant;
+use uuid::Uuid;
+
+/// Playback speeds selectable with +/-.
+const SPEEDS: [f64; 7] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0];
+const NORMAL_SPEED: usize = 2;
+/// Longer pauses in a recording are shortened to this many seconds while playing.
+const IDLE_LIMIT: f64 = 2.0;
+/// Jumping to the previous marker skips one this close behind the position,
+/// so repeated presses keep going back.
+const PREV_MARKER_SLACK: f64 = 0.5;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CastEvent {
+    Output(String),
+    Resize(u16, u16),
+    Marker(Marker),
+}
+
+/// A parsed asciicast v2 file (see `recording`). Input events are not needed
+/// for playback and are skipped.
+pub struct Cast {
+    pub title: String,
+    width: u16,
+    height: u16,
+    events: Vec<(f64, CastEvent)>,
+}
+
+impl Cast {
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    fn parse(text: &str) -> std::io::Result<Self> {
+        let mut lines = text.lines();
+        let header: serde_json::Value = lines
+            .next()
+            .and_then(|line| serde_json::from_str(line).ok())
+            .filter(|h: &serde_json::Value| h["version"] == 2)
+            .ok_or_else(|| {
+                std::io::Error::new(std::io::ErrorKind::InvalidData, "asciicast v2 ではありません")
+            })?;
+        let size = |key: &str, default: u16| {
+            header[key]
+                .as_u64()
+                .and_then(|v| u16::try_from(v).ok())
+                .unwrap_or(default)
+        };
+
+        let mut events = Vec::new();
+        // A line cut off by a crash (or an unknown event) is skipped
+        for line in lines {
+            let Ok((time, code, data)) = serde_json::from_str::<(f64, String, String)>(line) else {
+                continue;
+            };
+            let event = match code.as_str() {
+                "o" => CastEvent::Output(data),
+                "r" => {
+                    let Some((cols, rows)) = data.split_once('x') else {
+                        continue;
+                    };
+                    let (Ok(cols), Ok(rows)) = (cols.parse(), rows.parse()) else {
+                        continue;
+                    };
+                    CastEvent::Resize(cols, rows)
+                }
+                "m" => match Marker::parse(&data) {
+                    Some(marker) => CastEvent::Marker(marker),
+                    None => continue,
+                },
+                _ => continue,
+            };
+            // Times before the start can't be played; a negative one would also
+            // make the duration negative
+            if !(time >= 0.0 && time.is_finite()) {
+                continue;
+            }
+            events.push((time, event));
+        }
+        // Playback applies events in order; a hand-edited file may not be sorted
+        events.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+
+        Ok(Self {
+            title: header["title"].as_str().unwrap_or_default().to_string(),
+            width: size("width", crate::terminal::DEFAULT_COLS),
+            height: size("height", crate::terminal::DEFAULT_ROWS),
+            events,
+        })
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.events.last().map_or(0.0, |(time, _)| *time)
+    }
+}
+
+/// Plays a recording into its own terminal, drawn by the log panel like a live session.
+pub struct ReplayPlayer {
+    pub session_id: Uuid,
+    cast: Cast,
+    terminals: TerminalStore,
+    /// Current time in the recording (seconds)
+    position: f64,
+    /// Index of the first event not yet applied
+    next_event: usize,
+    playing: bool,
+    speed: usize,
+    last_tick: Instant,
+    // Last instruction and status passed, for the panel title
+    instruction: Option<String>,
+    status: Option<String>,
+}
+
+impl ReplayPlayer {
+    pub fn new(session_id: Uuid, cast: Cast) -> Self {
+        let mut player = Self {
+            session_id,
+            cast,
+            terminals: TerminalStore::new(),
+            position: 0.0,
+            next_event: 0,
+            playing: true,
+            speed: NORMAL_SPEED,
+            last_tick: Instant::now(),
+            instruction: None,
+            status: None,
+        };
+        player.rewind();
+        player
+    }
+
+    pub fn screen(&self) -> Option<&vt100::Screen> {
+        self.terminals.screen(&self.session_id)
+    }
+
+    pub fn title(&self) -> &str {
+        &self.cast.title
+    }
+
+    pub fn position(&self) -> f64 {
+        self.position
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.cast.duration()
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    pub fn speed(&self) -> f64 {
+        SPEEDS[self.speed]
+    }
+
+    pub fn instruction(&self) -> Option<&str> {
+        self.instruction.as_deref()
+    }
+
+    pub fn status(&self) -> Option<&str> {
+        self.status.as_deref()
+    }
+
+    pub fn toggle_play(&mut self) {
+        if !self.playing && self.position >= self.duration() {
+            self.seek(0.0);
+        }
+        self.playing = !self.playing;
+        self.last_tick = Instant::now();
+    }
+
+    pub fn faster(&mut self) {
+        self.speed = (self.speed + 1).min(SPEEDS.len() - 1);
+    }
+
+    pub fn slower(&mut self) {
+        self.speed = self.speed.saturating_sub(1);
+    }
+
+    /// Advance by the wall-clock time since the last tick (when playing).
+    pub fn tick(&mut self) {
+        let elapsed = self.last_tick.elapsed().as_secs_f64();
+        self.last_tick = Instant::now();
+        if self.playing {
+            self.advance(elapsed * self.speed());
+        }
+    }
+
+    /// Move forward `seconds` of recording time, shortening long idle gaps.
+    fn advance(&mut self, seconds: f64) {
+        if let Some((next, _)) = self.cast.events.get(self.next_event)
+            && next - self.position > IDLE_LIMIT
+        {
+            self.position = next - IDLE_LIMIT;
+        }
+        self.position += seconds;
+        self.apply_until(self.position);
+        if self.next_event >= self.cast.events.len() {
+            self.position = self.duration();
+            self.playing = false;
+        }
+    }
+
+    pub fn seek(&mut self, time: f64) {
+        let time = time.min(self.duration()).max(0.0);
+        if time < self.position {
+            self.rewind();
+        }
+        self.apply_until(time);
+        self.position = time;
+    }
+
+    pub fn seek_by(&mut self, seconds: f64) {
+        self.seek(self.position + seconds);
+    }
+
+    /// Seek to a fraction (0.0..1.0) of the recording.
+    pub fn seek_fraction(&mut self, fraction: f64) {
+        self.seek(self.duration() * fraction);
+    }
+
+    pub fn next_instruction(&mut self) {
+        self.seek_next(|m| matches!(m, Marker::Instruction(_)));
+    }
+
+    pub fn prev_instruction(&mut self) {
+        self.seek_prev(|m| matches!(m, Marker::Instruction(_)));
+    }
+
+    pub fn next_state_change(&mut self) {
+        self.seek_next(|m| matches!(m, Marker::Status(_)));
+    }
+
+    pub fn prev_state_change(&mut self) {
+        self.seek_prev(|m| matches!(m, Marker::Status(_)));
+    }
+
+    fn seek_next(&mut self, wanted: impl Fn(&Marker) -> bool) {
+        // Markers at the current position were already applied
+        let found = self.cast.events[self.next_event..]
+            .iter()
+            .find(|(_, e)| matches!(e, CastEvent::Marker(m) if wanted(m)))
+            .map(|(time, _)| *time);
+        if let Some(time) = found {
+            self.seek(time);
+        }
+    }
+
+    fn seek_prev(&mut self, wanted: impl Fn(&Marker) -> bool) {
+        let before = self.position - PREV_MARKER_SLACK;
+        let found = self
+            .cast
+            .events
+            .iter()
+            .rev()
+            .find(|(time, e)| *time < before && matches!(e, CastEvent::Marker(m) if wanted(m)))
+            .map(|(time, _)| *time);
+        self.seek(found.unwrap_or(0.0));
+    }
+
+    /// Back to an empty screen at the recording's initial size.
+    fn rewind(&mut self) {
+        self.terminals.remove(&self.session_id);
+        self.terminals
+            .resize(self.session_id, self.cast.height, self.cast.width);
+        self.next_event = 0;
+        self.position = 0.0;
+        self.instruction = None;
+        self.status = None;
+    }
+
+    fn apply_until(&mut self, time: f64) {
+        while let Some((at, event)) = self.cast.events.get(self.next_event) {
+            if *at > time {
+                break;
+            }
+            match event {
+                CastEvent::Output(data) => self.terminals.process(self.session_id, data.as_bytes()),
+                CastEvent::Resize(cols, rows) => self.terminals.resize(self.session_id, *rows, *cols),
+                CastEvent::Marker(Marker::Instruction(text)) => {
+                    self.instruction = Some(text.clone());
+                }
+                CastEvent::Marker(Marker::Status(status)) => self.status = Some(status.clone()),
+            }
+            self.next_event += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAST: &str = concat!(
+        "{\"version\":2,\"width\":20,\"height\":4,\"title\":\"demo\"}\n",
+        "[0.5,\"m\",\"instruction: first\"]\n",
+        "[0.5,\"i\",\"first\\n\"]\n",
+        "[1.0,\"o\",\"one\"]\n",
+        "[2.0,\"m\",\"status: NeedsInput\"]\n",
+        "[30.0,\"m\",\"instruction: second\"]\n",
+        "[31.0,\"o\",\"\\r\\ntwo\"]\n",
+        "[31.5,\"r\",\"10x3\"]\n",
+        "[32.0,\"m\",\"status: Done\"]\n",
+        "[32.5,\"o\",\"cut",
+    );
+
+    fn player() -> ReplayPlayer {
+        ReplayPlayer::new(Uuid::new_v4(), Cast::parse(CAST).unwrap())
+    }
+
+    fn contents(player: &ReplayPlayer) -> String {
+        player.screen().unwrap().contents()
+    }
+
+    #[test]
+    fn parses_events_and_skips_input() {
+        let cast = Cast::parse(CAST).unwrap();
+        assert_eq!(cast.title, "demo");
+        assert_eq!((cast.width, cast.height), (20, 4));
+        assert_eq!(cast.events.len(), 7);
+        assert_eq!(cast.duration(), 32.0);
+        assert!(Cast::parse("not a cast").is_err());
+    }
+
+    #[test]
+    fn negative_and_unordered_times_are_tamed() {
+        let cast = Cast::parse(concat!(
+            "{\"version\":2,\"width\":20,\"height\":4}\n",
+            "[-3.0,\"o\",\"before\"]\n",
+            "[2.0,\"o\",\" two\"]\n",
+            "[1.0,\"o\",\"one\"]\n",
+        ))
+        .unwrap();
+        assert_eq!(cast.duration(), 2.0);
+        let mut player = ReplayPlayer::new(Uuid::new_v4(), cast);
+        player.seek(5.0);
+        assert_eq!(contents(&player), "one two");
+
+        let only_negative = "{\"version\":2}\n[-1.0,\"o\",\"x\"]\n";
+        let mut player = ReplayPlayer::new(Uuid::new_v4(), Cast::parse(only_negative).unwrap());
+        player.seek(-2.0);
+        player.seek_by(3.0);
+        assert_eq!(player.position(), 0.0);
+    }
+
+    #[test]
+    fn seeking_rebuilds_the_screen() {
+        let mut player = player();
+        player.seek(31.6);
+        assert_eq!(contents(&player), "one\ntwo");
+        assert_eq!(player.screen().unwrap().size(), (3, 10));
+        assert_eq!(player.instruction(), Some("second"));
+
+        player.seek(1.5);
+        assert_eq!(contents(&player), "one");
+        assert_eq!(player.screen().unwrap().size(), (4, 20));
+        assert_eq!(player.instruction(), Some("first"));
+        assert_eq!(player.status(), None);
+    }
+
+    #[test]
+    fn jumps_between_markers() {
+        let mut player = player();
+        player.next_state_change();
+        assert_eq!(player.position(), 2.0);
+        assert_eq!(player.status(), Some("NeedsInput"));
+        player.next_instruction();
+        assert_eq!(player.position(), 30.0);
+        player.next_state_change();
+        assert_eq!(player.status(), Some("Done"));
+
+        player.prev_instruction();
+        assert_eq!(player.position(), 30.0);
+        player.prev_instruction();
+        assert_eq!(player.position(), 0.5);
+    }
+
+    #[test]
+    fn playback_shortens_idle_gaps_and_stops_at_end() {
+        let mut player = player();
+        player.seek(2.0);
+        // 28s of silence before the next event
+        player.advance(0.5);
+        assert!(player.position() > 27.0);
+        player.advance(10.0);
+        assert_eq!(player.position(), 32.0);
+        assert!(!player.is_playing());
+        player.toggle_play();
+        assert!(player.is_playing());
+        assert_eq!(player.position(), 0.0);
+    }
+}
@@ -0,0 +1,112 @@
+use std::ops::Range;
+
+/// Scroll position of one log view over a growing list of lines.
+///
+/// Lines are addressed by absolute number: the view holds lines `first..end`,
+/// where `first` grows as old lines are dropped. Following keeps the newest
+/// lines in view; otherwise the view stays on the same lines as output arrives.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Scrollback {
+    /// First visible line while paused; None follows the tail
+    top: Option<usize>,
+}
+
+impl Scrollback {
+    pub fn is_following(&self) -> bool {
+        self.top.is_none()
+    }
+
+    /// Absolute line numbers to show in `height` rows.
+    pub fn visible(&self, first: usize, end: usize, height: usize) -> Range<usize> {
+        let tail_top = end.saturating_sub(height).max(first);
+        let top = self.top.map_or(tail_top, |top| top.clamp(first, tail_top));
+        top..(top + height).min(end)
+    }
+
+    /// Scroll by `delta` lines (negative is up), pausing the view.
+    pub fn scroll_by(&mut self, delta: isize, first: usize, end: usize, height: usize) {
+        let tail_top = end.saturating_sub(height).max(first);
+        let top = self.visible(first, end, height).start;
+        self.top = Some(top.saturating_add_signed(delta).clamp(first, tail_top));
+    }
+
+    /// Pause with `line` in view, centering it if it is off screen.
+    pub fn reveal(&mut self, line: usize, first: usize, end: usize, height: usize) {
+        let visible = self.visible(first, end, height);
+        let tail_top = end.saturating_sub(height).max(first);
+        let top = if visible.contains(&line) {
+            visible.start
+        } else {
+            line.saturating_sub(height / 2).clamp(first, tail_top)
+        };
+        self.top = Some(top);
+    }
+
+    pub fn scroll_to_top(&mut self, first: usize) {
+        self.top = Some(first);
+    }
+
+    pub fn follow(&mut self) {
+        self.top = None;
+    }
+
+    /// Follow the tail, or pause where the view currently is.
+    pub fn toggle_follow(&mut self, first: usize, end: usize, height: usize) {
+        self.top = match self.top {
+            Some(_) => None,
+            None => Some(self.visible(first, end, height).start),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follows_tail_until_scrolled() {
+        let mut scroll = Scrollback::default();
+        assert_eq!(scroll.visible(0, 100, 10), 90..100);
+        assert_eq!(scroll.visible(0, 5, 10), 0..5);
+
+        scroll.scroll_by(-15, 0, 100, 10);
+        assert!(!scroll.is_following());
+        assert_eq!(scroll.visible(0, 100, 10), 75..85);
+        // New output doesn't move a paused view
+        assert_eq!(scroll.visible(0, 150, 10), 75..85);
+
+        scroll.follow();
+        assert_eq!(scroll.visible(0, 150, 10), 140..150);
+    }
+
+    #[test]
+    fn clamps_to_available_lines() {
+        let mut scroll = Scrollback::default();
+        scroll.scroll_by(-1000, 20, 100, 10);
+        assert_eq!(scroll.visible(20, 100, 10), 20..30);
+        scroll.scroll_by(1000, 20, 100, 10);
+        assert_eq!(scroll.visible(20, 100, 10), 90..100);
+        // Lines dropped from the front push the view down
+        scroll.scroll_to_top(20);
+        assert_eq!(scroll.visible(50, 120, 10), 50..60);
+    }
+
+    #[test]
+    fn reveal_centers_lines_out_of_view() {
+        let mut scroll = Scrollback::default();
+        scroll.reveal(95, 0, 100, 10);
+        assert!(!scroll.is_following());
+        assert_eq!(scroll.visible(0, 100, 10), 90..100);
+        scroll.reveal(40, 0, 100, 10);
+        assert_eq!(scroll.visible(0, 100, 10), 35..45);
+    }
+
+    #[test]
+    fn toggle_pauses_at_current_tail() {
+        let mut scroll = Scrollback::default();
+        scroll.toggle_follow(0, 100, 10);
+        assert_eq!(scroll.visible(0, 200, 10), 90..100);
+        scroll.toggle_follow(0, 200, 10);
+        assert!(scroll.is_following());
+    }
+}
@@ -0,0 +1,148 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use std::ops::{Range, RangeInclusive};
+use unicode_width::UnicodeWidthChar;
+
+/// Whole lines, or a rectangle of display columns across lines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SelectionKind {
+    Line,
+    Block,
+}
+
+/// A keyboard selection over numbered lines: the anchor stays where the
+/// selection started and the cursor moves. Columns are display columns, so
+/// blocks stay rectangular over wide (CJK) characters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Selection {
+    pub kind: SelectionKind,
+    anchor: (usize, usize),
+    cursor: (usize, usize),
+}
+
+impl Selection {
+    pub fn new(kind: SelectionKind, line: usize, col: usize) -> Self {
+        Self {
+            kind,
+            anchor: (line, col),
+            cursor: (line, col),
+        }
+    }
+
+    /// Line the cursor is on.
+    pub fn cursor_line(&self) -> usize {
+        self.cursor.0
+    }
+
+    /// Move the cursor, keeping it on `lines` and in the first `max_col` columns.
+    pub fn move_by(&mut self, lines: isize, cols: isize, within: &Range<usize>, max_col: usize) {
+        let last = within.end.saturating_sub(1).max(within.start);
+        self.cursor.0 = self.cursor.0.saturating_add_signed(lines).clamp(within.start, last);
+        self.cursor.1 = self.cursor.1.saturating_add_signed(cols).min(max_col.saturating_sub(1));
+    }
+
+    pub fn lines(&self) -> RangeInclusive<usize> {
+        self.anchor.0.min(self.cursor.0)..=self.anchor.0.max(self.cursor.0)
+    }
+
+    fn columns(&self) -> Range<usize> {
+        self.anchor.1.min(self.cursor.1)..self.anchor.1.max(self.cursor.1) + 1
+    }
+
+    /// Bytes of line `n` inside the selection (None when it isn't selected).
+    pub fn byte_range(&self, n: usize, line: &str) -> Option<Range<usize>> {
+        if !self.lines().contains(&n) {
+            return None;
+        }
+        match self.kind {
+            SelectionKind::Line => Some(0..line.len()),
+            SelectionKind::Block => Some(column_bytes(line, self.columns())),
+        }
+    }
+
+    /// The selected text, one line per selected line.
+    pub fn text(&self, mut line: impl FnMut(usize) -> String) -> String {
+        self.lines()
+            .map(|n| {
+                let text = line(n);
+                match self.byte_range(n, &text) {
+                    Some(range) => text[range].to_string(),
+                    None => String::new(),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Bytes of the characters of `line` that overlap display columns `cols`.
+fn column_bytes(line: &str, cols: Range<usize>) -> Range<usize> {
+    let mut col = 0;
+    let mut start = None;
+    let mut end = line.len();
+    for (i, c) in line.char_indices() {
+        let width = c.width().unwrap_or(0);
+        if col >= cols.end {
+            end = i;
+            break;
+        }
+        if start.is_none() && col + width.max(1) > cols.start {
+            start = Some(i);
+        }
+        col += width;
+    }
+    let start = start.unwrap_or(line.len());
+    start..end.max(start)
+}
+
+/// OSC 52 sequence that puts `text` on the system clipboard. The terminal does
+/// the copying, so it works over SSH without a clipboard daemon; inside tmux
+/// the sequence is passed through to the outer terminal.
+pub fn osc52(text: &str, tmux: bool) -> String {
+    let sequence = format!("\x1b]52;c;{}\x07", STANDARD.encode(text));
+    if tmux {
+        format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"))
+    } else {
+        sequence
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINES: [&str; 4] = ["alpha beta", "日本語のログ", "", "gamma"];
+
+    fn text(selection: &Selection) -> String {
+        selection.text(|n| LINES[n].to_string())
+    }
+
+    #[test]
+    fn line_selection_copies_whole_lines() {
+        let mut s = Selection::new(SelectionKind::Line, 2, 0);
+        s.move_by(-2, 0, &(0..4), 80);
+        assert_eq!(s.lines(), 0..=2);
+        assert_eq!(text(&s), "alpha beta\n日本語のログ\n");
+        s.move_by(10, 0, &(0..4), 80);
+        assert_eq!(s.lines(), 2..=3);
+    }
+
+    #[test]
+    fn block_selection_uses_display_columns() {
+        let mut s = Selection::new(SelectionKind::Block, 0, 2);
+        s.move_by(1, 3, &(0..4), 80);
+        // Columns 2..6: "pha " and the two wide characters covering them
+        assert_eq!(text(&s), "pha \n本語");
+        assert_eq!(s.byte_range(1, LINES[1]), Some(3..9));
+        assert_eq!(s.byte_range(3, LINES[3]), None);
+
+        let s = Selection::new(SelectionKind::Block, 3, 9);
+        assert_eq!(text(&s), "");
+    }
+
+    #[test]
+    fn osc52_encodes_and_wraps_for_tmux() {
+        assert_eq!(osc52("hi", false), "\x1b]52;c;aGk=\x07");
+        assert_eq!(osc52("hi", true), "\x1bPtmux;\x1b\x1b]52;c;aGk=\x07\x1b\\");
+    }
+}
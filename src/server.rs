@@ -0,0 +1,263 @@
+use crate::config::AppConfig;
+use crate::engine::SessionEngine;
+use crate::protocol::{self, Request};
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::Shutdown;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+
+/// Longest wait for a client request between engine ticks.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+/// A freshly started server exits if no client attaches within this time.
+const FIRST_CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Events queued for a client that isn't reading; once full it is detached.
+const CLIENT_QUEUE: usize = 4096;
+
+enum Incoming {
+    Request(Request),
+    Closed(usize),
+}
+
+/// An attached client. Its own thread writes the queued events, so a client
+/// that stops reading (suspended, on a slow link) never holds up the engine.
+struct Client {
+    queue: mpsc::SyncSender<Arc<[u8]>>,
+    stream: UnixStream,
+}
+
+impl Client {
+    fn new(stream: UnixStream) -> std::io::Result<Self> {
+        let (queue, lines) = mpsc::sync_channel::<Arc<[u8]>>(CLIENT_QUEUE);
+        let mut writer = stream.try_clone()?;
+        std::thread::spawn(move || {
+            for line in lines {
+                if writer.write_all(&line).is_err() {
+                    // Never go on after a partial line; the reader sees EOF
+                    let _ = writer.shutdown(Shutdown::Both);
+                    return;
+                }
+            }
+        });
+        Ok(Self { queue, stream })
+    }
+
+    /// Queue a line without blocking. False when the client is gone or has
+    /// fallen too far behind, and should be detached.
+    fn send(&self, line: &Arc<[u8]>) -> bool {
+        self.queue.try_send(Arc::clone(line)).is_ok()
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        // Unblocks a writer stuck on a full socket and ends the reader thread
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+/// Run the session daemon in the foreground (`deck server`).
+///
+/// The engine lives here so agents survive the TUI closing; clients attach
+/// over `config.socket_path`. Exits once no client is attached and no
+/// session is running.
+pub fn run(config: AppConfig) -> std::io::Result<()> {
+    let socket_path = config.socket_path.clone();
+    if UnixStream::connect(&socket_path).is_ok() {
+        return Err(std::io::Error::new(
+            ErrorKind::AddrInUse,
+            "deck server は既に起動しています",
+        ));
+    }
+    // Left behind by a server that did not exit cleanly
+    let _ = std::fs::remove_file(&socket_path);
+    let listener = UnixListener::bind(&socket_path)?;
+    std::fs::set_permissions(&socket_path, std::fs::Permissions::from_mode(0o600))?;
+    listener.set_nonblocking(true)?;
+
+    let result = serve(&listener, SessionEngine::new(config));
+    let _ = std::fs::remove_file(&socket_path);
+    result
+}
+
+fn serve(listener: &UnixListener, mut engine: SessionEngine) -> std::io::Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let mut clients: HashMap<usize, Client> = HashMap::new();
+    let mut next_client = 0;
+    let started = Instant::now();
+    let mut had_client = false;
+
+    loop {
+        // Attach new clients with a snapshot of the current state
+        loop {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    let id = next_client;
+                    next_client += 1;
+                    if let Some(client) = attach(id, stream, &engine, tx.clone()) {
+                        clients.insert(id, client);
+                        had_client = true;
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        // Apply client requests, waiting briefly for the first one
+        let mut incoming: Vec<Incoming> = rx.recv_timeout(POLL_INTERVAL).into_iter().collect();
+        incoming.extend(rx.try_iter());
+        for msg in incoming {
+            match msg {
+                Incoming::Request(request) => engine.handle(request),
+                Incoming::Closed(id) => {
+                    clients.remove(&id);
+                }
+            }
+        }
+
+        engine.tick();
+        for event in engine.take_events() {
+            let line: Arc<[u8]> = protocol::encode_line(&event).into();
+            clients.retain(|_, client| client.send(&line));
+        }
+
+        let idle = clients.is_empty() && !engine.is_busy();
+        if idle && (had_client || started.elapsed() > FIRST_CLIENT_TIMEOUT) {
+            return Ok(());
+        }
+    }
+}
+
+/// Queue the snapshot and start forwarding the client's requests.
+/// Returns the client to broadcast events to, or None if it is already gone.
+fn attach(
+    id: usize,
+    stream: UnixStream,
+    engine: &SessionEngine,
+    tx: mpsc::Sender<Incoming>,
+) -> Option<Client> {
+    stream.set_nonblocking(false).ok()?;
+    let client = Client::new(stream.try_clone().ok()?).ok()?;
+    if !client.send(&protocol::encode_line(&engine.snapshot()).into()) {
+        return None;
+    }
+
+    std::thread::spawn(move || {
+        for line in BufReader::new(stream).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            // Unknown requests (e.g. from a newer client) are ignored
+            if let Ok(request) = serde_json::from_str::<Request>(&line)
+                && tx.send(Incoming::Request(request)).is_err()
+            {
+                return;
+            }
+        }
+        let _ = tx.send(Incoming::Closed(id));
+    });
+    Some(client)
+}
+
+/// Start `deck server` in the background, detached from the current terminal
+/// (its own session) so it survives the TUI closing. Its stderr goes to `deck.log`.
+pub fn spawn_detached(config: &AppConfig) -> std::io::Result<()> {
+    let log = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(config.socket_path.with_extension("log"))?;
+    let mut command = Command::new(std::env::current_exe()?);
+    command
+        .arg("server")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(log);
+    // SAFETY: setsid(2) is async-signal-safe and touches no memory
+    unsafe {
+        command.pre_exec(|| {
+            if libc::setsid() == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    command.spawn()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::RemoteEngine;
+    use crate::protocol::Event;
+    use crate::session::Session;
+
+    fn wait_for(remote: &mut RemoteEngine, done: impl Fn(&Event) -> bool) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            if remote.poll().iter().any(&done) {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    #[test]
+    fn clients_share_one_engine() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let config = AppConfig::in_dir(tmp.path());
+        let server = {
+            let config = config.clone();
+            std::thread::spawn(move || run(config))
+        };
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut first = loop {
+            match RemoteEngine::connect(&config.socket_path) {
+                Ok(remote) => break remote,
+                Err(_) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(20)),
+                Err(e) => panic!("server did not start: {}", e),
+            }
+        };
+        let mut second = RemoteEngine::connect(&config.socket_path).unwrap();
+        assert!(wait_for(&mut second, |e| matches!(e, Event::Snapshot { .. })));
+
+        // A session created by one client shows up in the other
+        let session = Session::new("shared".to_string(), tmp.path().to_path_buf(), tmp.path());
+        first.send(&Request::CreateSession {
+            session: Box::new(session),
+        });
+        assert!(wait_for(&mut second, |e| {
+            matches!(e, Event::Sessions { sessions } if sessions[0].name == "shared")
+        }));
+
+        // The server exits once every client has detached
+        drop(first);
+        drop(second);
+        server.join().unwrap().unwrap();
+        assert!(!config.socket_path.exists());
+    }
+
+    #[test]
+    fn client_that_stops_reading_is_dropped_without_blocking() {
+        let (stream, peer) = UnixStream::pair().unwrap();
+        let client = Client::new(stream).unwrap();
+        let line: Arc<[u8]> = vec![b'x'; 4096].into();
+        let started = Instant::now();
+        // The peer never reads: the socket buffer fills, then the queue
+        let sent = (0..CLIENT_QUEUE * 4).take_while(|_| client.send(&line)).count();
+        assert!(sent < CLIENT_QUEUE * 4);
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        // Dropping it closes the connection, even mid-write
+        drop(client);
+        let mut rest = Vec::new();
+        let _ = std::io::Read::read_to_end(&mut &peer, &mut rest);
+    }
+}
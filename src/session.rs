@@ -1,3 +1,6 @@
+use crate::launch::LaunchSpec;
+use crate::needs_input::NeedsInputReason;
+use crate::proc_tree::ProcessStart;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
@@ -24,15 +27,37 @@ impl SessionStatus {
     }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct Session {
     pub id: Uuid,
     pub name: String,
     pub root_path: PathBuf,
+    /// Agent program, arguments and environment (defaults to plain `claude`)
+    #[serde(default)]
+    pub launch: LaunchSpec,
     pub status: SessionStatus,
-    #[serde(skip)]
+    /// Agent pid (= its process group id); persisted so processes left behind
+    /// by a previous run can be found at startup
+    #[serde(default)]
     pub pty_pid: Option<u32>,
+    /// When the agent started, so a group that later reused `pty_pid` isn't
+    /// taken for it
+    #[serde(default)]
+    pub pty_started: Option<ProcessStart>,
     pub instruction: Option<String>,
+    /// Needs-input detection profile; None uses the one for its program
+    #[serde(default)]
+    pub needs_input_profile: Option<String>,
+    /// What put the session in NeedsInput (cleared when it leaves it)
+    #[serde(default)]
+    pub needs_input_reason: Option<NeedsInputReason>,
+    /// Process a Running session that has gone quiet is busy with (it is not
+    /// waiting for the user); cleared by output or a status change
+    #[serde(default)]
+    pub busy: Option<String>,
+    /// Claude conversation id; the next spawn passes `--resume <id>`
+    #[serde(default)]
+    pub conversation_id: Option<String>,
     pub log_path: PathBuf,
     pub exit_code: Option<i32>,
     pub created_at: DateTime<Utc>,
@@ -47,9 +72,15 @@ impl Session {
             id,
             name,
             root_path,
+            launch: LaunchSpec::default(),
             status: SessionStatus::Queued,
             pty_pid: None,
+            pty_started: None,
             instruction: None,
+            needs_input_profile: None,
+            needs_input_reason: None,
+            busy: None,
+            conversation_id: None,
             log_path,
             exit_code: None,
             created_at: Utc::now(),
@@ -76,6 +107,10 @@ impl Session {
 
     pub fn transition_to(&mut self, target: SessionStatus) -> Result<(), &'static str> {
         self.can_transition_to(&target)?;
+        if target != SessionStatus::NeedsInput {
+            self.needs_input_reason = None;
+        }
+        self.busy = None;
         self.status = target;
         self.updated_at = Utc::now();
         Ok(())
@@ -169,12 +204,40 @@ mod tests {
 
     #[test]
     fn serialization_roundtrip() {
-        let s = make_session();
+        let mut s = make_session();
+        s.pty_pid = Some(4242);
         let json = serde_json::to_string(&s).unwrap();
         let s2: Session = serde_json::from_str(&json).unwrap();
         assert_eq!(s.id, s2.id);
         assert_eq!(s.name, s2.name);
         assert_eq!(s.status, s2.status);
-        assert!(s2.pty_pid.is_none()); // skipped in serde
+        assert_eq!(s2.pty_pid, Some(4242));
+    }
+
+    #[test]
+    fn launch_spec_roundtrip() {
+        let mut s = make_session();
+        s.launch = LaunchSpec::parse("claude --model opus", "FOO=1").unwrap();
+        let json = serde_json::to_string(&s).unwrap();
+        let s2: Session = serde_json::from_str(&json).unwrap();
+        assert_eq!(s2.launch, s.launch);
+    }
+
+    #[test]
+    fn conversation_id_roundtrip() {
+        let mut s = make_session();
+        s.conversation_id = Some("0b6f3c52-8d1e-4a8e-9a53-2f1d3c4b5a69".to_string());
+        let json = serde_json::to_string(&s).unwrap();
+        let s2: Session = serde_json::from_str(&json).unwrap();
+        assert_eq!(s2.conversation_id, s.conversation_id);
+    }
+
+    #[test]
+    fn missing_launch_defaults_to_claude() {
+        let s = make_session();
+        let mut value = serde_json::to_value(&s).unwrap();
+        value.as_object_mut().unwrap().remove("launch");
+        let s2: Session = serde_json::from_value(value).unwrap();
+        assert_eq!(s2.launch, LaunchSpec::default());
     }
 }
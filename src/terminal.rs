@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+use uuid::Uuid;
+
+pub const DEFAULT_ROWS: u16 = 24;
+pub const DEFAULT_COLS: u16 = 80;
+
+/// Rows kept above the visible screen by each session's terminal.
+const SCROLLBACK_ROWS: usize = 1_000;
+
+/// Per-session VT100/xterm state machines fed with raw PTY output.
+///
+/// Unlike `LogStore`, which keeps plain-text lines, this holds the cell grid
+/// (attributes, cursor, alternate screen) that the session's TUI draws into.
+pub struct TerminalStore {
+    parsers: HashMap<Uuid, vt100::Parser>,
+}
+
+impl TerminalStore {
+    pub fn new() -> Self {
+        Self {
+            parsers: HashMap::new(),
+        }
+    }
+
+    /// Feed raw PTY output into the session's terminal, creating it on first use.
+    pub fn process(&mut self, session_id: Uuid, data: &[u8]) {
+        self.parsers
+            .entry(session_id)
+            .or_insert_with(|| vt100::Parser::new(DEFAULT_ROWS, DEFAULT_COLS, SCROLLBACK_ROWS))
+            .process(data);
+    }
+
+    /// Resize the session's terminal, creating it if output hasn't arrived yet.
+    pub fn resize(&mut self, session_id: Uuid, rows: u16, cols: u16) {
+        self.parsers
+            .entry(session_id)
+            .or_insert_with(|| vt100::Parser::new(rows, cols, SCROLLBACK_ROWS))
+            .screen_mut()
+            .set_size(rows, cols);
+    }
+
+    /// Size and escape sequences reproducing the session's screen and input modes.
+    pub fn snapshot(&self, session_id: &Uuid) -> Option<(u16, u16, Vec<u8>)> {
+        let screen = self.parsers.get(session_id)?.screen();
+        let (rows, cols) = screen.size();
+        let mut state = Vec::new();
+        if screen.alternate_screen() {
+            state.extend_from_slice(b"\x1b[?1049h");
+        }
+        state.extend(screen.state_formatted());
+        Some((rows, cols, state))
+    }
+
+    /// Rebuild a session's terminal from a `snapshot`.
+    pub fn restore(&mut self, session_id: Uuid, rows: u16, cols: u16, state: &[u8]) {
+        let mut parser = vt100::Parser::new(rows, cols, SCROLLBACK_ROWS);
+        parser.process(state);
+        self.parsers.insert(session_id, parser);
+    }
+
+    pub fn screen(&self, session_id: &Uuid) -> Option<&vt100::Screen> {
+        self.parsers.get(session_id).map(|p| p.screen())
+    }
+
+    /// The last `count` lines of the session's screen as plain text, down to
+    /// the last row with anything on it. Rows the terminal wrapped are joined
+    /// back into the line the program printed.
+    pub fn bottom_lines(&self, session_id: &Uuid, count: usize) -> Vec<String> {
+        let Some(screen) = self.screen(session_id) else {
+            return Vec::new();
+        };
+        let mut lines: Vec<String> = Vec::new();
+        let mut continued = false;
+        for (row, text) in screen.rows(0, screen.size().1).enumerate() {
+            match lines.last_mut() {
+                Some(line) if continued => line.push_str(&text),
+                _ => lines.push(text),
+            }
+            continued = screen.row_wrapped(row as u16);
+        }
+        while lines.last().is_some_and(|l| l.trim().is_empty()) {
+            lines.pop();
+        }
+        lines.split_off(lines.len().saturating_sub(count))
+    }
+
+    pub fn remove(&mut self, session_id: &Uuid) {
+        self.parsers.remove(session_id);
+    }
+
+    /// Keep only the sessions for which `keep` returns true.
+    pub fn retain(&mut self, keep: impl Fn(&Uuid) -> bool) {
+        self.parsers.retain(|id, _| keep(id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_session_has_no_screen() {
+        let store = TerminalStore::new();
+        assert!(store.screen(&Uuid::new_v4()).is_none());
+    }
+
+    #[test]
+    fn keeps_colors() {
+        let mut store = TerminalStore::new();
+        let id = Uuid::new_v4();
+        store.process(id, b"\x1b[31mred\x1b[0m");
+        let cell = store.screen(&id).unwrap().cell(0, 0).unwrap();
+        assert_eq!(cell.contents(), "r");
+        assert_eq!(cell.fgcolor(), vt100::Color::Idx(1));
+    }
+
+    #[test]
+    fn carriage_return_overwrites_cells() {
+        let mut store = TerminalStore::new();
+        let id = Uuid::new_v4();
+        store.process(id, b"| working\r/ working");
+        let screen = store.screen(&id).unwrap();
+        assert_eq!(screen.contents(), "/ working");
+    }
+
+    #[test]
+    fn resize_before_output() {
+        let mut store = TerminalStore::new();
+        let id = Uuid::new_v4();
+        store.resize(id, 40, 120);
+        store.process(id, b"hello");
+        assert_eq!(store.screen(&id).unwrap().size(), (40, 120));
+        store.resize(id, 10, 30);
+        assert_eq!(store.screen(&id).unwrap().size(), (10, 30));
+    }
+
+    #[test]
+    fn snapshot_restores_screen_and_modes() {
+        let mut store = TerminalStore::new();
+        let id = Uuid::new_v4();
+        store.resize(id, 10, 40);
+        store.process(id, b"\x1b[?2004h\x1b[1mbold\x1b[0m\r\nnext");
+        let (rows, cols, state) = store.snapshot(&id).unwrap();
+
+        let mut copy = TerminalStore::new();
+        copy.restore(id, rows, cols, &state);
+        let screen = copy.screen(&id).unwrap();
+        assert_eq!(screen.size(), (10, 40));
+        assert_eq!(screen.contents(), "bold\nnext");
+        assert!(screen.cell(0, 0).unwrap().bold());
+        assert!(screen.bracketed_paste());
+        assert_eq!(screen.cursor_position(), (1, 4));
+    }
+
+    #[test]
+    fn bottom_lines_join_wrapped_rows() {
+        let mut store = TerminalStore::new();
+        let id = Uuid::new_v4();
+        store.resize(id, 6, 10);
+        store.process(id, b"a\r\nb\r\n\x1b[1mDo you want to go?\x1b[0m\r\n\r\n");
+        assert_eq!(store.bottom_lines(&id, 2), ["b", "Do you want to go?"]);
+        assert_eq!(store.bottom_lines(&id, 9).len(), 3);
+    }
+
+    #[test]
+    fn tracks_alternate_screen() {
+        let mut store = TerminalStore::new();
+        let id = Uuid::new_v4();
+        store.process(id, b"shell\x1b[?1049h\x1b[Hfull screen app");
+        assert!(store.screen(&id).unwrap().alternate_screen());
+        store.process(id, b"\x1b[?1049l");
+        assert_eq!(store.screen(&id).unwrap().contents(), "shell");
+    }
+}
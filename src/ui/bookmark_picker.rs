@@ -0,0 +1,44 @@
+use crate::app::AppState;
+use crate::ui::theme;
+use ftui_core::geometry::Rect;
+use ftui_render::frame::Frame;
+use ftui_style::Style;
+use ftui_widgets::list::{List, ListItem, ListState};
+use ftui_widgets::StatefulWidget;
+
+/// The active session's bookmarks, oldest first. Those in rotated log
+/// segments are listed but can't be jumped to.
+pub fn render(state: &AppState, frame: &mut Frame, area: Rect) {
+    let Some(bookmarks) = state
+        .sessions
+        .get(state.active_session)
+        .and_then(|s| state.bookmarks.get(&s.id))
+    else {
+        return;
+    };
+    let current = bookmarks.segment();
+    let items: Vec<ListItem> = bookmarks
+        .items
+        .iter()
+        .map(|b| {
+            let time = b
+                .created_at
+                .with_timezone(&chrono::Local)
+                .format("%m-%d %H:%M");
+            let note = if b.note.is_empty() { "(メモなし)" } else { b.note.as_str() };
+            let rotated = if b.segment == current { "" } else { " [ローテーション済み]" };
+            ListItem::new(format!("{} {}{} │ {}", time, note, rotated, b.text.trim()))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(theme::panel_block("Bookmarks (Enter:移動 d:削除 Esc:閉じる)", true))
+        .highlight_style(
+            Style::new()
+                .fg(theme::HIGHLIGHT_FG)
+                .bg(theme::HIGHLIGHT_BG),
+        );
+    let mut list_state = ListState::default();
+    list_state.select(state.bookmark_pick);
+    list.render(area, frame, &mut list_state);
+}
@@ -1,7 +1,9 @@
-use crate::app::AppState;
+use crate::app::{AppState, Panel};
 use crate::ui::theme;
 use ftui_core::geometry::Rect;
 use ftui_render::frame::Frame;
+use ftui_style::Style;
+use ftui_text::{Line, Span, Text};
 use ftui_widgets::paragraph::Paragraph;
 use ftui_widgets::Widget;
 
@@ -15,9 +17,35 @@ pub fn render(state: &AppState, frame: &mut Frame, area: Rect, focused: bool) {
     let visible_height = area.height.saturating_sub(2) as usize;
     let content = state.file_preview.visible_content(visible_height);
 
-    let mut paragraph = Paragraph::new(content).block(theme::panel_block(title, focused));
-    if state.file_preview.path.is_none() {
-        paragraph = paragraph.style(theme::placeholder_style());
-    }
-    paragraph.render(area, frame);
+    // Lines under the selection are drawn highlighted
+    let selection = match state.visual {
+        Some((Panel::FilePreview, selection)) => selection,
+        _ => {
+            let mut paragraph =
+                Paragraph::new(content).block(theme::panel_block(title, focused));
+            if state.file_preview.path.is_none() {
+                paragraph = paragraph.style(theme::placeholder_style());
+            }
+            paragraph.render(area, frame);
+            return;
+        }
+    };
+    let style = Style::new().fg(theme::HIGHLIGHT_FG).bg(theme::SELECTION_BG);
+    let lines = content.split('\n').enumerate().map(|(i, line)| {
+        match selection.byte_range(state.file_preview.scroll + i, line) {
+            Some(range) if range.is_empty() => Line::from_spans([
+                Span::raw(&line[..range.start]),
+                Span::styled(" ", style),
+            ]),
+            Some(range) => Line::from_spans([
+                Span::raw(&line[..range.start]),
+                Span::styled(&line[range.clone()], style),
+                Span::raw(&line[range.end..]),
+            ]),
+            None => Line::raw(line),
+        }
+    });
+    Paragraph::new(Text::from_lines(lines))
+        .block(theme::panel_block(title, focused))
+        .render(area, frame);
 }
@@ -2,8 +2,14 @@ use crate::app::AppState;
 use crate::ui::theme;
 use ftui_core::geometry::Rect;
 use ftui_render::frame::Frame;
+use ftui_style::Style;
+use ftui_text::{Line, Span, Text};
 use ftui_widgets::paragraph::Paragraph;
 use ftui_widgets::Widget;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Widest a choice's label gets in the quick-reply strip.
+const LABEL_WIDTH: usize = 16;
 
 pub fn render(state: &AppState, frame: &mut Frame, area: Rect, focused: bool) {
     let prompt = if let Some(session) = state.sessions.get(state.active_session) {
@@ -14,4 +20,44 @@ pub fn render(state: &AppState, frame: &mut Frame, area: Rect, focused: bool) {
 
     let paragraph = Paragraph::new(prompt).block(theme::panel_block("Input", focused));
     paragraph.render(area, frame);
+
+    // Quick replies for a session waiting on input, right-aligned on the input line
+    let Some((session, choices)) = state.quick_replies() else {
+        return;
+    };
+    let key_style = Style::new().fg(theme::STATUS_NEEDS_INPUT).bold();
+    let mut spans = vec![Span::styled(
+        format!(" ◆ {} ", session.name),
+        Style::new().fg(theme::HINT_FG),
+    )];
+    for (i, choice) in choices.iter().take(9).enumerate() {
+        spans.push(Span::styled(format!(" Alt+{}", i + 1), key_style));
+        spans.push(Span::raw(format!(":{}", truncate(&choice.label))));
+    }
+    spans.push(Span::raw(" "));
+    let width: usize = spans.iter().map(|s| s.content.width()).sum();
+    let inner_width = area.width.saturating_sub(2);
+    let width = (width as u16).min(inner_width);
+    if width == 0 || area.height < 3 {
+        return;
+    }
+    let strip = Rect::new(area.x + 1 + inner_width - width, area.y + 1, width, 1);
+    Paragraph::new(Text::from_lines([Line::from_spans(spans)])).render(strip, frame);
+}
+
+fn truncate(label: &str) -> String {
+    if label.width() <= LABEL_WIDTH {
+        return label.to_string();
+    }
+    let mut out = String::new();
+    let mut width = 0;
+    for c in label.chars() {
+        width += c.width().unwrap_or(0);
+        if width >= LABEL_WIDTH {
+            break;
+        }
+        out.push(c);
+    }
+    out.push('…');
+    out
 }
@@ -1,61 +1,631 @@
-use crate::app::{AppState, LogMode};
+use crate::app::{AppState, LogMode, Panel};
+use crate::replay::ReplayPlayer;
+use crate::log_search::LogSearch;
+use crate::log_store::{Timeline, TimelineLine};
+use crate::scrollback::Scrollback;
+use crate::session::Session;
 use crate::ui::theme;
 use ftui_core::geometry::Rect;
+use ftui_render::cell::{Cell, CellAttrs, CellContent, StyleFlags};
 use ftui_render::frame::Frame;
+use ftui_style::Style;
+use ftui_text::{Line, Span, Text};
 use ftui_widgets::paragraph::Paragraph;
 use ftui_widgets::Widget;
+use std::cell::Ref;
+use std::ops::Range;
+use uuid::Uuid;
 
 pub fn render(state: &AppState, frame: &mut Frame, area: Rect, focused: bool) {
-    let title = match state.log_mode {
-        LogMode::Individual => "Log [individual] (t:toggle)",
-        LogMode::Unified => "Log [unified] (t:toggle)",
+    if let Some(replay) = &state.replay {
+        render_replay(replay, frame, area, focused);
+        return;
+    }
+
+    let base = if state.passthrough {
+        "Log [passthrough] (Ctrl+]:exit)"
+    } else {
+        match state.log_mode {
+            LogMode::Individual => "Log [individual] (t:toggle i:passthrough)",
+            LogMode::Unified => "Log [unified] (t:toggle)",
+            LogMode::Grid => "Log [grid] (g:toggle)",
+        }
     };
+    let mut title = if state.log_zoomed {
+        format!("{} [zoom]", base)
+    } else {
+        base.to_string()
+    };
+    if state.log_mode == LogMode::Unified
+        && let Some(filter) = &state.log_filter
+    {
+        title.push_str(&format!(" [filter: {}]", filter.expr));
+    }
+    let title = title.as_str();
+
+    if state.log_mode == LogMode::Grid {
+        render_grid(state, frame, area, title, focused);
+        return;
+    }
+
+    // Individual mode draws the session's terminal screen when it has one,
+    // unless the log is scrolled back
+    if state.log_mode == LogMode::Individual
+        && let Some(session) = state.sessions.get(state.active_session)
+        && state.log_scroll.get(&session.id).is_none_or(|s| s.is_following())
+        && let Some(screen) = state.terminals.screen(&session.id)
+    {
+        let mut block = theme::panel_block(title, focused);
+        if state.passthrough {
+            block = block.border_style(Style::new().fg(theme::PASSTHROUGH_BORDER));
+        }
+        let inner = block.inner(area);
+        block.render(area, frame);
+        render_screen(screen, frame, inner, focused);
+        return;
+    }
 
     let visible_height = area.height.saturating_sub(2) as usize;
+    let block_title = |position: String| {
+        let mut title = format!("{} {}", title, position);
+        if let Some(search) = state.search.as_ref().filter(|s| !s.query.is_empty()) {
+            let (current, total) = search.counter();
+            title.push_str(&format!(" [search {}/{}]", current, total));
+        }
+        title
+    };
 
-    let content = match state.log_mode {
-        LogMode::Individual => render_individual(state, visible_height),
-        LogMode::Unified => render_unified(state, visible_height),
+    // Unified lines also carry styles for their timestamp and session name
+    let unified = match state.log_mode {
+        LogMode::Unified => Some(unified_lines(state)),
+        _ => None,
+    };
+    let unified = unified.as_deref().unwrap_or_default();
+    let range = match state.log_mode {
+        LogMode::Unified => Some(0..unified.len()),
+        _ => text_range(state),
+    };
+    let Some(range) = range.filter(|range| !range.is_empty()) else {
+        let placeholder = if state.log_mode == LogMode::Individual && state.sessions.is_empty() {
+            "(セッション未選択)"
+        } else {
+            "(ログ出力なし)"
+        };
+        Paragraph::new(placeholder)
+            .block(theme::panel_block(title, focused))
+            .style(theme::placeholder_style())
+            .render(area, frame);
+        return;
     };
 
-    let is_empty = content.starts_with('(');
-    let mut paragraph = Paragraph::new(content).block(theme::panel_block(title, focused));
-    if is_empty {
-        paragraph = paragraph.style(theme::placeholder_style());
+    let scroll = match state.log_mode {
+        LogMode::Unified => state.unified_scroll,
+        _ => state
+            .sessions
+            .get(state.active_session)
+            .and_then(|s| state.log_scroll.get(&s.id).copied())
+            .unwrap_or_default(),
+    };
+    let visible = scroll.visible(range.start, range.end, visible_height);
+    let search = state.search.as_ref();
+    let selection = match state.visual {
+        Some((Panel::Log, selection)) => Some(selection),
+        _ => None,
+    };
+    let bookmarked = match state.log_mode {
+        LogMode::Individual => state.bookmarked_lines(),
+        _ => Default::default(),
+    };
+    let bookmark_style = Style::new().fg(theme::BOOKMARK_FG).bold();
+    let text = Text::from_lines(visible.clone().map(|n| {
+        let marked;
+        let (line, base) = match unified.get(n) {
+            Some(line) => (line.text.as_str(), line.styles.as_slice()),
+            None if bookmarked.contains(&n) => {
+                let line = individual_line(state, n);
+                marked = [(0..line.len(), bookmark_style)];
+                (line, &marked[..])
+            }
+            None => (individual_line(state, n), &[][..]),
+        };
+        let selected = selection.and_then(|s| s.byte_range(n, line));
+        highlighted_line(line, n, search, base, selected)
+    }));
+    let mut position = position_label(&scroll, &visible, range.end);
+    // Scrolled back as far as the log file goes, with older lines rotated out
+    let rotated = state.log_mode == LogMode::Individual
+        && visible.start == range.start
+        && state
+            .sessions
+            .get(state.active_session)
+            .and_then(|s| state.log_history.get(&s.id))
+            .is_some_and(|history| history.rotated());
+    if rotated {
+        position.push_str(" [これより前はローテーション済み: e で書き出し]");
     }
-    paragraph.render(area, frame);
+    let title = block_title(position);
+    Paragraph::new(text)
+        .block(theme::panel_block(&title, focused))
+        .render(area, frame);
 }
 
-fn render_individual(state: &AppState, visible_height: usize) -> String {
-    if let Some(session) = state.sessions.get(state.active_session) {
-        let lines = state.log_store.lines(&session.id);
-        if lines.is_empty() {
-            return "(ログ出力なし)".to_string();
+/// Line numbers of the scrollable text view in the current mode (None in grid
+/// mode). Individual mode starts with the lines that can be paged in from the
+/// log file, before those still in memory.
+pub fn text_range(state: &AppState) -> Option<Range<usize>> {
+    match state.log_mode {
+        LogMode::Grid => None,
+        LogMode::Unified => Some(0..unified_lines(state).len()),
+        LogMode::Individual => {
+            let session = state.sessions.get(state.active_session)?;
+            let first = state.log_store.first_line(&session.id);
+            let end = first + state.log_store.lines(&session.id).len();
+            let start = state
+                .log_history
+                .get(&session.id)
+                .map_or(first, |history| history.range(first).start);
+            Some(start..end)
         }
-        let start = lines.len().saturating_sub(visible_height);
-        lines[start..].join("\n")
+    }
+}
+
+/// A line of the active session's log; lines from the file that aren't paged
+/// in yet are blank until the next update loads them.
+pub fn individual_line(state: &AppState, n: usize) -> &str {
+    let Some(session) = state.sessions.get(state.active_session) else {
+        return "";
+    };
+    let first = state.log_store.first_line(&session.id);
+    let line = match n.checked_sub(first) {
+        Some(i) => state.log_store.lines(&session.id).get(i).map(String::as_str),
+        None => state
+            .log_history
+            .get(&session.id)
+            .and_then(|history| history.line(n)),
+    };
+    line.unwrap_or_default()
+}
+
+/// A style applied to a byte range of a line.
+type StyledRange = (Range<usize>, Style);
+
+/// A log line with the selection and search matches highlighted (the current
+/// hit stands out). `base` styles byte ranges of the line outside them.
+fn highlighted_line(
+    line: &str,
+    number: usize,
+    search: Option<&LogSearch>,
+    base: &[StyledRange],
+    selected: Option<Range<usize>>,
+) -> Line {
+    let selection_style = Style::new().fg(theme::HIGHLIGHT_FG).bg(theme::SELECTION_BG);
+    // A selected line with nothing under the selection still shows it
+    if let Some(range) = selected.as_ref().filter(|r| r.is_empty()) {
+        return Line::from_spans([
+            Span::raw(&line[..range.start]),
+            Span::styled(" ", selection_style),
+        ]);
+    }
+    let matches = search.map_or_else(Vec::new, |s| s.matches_in(line));
+    if matches.is_empty() && base.is_empty() && selected.is_none() {
+        return Line::raw(line);
+    }
+    let current = search
+        .and_then(|s| s.current())
+        .filter(|hit| hit.line == number)
+        .map(|hit| hit.start);
+
+    // Cut the line wherever the selection, a match or a base range starts or ends
+    let mut cuts: Vec<usize> = matches
+        .iter()
+        .chain(&selected)
+        .chain(base.iter().map(|(range, _)| range))
+        .flat_map(|range| [range.start, range.end])
+        .chain([0, line.len()])
+        .collect();
+    cuts.sort_unstable();
+    cuts.dedup();
+
+    let spans = cuts.windows(2).map(|w| {
+        let (start, end) = (w[0], w[1]);
+        let style = if selected.as_ref().is_some_and(|r| r.contains(&start)) {
+            selection_style
+        } else if let Some(m) = matches.iter().find(|m| m.contains(&start)) {
+            let bg = if current == Some(m.start) {
+                theme::SEARCH_CURRENT_BG
+            } else {
+                theme::SEARCH_MATCH_BG
+            };
+            Style::new().fg(theme::HIGHLIGHT_FG).bg(bg)
+        } else if let Some((_, style)) = base.iter().find(|(r, _)| r.contains(&start)) {
+            *style
+        } else {
+            return Span::raw(&line[start..end]);
+        };
+        Span::styled(&line[start..end], style)
+    });
+    Line::from_spans(spans.collect::<Vec<_>>())
+}
+
+/// A recording played back through the same cell renderer as live terminals.
+fn render_replay(replay: &ReplayPlayer, frame: &mut Frame, area: Rect, focused: bool) {
+    let mut title = format!(
+        "Replay {} {} {}x {} / {}",
+        replay.title(),
+        if replay.is_playing() { "▶" } else { "⏸" },
+        replay.speed(),
+        format_time(replay.position()),
+        format_time(replay.duration()),
+    );
+    if let Some(status) = replay.status() {
+        title.push_str(&format!(" [{}]", status));
+    }
+    if let Some(instruction) = replay.instruction() {
+        title.push_str(&format!(" > {}", instruction));
+    }
+    let block = theme::panel_block(&title, focused);
+    let inner = block.inner(area);
+    block.render(area, frame);
+    if let Some(screen) = replay.screen() {
+        render_screen(screen, frame, inner, false);
+    }
+}
+
+/// `m:ss`, or `h:mm:ss` for recordings over an hour.
+fn format_time(seconds: f64) -> String {
+    let total = seconds as u64;
+    let (h, m, s) = (total / 3600, total / 60 % 60, total % 60);
+    if h > 0 {
+        format!("{}:{:02}:{:02}", h, m, s)
     } else {
-        "(セッション未選択)".to_string()
+        format!("{}:{:02}", m, s)
+    }
+}
+
+/// Content area of the log panel (inside its border).
+pub fn inner_area(area: Rect) -> Rect {
+    theme::panel_block("", false).inner(area)
+}
+
+/// Area each session's terminal is drawn into for the given log panel area.
+/// PTYs are sized to these so the agent wraps its output at the visible width.
+pub fn terminal_viewports(state: &AppState, area: Rect) -> Vec<(Uuid, Rect)> {
+    let inner = inner_area(area);
+    match state.log_mode {
+        LogMode::Grid => {
+            let sessions = grid_sessions(state);
+            let tiles = grid_tiles(inner, sessions.len());
+            sessions
+                .iter()
+                .zip(tiles)
+                .map(|(s, tile)| (s.id, inner_area(tile)))
+                .collect()
+        }
+        _ => state.sessions.iter().map(|s| (s.id, inner)).collect(),
+    }
+}
+
+/// Sessions shown as tiles in grid mode: those with a terminal screen.
+fn grid_sessions(state: &AppState) -> Vec<&Session> {
+    state
+        .sessions
+        .iter()
+        .filter(|s| state.terminals.screen(&s.id).is_some())
+        .collect()
+}
+
+/// Split `area` into `n` tiles, filling rows left to right.
+fn grid_tiles(area: Rect, n: usize) -> Vec<Rect> {
+    if n == 0 {
+        return Vec::new();
+    }
+    let cols = (n as f64).sqrt().ceil() as u32;
+    let rows = (n as u32).div_ceil(cols);
+    let (x, y, w, h) = (
+        area.x as u32,
+        area.y as u32,
+        area.width as u32,
+        area.height as u32,
+    );
+    (0..n as u32)
+        .map(|i| {
+            let (r, c) = (i / cols, i % cols);
+            let x0 = x + w * c / cols;
+            let x1 = x + w * (c + 1) / cols;
+            let y0 = y + h * r / rows;
+            let y1 = y + h * (r + 1) / rows;
+            Rect::new(x0 as u16, y0 as u16, (x1 - x0) as u16, (y1 - y0) as u16)
+        })
+        .collect()
+}
+
+fn render_grid(state: &AppState, frame: &mut Frame, area: Rect, title: &str, focused: bool) {
+    let block = theme::panel_block(title, focused);
+    let inner = block.inner(area);
+    block.render(area, frame);
+
+    let sessions = grid_sessions(state);
+    if sessions.is_empty() {
+        Paragraph::new("(ターミナルなし)")
+            .style(theme::placeholder_style())
+            .render(inner, frame);
+        return;
+    }
+
+    let active_id = state.sessions.get(state.active_session).map(|s| s.id);
+    for (session, tile) in sessions.iter().zip(grid_tiles(inner, sessions.len())) {
+        let is_active = active_id == Some(session.id);
+        let label = format!("{} {}", session.status.icon(), session.name);
+        let tile_block = theme::panel_block(&label, focused && is_active);
+        let tile_inner = tile_block.inner(tile);
+        tile_block.render(tile, frame);
+        if let Some(screen) = state.terminals.screen(&session.id) {
+            render_screen(screen, frame, tile_inner, focused && is_active);
+        }
     }
 }
 
-fn render_unified(state: &AppState, visible_height: usize) -> String {
-    let mut all_lines: Vec<String> = Vec::new();
+/// Draw a vt100 screen cell-by-cell. When the panel is shorter than the
+/// terminal, the rows around the cursor are kept in view.
+fn render_screen(screen: &vt100::Screen, frame: &mut Frame, area: Rect, show_cursor: bool) {
+    let (rows, cols) = screen.size();
+    let (cursor_row, cursor_col) = screen.cursor_position();
+    let top = (cursor_row + 1)
+        .saturating_sub(area.height)
+        .min(rows.saturating_sub(area.height));
+    let show_cursor = show_cursor && !screen.hide_cursor();
 
-    for session in &state.sessions {
-        let lines = state.log_store.lines(&session.id);
-        let prefix = &session.name;
-        for line in lines {
-            if !line.is_empty() {
-                all_lines.push(format!("[{}] {}", prefix, line));
+    for y in 0..area.height.min(rows - top) {
+        let row = top + y;
+        for x in 0..area.width.min(cols) {
+            let Some(cell) = screen.cell(row, x) else {
+                continue;
+            };
+            if cell.is_wide_continuation() {
+                continue;
             }
+            let is_cursor = show_cursor && row == cursor_row && x == cursor_col;
+            let rendered = to_cell(cell, is_cursor, frame);
+            frame.buffer.set(area.x + x, area.y + y, rendered);
         }
     }
+}
+
+fn to_cell(cell: &vt100::Cell, is_cursor: bool, frame: &mut Frame) -> Cell {
+    let contents = cell.contents();
+    let mut chars = contents.chars();
+    let content = match (chars.next(), chars.next()) {
+        (None, _) => CellContent::from_char(' '),
+        (Some(c), None) => CellContent::from_char(c),
+        _ => CellContent::from_grapheme(frame.intern(contents)),
+    };
 
-    if all_lines.is_empty() {
-        return "(ログ出力なし)".to_string();
+    let mut flags = StyleFlags::empty();
+    if cell.bold() {
+        flags |= StyleFlags::BOLD;
+    }
+    if cell.dim() {
+        flags |= StyleFlags::DIM;
+    }
+    if cell.italic() {
+        flags |= StyleFlags::ITALIC;
+    }
+    if cell.underline() {
+        flags |= StyleFlags::UNDERLINE;
+    }
+    if cell.inverse() != is_cursor {
+        flags |= StyleFlags::REVERSE;
     }
 
-    let start = all_lines.len().saturating_sub(visible_height);
-    all_lines[start..].join("\n")
+    Cell::new(content)
+        .with_fg(theme::term_color(cell.fgcolor()))
+        .with_bg(theme::term_color(cell.bgcolor()))
+        .with_attrs(CellAttrs::new(flags, CellAttrs::LINK_ID_NONE))
+}
+
+/// Scroll position for the panel title, e.g. `[120-160/5000 paused]`.
+fn position_label(scroll: &Scrollback, visible: &Range<usize>, total: usize) -> String {
+    format!(
+        "[{}-{}/{}{}]",
+        visible.start + 1,
+        visible.end,
+        total,
+        if scroll.is_following() { "" } else { " paused" }
+    )
+}
+
+/// A line of the unified log as drawn, with styles for its prefix: the
+/// timestamp dimmed and the session name in the session's color.
+pub struct UnifiedLine {
+    pub text: String,
+    styles: Vec<StyledRange>,
+}
+
+impl AsRef<str> for UnifiedLine {
+    fn as_ref(&self) -> &str {
+        &self.text
+    }
+}
+
+/// The unified log, kept between frames: output is merged in as it arrives
+/// and the filter runs over new lines only, or over everything again once the
+/// filter itself changes.
+#[derive(Default)]
+pub struct UnifiedLog {
+    /// Filter, timestamps and session names the lines were made with; when
+    /// any of them changes the log is rebuilt
+    made_with: (Option<String>, bool, Vec<String>),
+    timeline: Timeline<UnifiedLine>,
+}
+
+impl UnifiedLog {
+    fn update(&mut self, state: &AppState) {
+        let filter = state.log_filter.as_ref();
+        let sessions: Vec<&Session> = state
+            .sessions
+            .iter()
+            .filter(|s| filter.is_none_or(|f| f.matches_session(s)))
+            .collect();
+        let made_with = (
+            filter.map(|f| f.expr.clone()),
+            state.show_timestamps,
+            sessions.iter().map(|s| s.name.clone()).collect(),
+        );
+        if made_with != self.made_with {
+            self.made_with = made_with;
+            self.timeline = Timeline::default();
+        }
+        let ids: Vec<Uuid> = sessions.iter().map(|s| s.id).collect();
+        self.timeline.sync(&state.log_store, &ids, |line| {
+            if filter.is_some_and(|f| !f.matches_line(line.text)) {
+                return None;
+            }
+            let name = sessions
+                .iter()
+                .find(|s| s.id == line.session_id)
+                .map_or("", |s| s.name.as_str());
+            let mut text = String::new();
+            let mut styles = Vec::new();
+            if state.show_timestamps {
+                text.push_str(&timestamp(&line));
+                styles.push((0..text.len(), Style::new().fg(theme::HINT_FG)));
+                text.push(' ');
+            }
+            let name_start = text.len();
+            text.push_str(&format!("[{}]", name));
+            let color = theme::session_color(&line.session_id);
+            styles.push((name_start..text.len(), Style::new().fg(color).bold()));
+            text.push(' ');
+            text.push_str(line.text);
+            Some(UnifiedLine { text, styles })
+        });
+    }
+}
+
+/// Every session's non-empty lines in order of arrival, prefixed with the
+/// session name (and the arrival time when timestamps are shown).
+pub fn unified_lines(state: &AppState) -> Ref<'_, [UnifiedLine]> {
+    // Already borrowed further up the stack: that borrow saw the latest lines
+    if let Ok(mut log) = state.unified_log.try_borrow_mut() {
+        log.update(state);
+    }
+    Ref::map(state.unified_log.borrow(), |log| log.timeline.items())
+}
+
+fn timestamp(line: &TimelineLine) -> String {
+    line.arrived
+        .with_timezone(&chrono::Local)
+        .format("%H:%M:%S")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::Backend;
+    use crate::config::AppConfig;
+    use crate::log_filter::LogFilter;
+
+    /// A client with `sessions` sessions, the first `with_screens` of which have output.
+    fn state(dir: &std::path::Path, sessions: usize, with_screens: usize) -> AppState {
+        let config = AppConfig::in_dir(dir);
+        let mut state = AppState::new(config.clone(), Backend::local(config));
+        for i in 0..sessions {
+            let session = Session::new(format!("s{}", i), dir.to_path_buf(), &dir.join("logs"));
+            if i < with_screens {
+                state.terminals.process(session.id, b"$ ");
+            }
+            state.sessions.push(session);
+        }
+        state
+    }
+
+    fn rect(x: u16, y: u16, width: u16, height: u16) -> Rect {
+        Rect::new(x, y, width, height)
+    }
+
+    #[test]
+    fn grid_tiles_fill_rows_left_to_right() {
+        let area = rect(2, 1, 80, 24);
+        assert!(grid_tiles(area, 0).is_empty());
+        assert_eq!(grid_tiles(area, 1), [area]);
+        assert_eq!(grid_tiles(area, 2), [rect(2, 1, 40, 24), rect(42, 1, 40, 24)]);
+        // Three sessions leave the last cell of a 2x2 grid empty
+        assert_eq!(
+            grid_tiles(area, 3),
+            [rect(2, 1, 40, 12), rect(42, 1, 40, 12), rect(2, 13, 40, 12)]
+        );
+        assert_eq!(grid_tiles(area, 4)[3], rect(42, 13, 40, 12));
+        // Five or more: three columns, the remainder spread over the tiles
+        let five = grid_tiles(area, 5);
+        assert_eq!(
+            five.iter().map(|t| t.width).collect::<Vec<_>>(),
+            [26, 27, 27, 26, 27]
+        );
+        assert_eq!(five[4], rect(28, 13, 27, 12));
+        assert_eq!(grid_tiles(area, 10).len(), 10);
+    }
+
+    #[test]
+    fn grid_tiles_stay_inside_tiny_areas() {
+        let area = rect(0, 0, 3, 1);
+        let tiles = grid_tiles(area, 5);
+        assert_eq!(tiles.len(), 5);
+        for tile in &tiles {
+            assert!(tile.x + tile.width <= 3 && tile.y + tile.height <= 1, "{:?}", tile);
+        }
+        // The first row gets no height at all
+        assert_eq!(tiles[0].height, 0);
+        assert!(grid_tiles(rect(0, 0, 0, 0), 2).iter().all(|t| t.width == 0 && t.height == 0));
+    }
+
+    #[test]
+    fn unified_log_filters_only_new_output() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let mut state = state(tmp.path(), 2, 0);
+        let (a, b) = (state.sessions[0].id, state.sessions[1].id);
+        state.log_store.append(a, b"error: one\nok\n");
+        state.log_store.append(b, b"error: two\n");
+        let texts = |state: &AppState| -> Vec<String> {
+            unified_lines(state).iter().map(|l| l.text.clone()).collect()
+        };
+        assert_eq!(texts(&state), ["[s0] error: one", "[s0] ok", "[s1] error: two"]);
+
+        state.log_filter = Some(LogFilter::parse("error -two", &Default::default()).unwrap());
+        assert_eq!(texts(&state), ["[s0] error: one"]);
+        state.log_store.append(b, b"error: three\nfine\n");
+        assert_eq!(texts(&state), ["[s0] error: one", "[s1] error: three"]);
+
+        state.sessions[1].name = "renamed".to_string();
+        assert_eq!(texts(&state)[1], "[renamed] error: three");
+        state.log_filter = None;
+        assert_eq!(texts(&state).len(), 5);
+    }
+
+    #[test]
+    fn viewports_follow_the_log_mode() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let mut state = state(tmp.path(), 3, 2);
+        let area = rect(0, 0, 82, 26);
+
+        // Every session's terminal gets the whole panel inside the border
+        let viewports = terminal_viewports(&state, area);
+        assert_eq!(viewports.len(), 3);
+        assert!(viewports.iter().all(|(_, v)| *v == rect(1, 1, 80, 24)));
+
+        // Grid: only sessions with a screen, each inside its tile's border
+        state.log_mode = LogMode::Grid;
+        let viewports = terminal_viewports(&state, area);
+        assert_eq!(
+            viewports,
+            [
+                (state.sessions[0].id, rect(2, 2, 38, 22)),
+                (state.sessions[1].id, rect(42, 2, 38, 22)),
+            ]
+        );
+
+        // Too small for a border: nothing is left to draw into
+        let viewports = terminal_viewports(&state, rect(0, 0, 3, 3));
+        assert!(viewports.iter().all(|(_, v)| v.width <= 1 && v.height <= 1));
+    }
 }
@@ -1,7 +1,9 @@
+pub mod bookmark_picker;
 pub mod dir_tree_panel;
 pub mod file_panel;
 pub mod input_bar;
 pub mod log_panel;
+pub mod session_details;
 pub mod session_list;
 pub mod status_bar;
 pub mod theme;
@@ -0,0 +1,88 @@
+use crate::app::AppState;
+use crate::needs_input::NeedsInputReason;
+use crate::ui::theme;
+use ftui_core::geometry::Rect;
+use ftui_render::frame::Frame;
+use ftui_widgets::paragraph::Paragraph;
+use ftui_widgets::Widget;
+
+pub fn render(state: &AppState, frame: &mut Frame, area: Rect, focused: bool) {
+    let title = "Session (i:close)";
+
+    let Some(session) = state.sessions.get(state.active_session) else {
+        let paragraph = Paragraph::new("(セッション未選択)")
+            .style(theme::placeholder_style())
+            .block(theme::panel_block(title, focused));
+        paragraph.render(area, frame);
+        return;
+    };
+
+    let launch = &session.launch;
+    let env = if launch.env.is_empty() {
+        "-".to_string()
+    } else {
+        launch
+            .env
+            .iter()
+            // Values are often API keys: show only that one is set
+            .map(|(k, _)| format!("{}=••••", k))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let env_file = launch
+        .env_file
+        .as_ref()
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|| "-".to_string());
+    let pid = session
+        .pty_pid
+        .map(|p| p.to_string())
+        .unwrap_or_else(|| "-".to_string());
+    let conversation = session.conversation_id.as_deref().unwrap_or("-");
+    let profile = match &session.needs_input_profile {
+        Some(name) => name.clone(),
+        None => format!("{} (自動)", state.config.default_profile_for(&launch.program)),
+    };
+    let reason = match &session.needs_input_reason {
+        Some(NeedsInputReason::Pattern { pattern, text }) => {
+            format!("パターン /{}/ が \"{}\" に一致", pattern, text.trim())
+        }
+        Some(NeedsInputReason::Dialog { text }) => format!("選択ダイアログ \"{}\"", text),
+        Some(NeedsInputReason::Timeout { secs, unchecked: false }) => format!("{}秒間出力なし", secs),
+        Some(NeedsInputReason::Timeout { secs, unchecked: true }) => {
+            format!("{}秒間出力なし（プロセスの状態は確認できず）", secs)
+        }
+        Some(NeedsInputReason::Manual) => "手動 (m)".to_string(),
+        None => "-".to_string(),
+    };
+    let busy = session
+        .busy
+        .as_ref()
+        .map(|p| format!(" (出力なし・{} が動作中)", p))
+        .unwrap_or_default();
+    let exit_code = session
+        .exit_code
+        .map(|c| c.to_string())
+        .unwrap_or_else(|| "-".to_string());
+
+    let lines = [
+        format!("名前:         {}", session.name),
+        format!("状態:         {} {:?}{}", session.status.icon(), session.status, busy),
+        format!("ディレクトリ: {}", session.root_path.display()),
+        format!("コマンド:     {}", launch.command_line()),
+        format!("環境変数:     {}", env),
+        format!("envファイル:  {}", env_file),
+        format!("会話ID:       {}", conversation),
+        format!("入力待ち検知: {}", profile),
+        format!("入力待ち理由: {}", reason),
+        format!("PID:          {}", pid),
+        format!("終了コード:   {}", exit_code),
+        format!("作成:         {}", session.created_at.format("%Y-%m-%d %H:%M:%S")),
+        format!("更新:         {}", session.updated_at.format("%Y-%m-%d %H:%M:%S")),
+        format!("ログ:         {}", session.log_path.display()),
+        format!("ID:           {}", session.id),
+    ];
+
+    let paragraph = Paragraph::new(lines.join("\n")).block(theme::panel_block(title, focused));
+    paragraph.render(area, frame);
+}
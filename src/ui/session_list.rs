@@ -3,6 +3,7 @@ use crate::ui::theme;
 use ftui_core::geometry::Rect;
 use ftui_render::frame::Frame;
 use ftui_style::Style;
+use ftui_text::{Line, Span, Text};
 use ftui_widgets::borders::Borders;
 use ftui_widgets::list::{List, ListItem, ListState};
 use ftui_widgets::StatefulWidget;
@@ -19,7 +20,16 @@ pub fn render(state: &AppState, frame: &mut Frame, area: Rect, focused: bool) {
                 .map(|info| format!(" [{}/{}]", info.done, info.total))
                 .unwrap_or_default();
             let label = format!("[{}] {} {}{}", i + 1, s.status.icon(), s.name, br_suffix);
-            ListItem::new(label)
+            // Why it is waiting, or what keeps a quiet session busy, after the name
+            let (hint, color) = match (&s.needs_input_reason, &s.busy) {
+                (Some(reason), _) => (format!(" {}", reason.label()), theme::STATUS_NEEDS_INPUT),
+                (None, Some(process)) => (format!(" busy: {}", process), theme::HINT_FG),
+                (None, None) => (String::new(), theme::HINT_FG),
+            };
+            ListItem::new(Text::from_lines([Line::from_spans([
+                Span::raw(label),
+                Span::styled(hint, Style::new().fg(color)),
+            ])]))
         })
         .collect();
 
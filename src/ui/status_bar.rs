@@ -10,10 +10,10 @@ use ftui_widgets::Widget;
 
 fn panel_hints(panel: Panel) -> &'static str {
     match panel {
-        Panel::SessionList => "↑↓:選択 n:新規 d:削除 r:名変 m:入力切替",
+        Panel::SessionList => "↑↓:選択 n:新規 d:削除 s:停止 r:名変 m:入力切替 i:詳細 p:再生 e:書き出し",
         Panel::DirTree => "↑↓:移動 Enter:開く h:隠しファイル",
-        Panel::FilePreview => "↑↓:スクロール e:エディタで開く",
-        Panel::Log => "t:個別/統合切替",
+        Panel::FilePreview => "↑↓:スクロール v:選択 e:エディタで開く",
+        Panel::Log => "t:個別/統合切替 g:グリッド z:ズーム F:フィルタ T:時刻 i:キー直接送信 PgUp/PgDn:スクロール f:追従 /:検索 n/N:次/前 v:選択 b:ブックマーク B:一覧 [/]:前/次",
         Panel::Input => "Enter:送信 ↑↓:履歴 Esc:戻る",
     }
 }
@@ -35,7 +35,21 @@ pub fn render(state: &AppState, frame: &mut Frame, area: Rect) {
         }
     }
 
-    let hints = panel_hints(state.active_panel);
+    let hints = if let Some(notice) = &state.notice {
+        notice.as_str()
+    } else if state.disconnected {
+        "サーバーとの接続が切れました q:終了"
+    } else if state.passthrough {
+        "キー入力をセッションへ直接送信中 Ctrl+]:終了"
+    } else if state.bookmark_pick.is_some() {
+        "↑↓:選択 Enter:移動 d:削除 Esc:閉じる"
+    } else if state.visual.is_some() {
+        "↑↓←→/hjkl:移動 PgUp/PgDn g/G:先頭/末尾 v:行選択 Ctrl+V:矩形選択 y/Enter:コピー Esc:キャンセル"
+    } else if state.replay.is_some() {
+        "Space:再生/停止 ←→:5秒 ↑↓:1分 0-9:位置 [ ]:指示 n/N:状態変化 +/-:速度 Esc:閉じる"
+    } else {
+        panel_hints(state.active_panel)
+    };
     let hint_style = Style::new().fg(theme::HINT_FG);
     let dim_style = Style::new().fg(theme::HINT_FG).dim();
 
@@ -22,12 +22,42 @@ pub const HINT_FG: PackedRgba = PackedRgba::rgb(140, 140, 160);
 pub const HIGHLIGHT_BG: PackedRgba = PackedRgba::rgb(0, 180, 180);
 pub const HIGHLIGHT_FG: PackedRgba = PackedRgba::rgb(0, 0, 0);
 
+// ── 統合ログのセッション名（セッション ID ごとに固定） ──
+const SESSION_COLORS: [PackedRgba; 8] = [
+    PackedRgba::rgb(80, 200, 120),
+    PackedRgba::rgb(100, 160, 255),
+    PackedRgba::rgb(255, 150, 90),
+    PackedRgba::rgb(200, 130, 255),
+    PackedRgba::rgb(80, 210, 210),
+    PackedRgba::rgb(230, 210, 90),
+    PackedRgba::rgb(255, 110, 160),
+    PackedRgba::rgb(160, 220, 90),
+];
+
+/// セッションの表示色（同じセッションは再起動後も同じ色）
+pub fn session_color(id: &uuid::Uuid) -> PackedRgba {
+    SESSION_COLORS[(id.as_u128() % SESSION_COLORS.len() as u128) as usize]
+}
+
+// ── ログ検索 ──
+pub const SEARCH_MATCH_BG: PackedRgba = PackedRgba::rgb(200, 170, 60);
+pub const SEARCH_CURRENT_BG: PackedRgba = PackedRgba::rgb(255, 120, 60);
+
+// ── 選択範囲（コピー用） ──
+pub const SELECTION_BG: PackedRgba = PackedRgba::rgb(120, 170, 255);
+
+// ── ブックマークした行 ──
+pub const BOOKMARK_FG: PackedRgba = PackedRgba::rgb(255, 215, 95);
+
 // ── プレースホルダー ──
 pub const PLACEHOLDER: PackedRgba = PackedRgba::rgb(100, 100, 110);
 
 // ── ダイアログ ──
 pub const DIALOG_BORDER: PackedRgba = PackedRgba::rgb(255, 200, 60);
 
+// ── パススルー（キー直接送信中） ──
+pub const PASSTHROUGH_BORDER: PackedRgba = PackedRgba::rgb(255, 120, 60);
+
 /// フォーカス状態に応じた角丸ボーダー付き Block を返す
 pub fn panel_block(title: &str, focused: bool) -> Block<'_> {
     panel_block_with(title, focused, Borders::ALL)
@@ -70,3 +100,43 @@ pub fn junction_panel_block(title: &str, focused: bool) -> Block<'_> {
 pub fn placeholder_style() -> Style {
     Style::new().fg(PLACEHOLDER).dim()
 }
+
+// ── ターミナル出力 (ANSI 16色) ──
+const ANSI_PALETTE: [PackedRgba; 16] = [
+    PackedRgba::rgb(0, 0, 0),
+    PackedRgba::rgb(205, 49, 49),
+    PackedRgba::rgb(13, 188, 121),
+    PackedRgba::rgb(229, 229, 16),
+    PackedRgba::rgb(36, 114, 200),
+    PackedRgba::rgb(188, 63, 188),
+    PackedRgba::rgb(17, 168, 205),
+    PackedRgba::rgb(229, 229, 229),
+    PackedRgba::rgb(102, 102, 102),
+    PackedRgba::rgb(241, 76, 76),
+    PackedRgba::rgb(35, 209, 139),
+    PackedRgba::rgb(245, 245, 67),
+    PackedRgba::rgb(59, 142, 234),
+    PackedRgba::rgb(214, 112, 214),
+    PackedRgba::rgb(41, 184, 219),
+    PackedRgba::rgb(255, 255, 255),
+];
+
+/// vt100 の色を描画色に変換する（Default は端末既定色として TRANSPARENT を返す）
+pub fn term_color(color: vt100::Color) -> PackedRgba {
+    match color {
+        vt100::Color::Default => PackedRgba::TRANSPARENT,
+        vt100::Color::Rgb(r, g, b) => PackedRgba::rgb(r, g, b),
+        vt100::Color::Idx(i) if i < 16 => ANSI_PALETTE[i as usize],
+        vt100::Color::Idx(i) if i < 232 => {
+            // 6x6x6 カラーキューブ
+            let i = i - 16;
+            let level = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+            PackedRgba::rgb(level(i / 36), level((i / 6) % 6), level(i % 6))
+        }
+        vt100::Color::Idx(i) => {
+            // グレースケール
+            let v = 8 + (i - 232) * 10;
+            PackedRgba::rgb(v, v, v)
+        }
+    }
+}